@@ -0,0 +1,319 @@
+/*
+
+Picking the hash algorithm at runtime, e.g. from a config file or a
+command line flag, rather than at compile time by calling into one of
+the algorithm modules directly.
+
+Names are matched ignoring case and the separators people tend to put in
+them, with or without the family, so "SHA-256", "sha256", "SHA2-256" and
+"sha2_256" are all SHA-256, and "sha512/256" and "SHA-512_256" are both
+SHA-512/256.
+
+*/
+
+use crate::{sha224, sha256, sha384, sha512, sha512_224, sha512_256};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Algorithm {
+    Sha224,
+    Sha256,
+    Sha384,
+    Sha512,
+    Sha512_224,
+    Sha512_256,
+}
+
+impl Algorithm {
+    pub const ALL: [Algorithm; 6] = [
+        Algorithm::Sha224,
+        Algorithm::Sha256,
+        Algorithm::Sha384,
+        Algorithm::Sha512,
+        Algorithm::Sha512_224,
+        Algorithm::Sha512_256,
+    ];
+
+    // the name used in FIPS 180-4
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::Sha224 => "SHA-224",
+            Algorithm::Sha256 => "SHA-256",
+            Algorithm::Sha384 => "SHA-384",
+            Algorithm::Sha512 => "SHA-512",
+            Algorithm::Sha512_224 => "SHA-512/224",
+            Algorithm::Sha512_256 => "SHA-512/256",
+        }
+    }
+
+    // the ASN.1 object identifier, from the NIST algorithms arc
+    // 2.16.840.1.101.3.4.2 (see RFC 8017 appendix B.1)
+    pub fn oid(self) -> &'static str {
+        match self {
+            Algorithm::Sha224 => "2.16.840.1.101.3.4.2.4",
+            Algorithm::Sha256 => "2.16.840.1.101.3.4.2.1",
+            Algorithm::Sha384 => "2.16.840.1.101.3.4.2.2",
+            Algorithm::Sha512 => "2.16.840.1.101.3.4.2.3",
+            Algorithm::Sha512_224 => "2.16.840.1.101.3.4.2.5",
+            Algorithm::Sha512_256 => "2.16.840.1.101.3.4.2.6",
+        }
+    }
+
+    // the size of the digest, in bytes
+    pub fn output_size(self) -> usize {
+        match self {
+            Algorithm::Sha224 | Algorithm::Sha512_224 => 28,
+            Algorithm::Sha256 | Algorithm::Sha512_256 => 32,
+            Algorithm::Sha384 => 48,
+            Algorithm::Sha512 => 64,
+        }
+    }
+
+    // the size of a message block, in bytes
+    pub fn block_size(self) -> usize {
+        match self {
+            Algorithm::Sha224 | Algorithm::Sha256 => 64,
+            _ => 128,
+        }
+    }
+
+    pub fn hasher(self) -> Box<dyn DynHasher> {
+        match self {
+            Algorithm::Sha224 => Box::new(sha224::Sha224::new()),
+            Algorithm::Sha256 => Box::new(sha256::Sha256::new()),
+            Algorithm::Sha384 => Box::new(sha384::Sha384::new()),
+            Algorithm::Sha512 => Box::new(sha512::Sha512::new()),
+            Algorithm::Sha512_224 => Box::new(sha512_224::Sha512_224::new()),
+            Algorithm::Sha512_256 => Box::new(sha512_256::Sha512_256::new()),
+        }
+    }
+
+    // unlike the hash functions in the algorithm modules, this goes
+    // through the streaming hasher and so also accepts an empty message
+    pub fn hash(self, msg: &[u8]) -> Vec<u8> {
+        let mut hasher = self.hasher();
+        hasher.update(msg);
+        hasher.finalize()
+    }
+
+    // hash a file, memory-mapping it where possible, see crate::file
+    pub fn hash_file<P: AsRef<std::path::Path>>(
+        self,
+        path: P,
+    ) -> Result<Vec<u8>, crate::file::FileError> {
+        crate::file::hash_file(path.as_ref(), self.hasher())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ParseAlgorithmError(String);
+
+impl fmt::Display for ParseAlgorithmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown hash algorithm {:?}", self.0)
+    }
+}
+
+impl Error for ParseAlgorithmError {}
+
+impl FromStr for Algorithm {
+    type Err = ParseAlgorithmError;
+
+    fn from_str(s: &str) -> Result<Algorithm, ParseAlgorithmError> {
+        let name: String = s
+            .trim()
+            .chars()
+            .filter(|c| !matches!(c, '-' | '_' | '/' | ' '))
+            .collect::<String>()
+            .to_lowercase();
+        // the family, as in SHA2-256
+        let name = match name.strip_prefix("sha2") {
+            Some(size) if size.len() >= 3 => format!("sha{}", size),
+            _ => name,
+        };
+
+        match name.as_str() {
+            "sha224" => Ok(Algorithm::Sha224),
+            "sha256" => Ok(Algorithm::Sha256),
+            "sha384" => Ok(Algorithm::Sha384),
+            "sha512" => Ok(Algorithm::Sha512),
+            "sha512224" => Ok(Algorithm::Sha512_224),
+            "sha512256" => Ok(Algorithm::Sha512_256),
+            _ => Err(ParseAlgorithmError(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+// by name, so that an algorithm can be given in a config file
+#[cfg(feature = "serde")]
+impl serde::Serialize for Algorithm {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Algorithm {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Algorithm, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+// A streaming hasher for an algorithm only known at runtime. The digest
+// comes back as bytes, Algorithm::output_size of them.
+pub trait DynHasher: Send + Sync {
+    fn algorithm(&self) -> Algorithm;
+    fn update(&mut self, msg: &[u8]);
+    fn finalize(self: Box<Self>) -> Vec<u8>;
+    fn reset(&mut self);
+    fn box_clone(&self) -> Box<dyn DynHasher>;
+}
+
+macro_rules! dyn_hasher {
+    ($hasher:ty, $algorithm:expr) => {
+        impl DynHasher for $hasher {
+            fn algorithm(&self) -> Algorithm {
+                $algorithm
+            }
+
+            fn update(&mut self, msg: &[u8]) {
+                <$hasher>::update(self, msg)
+            }
+
+            fn finalize(self: Box<Self>) -> Vec<u8> {
+                <$hasher>::finalize(*self).as_ref().to_vec()
+            }
+
+            fn reset(&mut self) {
+                <$hasher>::reset(self)
+            }
+
+            fn box_clone(&self) -> Box<dyn DynHasher> {
+                Box::new(self.clone())
+            }
+        }
+    };
+}
+
+dyn_hasher!(sha224::Sha224, Algorithm::Sha224);
+dyn_hasher!(sha256::Sha256, Algorithm::Sha256);
+dyn_hasher!(sha384::Sha384, Algorithm::Sha384);
+dyn_hasher!(sha512::Sha512, Algorithm::Sha512);
+dyn_hasher!(sha512_224::Sha512_224, Algorithm::Sha512_224);
+dyn_hasher!(sha512_256::Sha512_256, Algorithm::Sha512_256);
+
+impl Clone for Box<dyn DynHasher> {
+    fn clone(&self) -> Box<dyn DynHasher> {
+        self.box_clone()
+    }
+}
+
+impl crate::StreamHasher for Box<dyn DynHasher> {
+    type Output = Vec<u8>;
+
+    fn update(&mut self, msg: &[u8]) {
+        DynHasher::update(self.as_mut(), msg)
+    }
+
+    fn finalize(self) -> Vec<u8> {
+        DynHasher::finalize(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Algorithm;
+
+    #[test]
+    fn test_algorithm_from_str() {
+        let names = [
+            ("SHA-256", Algorithm::Sha256),
+            ("sha256", Algorithm::Sha256),
+            ("SHA2-256", Algorithm::Sha256),
+            ("sha2_256", Algorithm::Sha256),
+            ("sha-224", Algorithm::Sha224),
+            ("SHA384", Algorithm::Sha384),
+            ("sha512", Algorithm::Sha512),
+            ("sha512/224", Algorithm::Sha512_224),
+            ("sha512/256", Algorithm::Sha512_256),
+            ("SHA-512/256", Algorithm::Sha512_256),
+            ("SHA2-512/256", Algorithm::Sha512_256),
+            ("sha512_256", Algorithm::Sha512_256),
+        ];
+        for (name, algorithm) in names.iter() {
+            assert_eq!(name.parse(), Ok(*algorithm), "{}", name);
+        }
+
+        for algorithm in Algorithm::ALL.iter() {
+            assert_eq!(algorithm.to_string().parse(), Ok(*algorithm));
+        }
+
+        for name in ["", "sha", "sha2", "sha1", "md5", "sha512/384", "sha3-256"].iter() {
+            assert!(name.parse::<Algorithm>().is_err(), "{}", name);
+        }
+        assert_eq!(
+            "sha1".parse::<Algorithm>().unwrap_err().to_string(),
+            "unknown hash algorithm \"sha1\""
+        );
+    }
+
+    #[test]
+    fn test_algorithm_hasher() {
+        let msg: Vec<u8> = (0..300u32).map(|i| (i * 7 % 251) as u8).collect();
+
+        for algorithm in Algorithm::ALL.iter() {
+            let mut hasher = algorithm.hasher();
+            assert_eq!(hasher.algorithm(), *algorithm);
+
+            hasher.update(&msg[..100]);
+            let clone = hasher.clone();
+            hasher.update(&msg[100..]);
+            let digest = hasher.finalize();
+            assert_eq!(digest.len(), algorithm.output_size());
+            assert_eq!(digest, algorithm.hash(&msg));
+            assert_ne!(clone.finalize(), digest);
+
+            let mut hasher = algorithm.hasher();
+            hasher.update(b"abc");
+            hasher.reset();
+            assert_eq!(hasher.finalize(), algorithm.hash(b""));
+        }
+
+        assert_eq!(
+            crate::encoding::hex(&Algorithm::Sha512_256.hash(b"abc")),
+            "53048e2681941ef99b2e29b76b4c7dabe4c2d0c634fc6d46e0e2f13107e7af23"
+        );
+        assert_eq!(
+            Algorithm::Sha256.hash(b"abc"),
+            crate::sha256::hash(b"abc").unwrap().as_ref()
+        );
+        assert_eq!(Algorithm::Sha384.block_size(), 128);
+        assert_eq!(Algorithm::Sha224.block_size(), 64);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_algorithm_serde() {
+        #[derive(serde::Deserialize)]
+        struct Config {
+            algorithm: Algorithm,
+        }
+
+        let config: Config = serde_json::from_str(r#"{"algorithm": "sha512/256"}"#).unwrap();
+        assert_eq!(config.algorithm, Algorithm::Sha512_256);
+        assert!(serde_json::from_str::<Config>(r#"{"algorithm": "md5"}"#).is_err());
+        assert_eq!(
+            serde_json::to_string(&Algorithm::Sha384).unwrap(),
+            "\"SHA-384\""
+        );
+    }
+}
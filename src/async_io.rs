@@ -0,0 +1,144 @@
+/*
+
+Async adapters for the streaming hashers, built on the tokio AsyncRead and
+AsyncWrite traits. Hashing is cheap compared to the I/O around it, so the
+bytes are hashed inline as they pass through rather than on a blocking
+thread.
+
+ - HashSink is an AsyncWrite which hashes everything written to it
+ - HashReader wraps an AsyncRead and hashes the bytes as they are read, so
+   a stream can be hashed while it is being copied somewhere else
+
+*/
+
+use crate::StreamHasher;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+pub struct HashSink<H> {
+    hasher: H,
+}
+
+impl<H: StreamHasher> HashSink<H> {
+    pub fn new(hasher: H) -> HashSink<H> {
+        HashSink { hasher }
+    }
+
+    pub fn finalize(self) -> H::Output {
+        self.hasher.finalize()
+    }
+}
+
+impl<H: StreamHasher + Unpin> AsyncWrite for HashSink<H> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().hasher.update(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+pub struct HashReader<R, H> {
+    inner: R,
+    hasher: H,
+}
+
+impl<R, H: StreamHasher> HashReader<R, H> {
+    pub fn new(inner: R, hasher: H) -> HashReader<R, H> {
+        HashReader { inner, hasher }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    // hands back the wrapped reader along with the hash of everything
+    // which has been read through it
+    pub fn finalize(self) -> (R, H::Output) {
+        (self.inner, self.hasher.finalize())
+    }
+}
+
+impl<R: AsyncRead + Unpin, H: StreamHasher + Unpin> AsyncRead for HashReader<R, H> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let already_filled = buf.filled().len();
+
+        match Pin::new(&mut this.inner).poll_read(cx, buf) {
+            Poll::Ready(Ok(())) => {
+                this.hasher.update(&buf.filled()[already_filled..]);
+                Poll::Ready(Ok(()))
+            }
+            other => other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HashReader, HashSink};
+    use crate::{sha256, sha512};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn message() -> Vec<u8> {
+        (0..10_000u32).map(|i| (i * 31 % 256) as u8).collect()
+    }
+
+    #[tokio::test]
+    async fn test_hash_sink() {
+        let msg = message();
+        let (mut client, mut server) = tokio::io::duplex(64);
+
+        let writer = tokio::spawn(async move {
+            for chunk in msg.chunks(100) {
+                client.write_all(chunk).await.unwrap();
+            }
+        });
+
+        let mut sink = HashSink::new(sha256::Sha256::new());
+        tokio::io::copy(&mut server, &mut sink).await.unwrap();
+        writer.await.unwrap();
+
+        assert_eq!(Some(sink.finalize()), sha256::hash(&message()));
+    }
+
+    #[tokio::test]
+    async fn test_hash_reader() {
+        let msg = message();
+        let (mut client, server) = tokio::io::duplex(64);
+
+        let writer = tokio::spawn(async move {
+            client.write_all(&msg).await.unwrap();
+        });
+
+        // the bytes read through the wrapper are unchanged, and hashed on the way
+        let mut reader = HashReader::new(server, sha512::Sha512::new());
+        let mut copied = Vec::new();
+        reader.read_to_end(&mut copied).await.unwrap();
+        writer.await.unwrap();
+
+        let (_, hash) = reader.finalize();
+        assert_eq!(copied, message());
+        assert_eq!(Some(hash), sha512::hash(&message()));
+    }
+}
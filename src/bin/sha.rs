@@ -0,0 +1,80 @@
+use sha_hash::algorithm::Algorithm;
+use sha_hash::trace;
+use std::io::Read;
+use std::process;
+
+const USAGE: &str = "Usage: sha trace [--algorithm ALGORITHM] [--format text|json|html] [MESSAGE]
+
+Walks MESSAGE through the preprocessing and hash computation, printing the
+padded message, the parsed blocks, the message schedule, the working
+variables after every round and the final additions. The message is read
+from stdin if it isn't given.
+
+ALGORITHM is one of sha224, sha256 (the default), sha384, sha512,
+sha512/224 and sha512/256.";
+
+fn fail(msg: &str) -> ! {
+    eprintln!("{}\n\n{}", msg, USAGE);
+    process::exit(2);
+}
+
+fn trace(args: &[String]) {
+    let mut algorithm = "sha256".to_string();
+    let mut format = "text".to_string();
+    let mut message = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-a" | "--algorithm" => match args.next() {
+                Some(value) => algorithm = value.clone(),
+                None => fail("--algorithm needs a value"),
+            },
+            "-f" | "--format" => match args.next() {
+                Some(value) => format = value.to_lowercase(),
+                None => fail("--format needs a value"),
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if message.is_none() => message = Some(arg.clone().into_bytes()),
+            _ => fail(&format!("unexpected argument: {}", arg)),
+        }
+    }
+
+    let message = message.unwrap_or_else(|| {
+        let mut stdin = Vec::new();
+        if let Err(err) = std::io::stdin().read_to_end(&mut stdin) {
+            eprintln!("failed to read stdin: {}", err);
+            process::exit(1);
+        }
+        stdin
+    });
+
+    let algorithm = match algorithm.parse::<Algorithm>() {
+        Ok(algorithm) => algorithm,
+        Err(_) => fail(&format!("unknown algorithm: {}", algorithm)),
+    };
+    let trace = match trace::trace(algorithm, &message) {
+        Some(trace) => trace,
+        None => fail("the message must not be empty"),
+    };
+
+    match format.as_str() {
+        "text" => print!("{}", trace.to_text()),
+        "json" => println!("{}", trace.to_json()),
+        "html" => print!("{}", trace.to_html()),
+        _ => fail(&format!("unknown format: {}", format)),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(|arg| arg.as_str()) {
+        Some("trace") => trace(&args[1..]),
+        Some("-h") | Some("--help") => println!("{}", USAGE),
+        Some(command) => fail(&format!("unknown command: {}", command)),
+        None => fail("missing command"),
+    }
+}
@@ -7,9 +7,9 @@ fn main() {
                  everyone you ever heard of, every human being who ever was, lived out their lives. -Carl Sagan";
     println!("Message is: {}", msg);
 
-    let hashes_256 = sha256::hash(&msg.as_bytes());
-    println!("SHA-256 hash of message is: {:#x?}", hashes_256.unwrap());
+    let hashes_256 = sha256::hash(msg.as_bytes());
+    println!("SHA-256 hash of message is: {}", hashes_256.unwrap());
 
-    let hashes_512 = sha512::hash(&msg.as_bytes());
-    println!("SHA-512 hash of message is: {:#x?}", hashes_512.unwrap());
+    let hashes_512 = sha512::hash(msg.as_bytes());
+    println!("SHA-512 hash of message is: {}", hashes_512.unwrap());
 }
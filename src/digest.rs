@@ -0,0 +1,401 @@
+/*
+
+The message digests returned by the hash functions, one type for each
+algorithm.
+
+A digest is kept as its big-endian byte string, i.e. the hash words
+H0..H7 one after another, which is how digests are written down as hex
+and how they are fed to anything further up the stack. The truncated
+variants (SHA-224, SHA-384, SHA-512/t) keep the leftmost bytes only, so
+only SHA-256 and SHA-512 digests convert to and from words.
+
+Equality is constant time, since digests are regularly compared against
+values supplied by someone else, e.g. MACs. ct_eq is the same comparison
+under a name which makes that explicit at the call site.
+
+*/
+
+use crate::encoding;
+use std::array::TryFromSliceError;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, PartialEq)]
+pub enum ParseDigestError {
+    // the length of the string in bytes, which has to be exactly twice the
+    // digest size
+    InvalidLength { expected: usize, found: usize },
+    InvalidCharacter { index: usize, character: char },
+}
+
+impl fmt::Display for ParseDigestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseDigestError::InvalidLength { expected, found } => write!(
+                f,
+                "invalid digest length: expected {} hex digits, found {}",
+                expected, found
+            ),
+            ParseDigestError::InvalidCharacter { index, character } => {
+                write!(f, "invalid hex digit {:?} at position {}", character, index)
+            }
+        }
+    }
+}
+
+impl Error for ParseDigestError {}
+
+macro_rules! digest {
+    ($name:ident, $len:expr) => {
+        #[derive(Clone, Copy, Eq)]
+        pub struct $name([u8; $len]);
+
+        impl $name {
+            pub const LEN: usize = $len;
+
+            pub fn from_bytes(bytes: [u8; $len]) -> $name {
+                $name(bytes)
+            }
+
+            pub fn as_bytes(&self) -> &[u8; $len] {
+                &self.0
+            }
+
+            pub fn to_hex(&self) -> String {
+                encoding::hex(&self.0)
+            }
+
+            // standard alphabet, padded
+            pub fn to_base64(&self) -> String {
+                encoding::base64(&self.0)
+            }
+
+            // URL and filename safe alphabet, unpadded
+            pub fn to_base64_url(&self) -> String {
+                encoding::base64_url(&self.0)
+            }
+
+            pub fn ct_eq(&self, other: &$name) -> bool {
+                crate::util::ct_eq(&self.0, &other.0)
+            }
+        }
+
+        impl PartialEq for $name {
+            fn eq(&self, other: &$name) -> bool {
+                self.ct_eq(other)
+            }
+        }
+
+        impl std::hash::Hash for $name {
+            fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+                self.0.hash(state)
+            }
+        }
+
+        impl AsRef<[u8]> for $name {
+            fn as_ref(&self) -> &[u8] {
+                &self.0
+            }
+        }
+
+        impl TryFrom<&[u8]> for $name {
+            type Error = TryFromSliceError;
+
+            fn try_from(bytes: &[u8]) -> Result<$name, TryFromSliceError> {
+                <[u8; $len]>::try_from(bytes).map($name)
+            }
+        }
+
+        impl fmt::LowerHex for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                if f.alternate() {
+                    f.write_str("0x")?;
+                }
+                f.write_str(&encoding::hex(&self.0))
+            }
+        }
+
+        impl fmt::UpperHex for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                if f.alternate() {
+                    f.write_str("0x")?;
+                }
+                f.write_str(&encoding::hex_upper(&self.0))
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                fmt::LowerHex::fmt(self, f)
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}({})", stringify!($name), self.to_hex())
+            }
+        }
+
+        // hex in either case, without a 0x prefix
+        impl FromStr for $name {
+            type Err = ParseDigestError;
+
+            fn from_str(s: &str) -> Result<$name, ParseDigestError> {
+                // checked first, so that nothing longer is decoded
+                if s.len() != $len * 2 {
+                    return Err(ParseDigestError::InvalidLength {
+                        expected: $len * 2,
+                        found: s.len(),
+                    });
+                }
+                let bytes =
+                    encoding::decode_hex(s).map_err(|encoding::InvalidHex(index, character)| {
+                        ParseDigestError::InvalidCharacter { index, character }
+                    })?;
+                let mut digest = [0u8; $len];
+                digest.copy_from_slice(&bytes);
+                Ok($name(digest))
+            }
+        }
+
+        // hex in human readable formats such as JSON, raw bytes otherwise
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for $name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                if serializer.is_human_readable() {
+                    serializer.serialize_str(&self.to_hex())
+                } else {
+                    serializer.serialize_bytes(&self.0)
+                }
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de> serde::Deserialize<'de> for $name {
+            fn deserialize<D: serde::Deserializer<'de>>(
+                deserializer: D,
+            ) -> Result<$name, D::Error> {
+                struct DigestVisitor;
+
+                impl<'de> serde::de::Visitor<'de> for DigestVisitor {
+                    type Value = $name;
+
+                    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                        write!(f, "a {} byte digest as hex or bytes", $len)
+                    }
+
+                    fn visit_str<E: serde::de::Error>(self, s: &str) -> Result<$name, E> {
+                        s.parse().map_err(E::custom)
+                    }
+
+                    fn visit_bytes<E: serde::de::Error>(self, bytes: &[u8]) -> Result<$name, E> {
+                        if bytes.len() != $len {
+                            return Err(E::invalid_length(bytes.len(), &self));
+                        }
+                        let mut digest = [0u8; $len];
+                        digest.copy_from_slice(bytes);
+                        Ok($name(digest))
+                    }
+
+                    fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                        self,
+                        mut seq: A,
+                    ) -> Result<$name, A::Error> {
+                        let mut digest = [0u8; $len];
+                        for (i, byte) in digest.iter_mut().enumerate() {
+                            *byte = seq
+                                .next_element()?
+                                .ok_or_else(|| serde::de::Error::invalid_length(i, &self))?;
+                        }
+                        if seq.next_element::<u8>()?.is_some() {
+                            return Err(serde::de::Error::invalid_length($len + 1, &self));
+                        }
+                        Ok($name(digest))
+                    }
+                }
+
+                if deserializer.is_human_readable() {
+                    deserializer.deserialize_str(DigestVisitor)
+                } else {
+                    deserializer.deserialize_bytes(DigestVisitor)
+                }
+            }
+        }
+    };
+}
+
+// conversions to and from the hash words, for the digests which are the
+// whole of the final hash value
+macro_rules! digest_words {
+    ($name:ident, $len:expr, $word:ty, $word_len:expr) => {
+        impl $name {
+            pub fn from_words(words: [$word; 8]) -> $name {
+                let mut bytes = [0u8; $len];
+                for (chunk, word) in bytes.chunks_mut($word_len).zip(words.iter()) {
+                    chunk.copy_from_slice(&word.to_be_bytes());
+                }
+                $name(bytes)
+            }
+
+            pub fn words(&self) -> [$word; 8] {
+                let mut words = [0; 8];
+                for (word, chunk) in words.iter_mut().zip(self.0.chunks($word_len)) {
+                    let mut buf = [0u8; $word_len];
+                    buf.copy_from_slice(chunk);
+                    *word = <$word>::from_be_bytes(buf);
+                }
+                words
+            }
+        }
+
+        impl From<[$word; 8]> for $name {
+            fn from(words: [$word; 8]) -> $name {
+                $name::from_words(words)
+            }
+        }
+    };
+}
+
+digest!(Sha224Digest, 28);
+digest!(Sha256Digest, 32);
+digest!(Sha384Digest, 48);
+digest!(Sha512Digest, 64);
+digest!(Sha512_224Digest, 28);
+digest!(Sha512_256Digest, 32);
+
+digest_words!(Sha256Digest, 32, u32, 4);
+digest_words!(Sha512Digest, 64, u64, 8);
+
+#[cfg(test)]
+mod tests {
+    use super::{ParseDigestError, Sha256Digest, Sha512Digest};
+    use crate::{sha256, sha512};
+
+    const ABC_256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    #[test]
+    fn test_digest_formatting() {
+        let digest = sha256::hash(b"abc").unwrap();
+        assert_eq!(digest.to_string(), ABC_256);
+        assert_eq!(format!("{:x}", digest), ABC_256);
+        assert_eq!(format!("{:#x}", digest), format!("0x{}", ABC_256));
+        assert_eq!(format!("{:X}", digest), ABC_256.to_uppercase());
+        assert_eq!(
+            digest.to_base64(),
+            "ungWv48Bz+pBQUDeXa4iI7ADYaOWF3qctBD/YfIAFa0="
+        );
+        assert_eq!(
+            digest.to_base64_url(),
+            "ungWv48Bz-pBQUDeXa4iI7ADYaOWF3qctBD_YfIAFa0"
+        );
+        assert_eq!(digest.as_ref()[..4], [0xba, 0x78, 0x16, 0xbf]);
+        assert_eq!(digest.words()[0], 0xba7816bf);
+        assert_eq!(Sha256Digest::from(digest.words()), digest);
+
+        let digest = sha512::hash(b"abc").unwrap();
+        assert!(digest.to_string().starts_with("ddaf35a193617aba"));
+        assert_eq!(digest.as_bytes().len(), Sha512Digest::LEN);
+        assert_eq!(digest.words()[7], 0x2a9ac94fa54ca49f);
+    }
+
+    #[test]
+    fn test_digest_from_str() {
+        let digest: Sha256Digest = ABC_256.parse().unwrap();
+        assert_eq!(Some(digest), sha256::hash(b"abc"));
+        assert_eq!(ABC_256.to_uppercase().parse::<Sha256Digest>(), Ok(digest));
+
+        let digest = sha512::hash(b"abc").unwrap();
+        assert_eq!(digest.to_string().parse::<Sha512Digest>(), Ok(digest));
+
+        assert_eq!(
+            "abcd".parse::<Sha256Digest>(),
+            Err(ParseDigestError::InvalidLength {
+                expected: 64,
+                found: 4
+            })
+        );
+        assert_eq!(
+            ABC_256.parse::<Sha512Digest>(),
+            Err(ParseDigestError::InvalidLength {
+                expected: 128,
+                found: 64
+            })
+        );
+        // the length is checked before the digits
+        assert_eq!(
+            "abc".parse::<Sha256Digest>(),
+            Err(ParseDigestError::InvalidLength {
+                expected: 64,
+                found: 3
+            })
+        );
+        assert_eq!(
+            "x".repeat(1000).parse::<Sha256Digest>(),
+            Err(ParseDigestError::InvalidLength {
+                expected: 64,
+                found: 1000
+            })
+        );
+        assert_eq!(
+            format!("{}\u{e9}", &ABC_256[1..]).parse::<Sha256Digest>(),
+            Err(ParseDigestError::InvalidLength {
+                expected: 64,
+                found: 65
+            })
+        );
+        assert_eq!(
+            ABC_256.replace("a9c", "a9g").parse::<Sha256Digest>(),
+            Err(ParseDigestError::InvalidCharacter {
+                index: 47,
+                character: 'g'
+            })
+        );
+        assert_eq!(
+            format!("0x{}", &ABC_256[2..]).parse::<Sha256Digest>(),
+            Err(ParseDigestError::InvalidCharacter {
+                index: 1,
+                character: 'x'
+            })
+        );
+    }
+
+    #[test]
+    fn test_digest_ct_eq() {
+        let digest = sha256::hash(b"abc").unwrap();
+        let mut bytes = *digest.as_bytes();
+        assert!(digest.ct_eq(&Sha256Digest::from_bytes(bytes)));
+
+        bytes[31] ^= 1;
+        assert!(!digest.ct_eq(&Sha256Digest::from_bytes(bytes)));
+        assert_ne!(digest, Sha256Digest::from_bytes(bytes));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_digest_serde() {
+        let digest = sha256::hash(b"abc").unwrap();
+
+        let json = serde_json::to_string(&digest).unwrap();
+        assert_eq!(json, format!("\"{}\"", ABC_256));
+        assert_eq!(serde_json::from_str::<Sha256Digest>(&json).unwrap(), digest);
+        assert!(serde_json::from_str::<Sha256Digest>("\"abcd\"").is_err());
+
+        let bytes = bincode::serialize(&digest).unwrap();
+        assert_eq!(bytes.len(), 8 + 32);
+        assert_eq!(&bytes[8..], digest.as_bytes());
+        assert_eq!(
+            bincode::deserialize::<Sha256Digest>(&bytes).unwrap(),
+            digest
+        );
+        assert!(bincode::deserialize::<Sha512Digest>(&bytes).is_err());
+
+        let digest = sha512::hash(b"abc").unwrap();
+        let bytes = bincode::serialize(&digest).unwrap();
+        assert_eq!(
+            bincode::deserialize::<Sha512Digest>(&bytes).unwrap(),
+            digest
+        );
+    }
+}
@@ -0,0 +1,982 @@
+/*
+
+HTTP Digest access authentication from RFC 7616, with SHA-256 and
+SHA-512/256 (the "SHA-512-256" of the RFC) and their -sess variants.
+MD5, still the default when a challenge has no algorithm, isn't in this
+crate, so those challenges are skipped.
+
+With H the hash in lower case hex, and KD(secret, data) = H(secret ":"
+data), the response to a challenge is
+
+    response = KD(H(A1), nonce ":" nc ":" cnonce ":" qop ":" H(A2))
+    A1 = username ":" realm ":" password
+       (-sess: H(username ":" realm ":" password) ":" nonce ":" cnonce)
+    A2 = method ":" uri
+       (auth-int: method ":" uri ":" H(body))
+
+Servers can keep H(username ":" realm ":" password), which ha1 returns,
+rather than the password. With userhash the username is sent as
+H(username ":" realm), and a username which can't go in a quoted string
+is sent as username* in the RFC 8187 encoding.
+
+The server has to check that the nonce is one it issued and is still
+fresh, and that nc goes up, so that responses can't be replayed. The
+uri the response covers is checked against the request by verify.
+
+*/
+
+use crate::algorithm::Algorithm;
+use crate::encoding;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DigestAlgorithm {
+    Sha256,
+    Sha256Sess,
+    Sha512_256,
+    Sha512_256Sess,
+}
+
+impl DigestAlgorithm {
+    pub fn name(self) -> &'static str {
+        match self {
+            DigestAlgorithm::Sha256 => "SHA-256",
+            DigestAlgorithm::Sha256Sess => "SHA-256-sess",
+            DigestAlgorithm::Sha512_256 => "SHA-512-256",
+            DigestAlgorithm::Sha512_256Sess => "SHA-512-256-sess",
+        }
+    }
+
+    pub fn algorithm(self) -> Algorithm {
+        match self {
+            DigestAlgorithm::Sha256 | DigestAlgorithm::Sha256Sess => Algorithm::Sha256,
+            DigestAlgorithm::Sha512_256 | DigestAlgorithm::Sha512_256Sess => Algorithm::Sha512_256,
+        }
+    }
+
+    pub fn is_session(self) -> bool {
+        matches!(
+            self,
+            DigestAlgorithm::Sha256Sess | DigestAlgorithm::Sha512_256Sess
+        )
+    }
+
+    fn h(self, data: &str) -> String {
+        encoding::hex(&self.algorithm().hash(data.as_bytes()))
+    }
+}
+
+impl FromStr for DigestAlgorithm {
+    type Err = DigestError;
+
+    fn from_str(s: &str) -> Result<DigestAlgorithm, DigestError> {
+        [
+            DigestAlgorithm::Sha256,
+            DigestAlgorithm::Sha256Sess,
+            DigestAlgorithm::Sha512_256,
+            DigestAlgorithm::Sha512_256Sess,
+        ]
+        .iter()
+        .find(|alg| alg.name().eq_ignore_ascii_case(s))
+        .copied()
+        .ok_or_else(|| DigestError::UnsupportedAlgorithm(s.to_string()))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Qop {
+    Auth,
+    // the body is covered as well
+    AuthInt,
+}
+
+impl Qop {
+    pub fn name(self) -> &'static str {
+        match self {
+            Qop::Auth => "auth",
+            Qop::AuthInt => "auth-int",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DigestError {
+    // a header which can't be parsed, or lacks a required parameter
+    Malformed,
+    UnsupportedAlgorithm(String),
+    // none of the challenge's qop values can be used
+    UnsupportedQop,
+    // an Authorization which isn't for the challenge
+    ChallengeMismatch,
+    // an Authorization whose uri isn't the request's
+    UriMismatch,
+    InvalidResponse,
+    // nc has run out, so a new challenge is needed
+    NonceExhausted,
+}
+
+impl fmt::Display for DigestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DigestError::Malformed => f.write_str("malformed Digest header"),
+            DigestError::UnsupportedAlgorithm(name) => write!(f, "unsupported algorithm {}", name),
+            DigestError::UnsupportedQop => f.write_str("no supported quality of protection"),
+            DigestError::ChallengeMismatch => {
+                f.write_str("the credentials aren't for the challenge")
+            }
+            DigestError::UriMismatch => f.write_str("the uri isn't that of the request"),
+            DigestError::InvalidResponse => f.write_str("invalid response"),
+            DigestError::NonceExhausted => f.write_str("the nonce has been used too often"),
+        }
+    }
+}
+
+impl Error for DigestError {}
+
+// H(username ":" realm ":" password), which a server can store in place
+// of the password
+pub fn ha1(alg: DigestAlgorithm, username: &str, realm: &str, password: &str) -> String {
+    alg.h(&format!("{}:{}:{}", username, realm, password))
+}
+
+// what's sent as the username when the challenge asks for userhash
+pub fn user_hash(alg: DigestAlgorithm, username: &str, realm: &str) -> String {
+    alg.h(&format!("{}:{}", username, realm))
+}
+
+// A WWW-Authenticate challenge
+#[derive(Clone, Debug, PartialEq)]
+pub struct Challenge {
+    realm: String,
+    nonce: String,
+    opaque: Option<String>,
+    algorithm: DigestAlgorithm,
+    qop: Vec<Qop>,
+    stale: bool,
+    userhash: bool,
+}
+
+impl Challenge {
+    // offering qop=auth, for the server to send
+    pub fn new(realm: &str, nonce: &str, algorithm: DigestAlgorithm) -> Challenge {
+        Challenge {
+            realm: realm.to_string(),
+            nonce: nonce.to_string(),
+            opaque: None,
+            algorithm,
+            qop: vec![Qop::Auth],
+            stale: false,
+            userhash: false,
+        }
+    }
+
+    pub fn realm(&self) -> &str {
+        &self.realm
+    }
+
+    pub fn nonce(&self) -> &str {
+        &self.nonce
+    }
+
+    pub fn opaque(&self) -> Option<&str> {
+        self.opaque.as_deref()
+    }
+
+    pub fn set_opaque(&mut self, opaque: &str) {
+        self.opaque = Some(opaque.to_string());
+    }
+
+    pub fn algorithm(&self) -> DigestAlgorithm {
+        self.algorithm
+    }
+
+    pub fn qop(&self) -> &[Qop] {
+        &self.qop
+    }
+
+    pub fn set_qop(&mut self, qop: &[Qop]) {
+        self.qop = qop.to_vec();
+    }
+
+    // the nonce has expired but the credentials were right, so the client
+    // can retry without asking the user again
+    pub fn stale(&self) -> bool {
+        self.stale
+    }
+
+    pub fn set_stale(&mut self, stale: bool) {
+        self.stale = stale;
+    }
+
+    pub fn userhash(&self) -> bool {
+        self.userhash
+    }
+
+    pub fn set_userhash(&mut self, userhash: bool) {
+        self.userhash = userhash;
+    }
+
+    // The Digest challenges in a WWW-Authenticate header, which may hold
+    // challenges of other schemes too, in the order they came in, which is
+    // the server's preference. Those for MD5 are left out.
+    pub fn parse_all(header: &str) -> Result<Vec<Challenge>, DigestError> {
+        let mut challenges = Vec::new();
+        for (scheme, params) in parse_auth_header(header)? {
+            if !scheme.eq_ignore_ascii_case("Digest") {
+                continue;
+            }
+            match Challenge::from_params(&params) {
+                Ok(challenge) => challenges.push(challenge),
+                Err(DigestError::UnsupportedAlgorithm(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(challenges)
+    }
+
+    fn from_params(params: &[(String, String)]) -> Result<Challenge, DigestError> {
+        let algorithm = param(params, "algorithm").unwrap_or("MD5").parse()?;
+        // RFC 2069 challenges have no qop, but RFC 7616 requires it
+        let qop = param(params, "qop")
+            .ok_or(DigestError::Malformed)?
+            .split(',')
+            .filter_map(|qop| match qop.trim() {
+                "auth" => Some(Qop::Auth),
+                "auth-int" => Some(Qop::AuthInt),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if qop.is_empty() {
+            return Err(DigestError::UnsupportedQop);
+        }
+        Ok(Challenge {
+            realm: param(params, "realm")
+                .ok_or(DigestError::Malformed)?
+                .to_string(),
+            nonce: param(params, "nonce")
+                .ok_or(DigestError::Malformed)?
+                .to_string(),
+            opaque: param(params, "opaque").map(str::to_string),
+            algorithm,
+            qop,
+            stale: flag(params, "stale"),
+            userhash: flag(params, "userhash"),
+        })
+    }
+}
+
+impl fmt::Display for Challenge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let qop = self
+            .qop
+            .iter()
+            .map(|qop| qop.name())
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(
+            f,
+            "Digest realm={}, qop={}, algorithm={}, nonce={}",
+            quote(&self.realm),
+            quote(&qop),
+            self.algorithm.name(),
+            quote(&self.nonce)
+        )?;
+        if let Some(opaque) = &self.opaque {
+            write!(f, ", opaque={}", quote(opaque))?;
+        }
+        if self.stale {
+            f.write_str(", stale=true")?;
+        }
+        if self.userhash {
+            f.write_str(", charset=UTF-8, userhash=true")?;
+        }
+        Ok(())
+    }
+}
+
+// The first Digest challenge in a WWW-Authenticate header, MD5 ones
+// being skipped
+impl FromStr for Challenge {
+    type Err = DigestError;
+
+    fn from_str(s: &str) -> Result<Challenge, DigestError> {
+        Challenge::parse_all(s)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| DigestError::UnsupportedAlgorithm("MD5".to_string()))
+    }
+}
+
+// Answers a challenge, counting the requests made with its nonce
+#[derive(Clone, Debug)]
+pub struct DigestClient {
+    challenge: Challenge,
+    username: String,
+    password: String,
+    nc: u32,
+}
+
+impl DigestClient {
+    pub fn new(challenge: Challenge, username: &str, password: &str) -> DigestClient {
+        DigestClient {
+            challenge,
+            username: username.to_string(),
+            password: password.to_string(),
+            nc: 0,
+        }
+    }
+
+    // how many requests have been made with the nonce
+    pub fn nc(&self) -> u32 {
+        self.nc
+    }
+
+    // The Authorization for the next request. The cnonce should be fresh
+    // random for every request, as the client's side of the protection
+    // against chosen plaintext. With a body, auth-int is used if the
+    // server offers it.
+    pub fn authorize(
+        &mut self,
+        method: &str,
+        uri: &str,
+        body: Option<&[u8]>,
+        cnonce: &str,
+    ) -> Result<Authorization, DigestError> {
+        let offered = |qop| self.challenge.qop.contains(&qop);
+        let qop = match body {
+            Some(_) if offered(Qop::AuthInt) => Qop::AuthInt,
+            _ if offered(Qop::Auth) => Qop::Auth,
+            _ => return Err(DigestError::UnsupportedQop),
+        };
+        self.nc = self.nc.checked_add(1).ok_or(DigestError::NonceExhausted)?;
+
+        let challenge = &self.challenge;
+        let alg = challenge.algorithm;
+        let username = if challenge.userhash {
+            user_hash(alg, &self.username, &challenge.realm)
+        } else {
+            self.username.clone()
+        };
+        let mut authorization = Authorization {
+            username,
+            realm: challenge.realm.clone(),
+            uri: uri.to_string(),
+            algorithm: alg,
+            nonce: challenge.nonce.clone(),
+            nc: self.nc,
+            cnonce: cnonce.to_string(),
+            qop,
+            response: String::new(),
+            opaque: challenge.opaque.clone(),
+            userhash: challenge.userhash,
+        };
+        let ha1 = ha1(alg, &self.username, &challenge.realm, &self.password);
+        authorization.response = authorization.expected_response(&ha1, method, body.unwrap_or(b""));
+        Ok(authorization)
+    }
+}
+
+// The Authorization header of a request
+#[derive(Clone, Debug, PartialEq)]
+pub struct Authorization {
+    // H(username ":" realm) when userhash is set
+    pub username: String,
+    pub realm: String,
+    pub uri: String,
+    pub algorithm: DigestAlgorithm,
+    pub nonce: String,
+    pub nc: u32,
+    pub cnonce: String,
+    pub qop: Qop,
+    pub response: String,
+    pub opaque: Option<String>,
+    pub userhash: bool,
+}
+
+impl Authorization {
+    // Whether it's from the user, hashed or not. A server which has
+    // turned on userhash has to look the user up by the hash.
+    pub fn is_user(&self, username: &str) -> bool {
+        if self.userhash {
+            crate::util::ct_eq(
+                self.username.as_bytes(),
+                user_hash(self.algorithm, username, &self.realm).as_bytes(),
+            )
+        } else {
+            self.username == username
+        }
+    }
+
+    // Checks the response for the challenge it answers, given the user's
+    // ha1. The uri is the request-target of the request, which the uri
+    // the response covers has to match (RFC 7616 3.4.6), or the header
+    // could be replayed for another resource. The body is needed for
+    // auth-int. Whether the nonce is fresh, and nc hasn't been seen
+    // before, is for the caller to check.
+    pub fn verify(
+        &self,
+        challenge: &Challenge,
+        method: &str,
+        uri: &str,
+        ha1: &str,
+        body: &[u8],
+    ) -> Result<(), DigestError> {
+        if self.realm != challenge.realm
+            || self.nonce != challenge.nonce
+            || self.opaque != challenge.opaque
+            || self.algorithm != challenge.algorithm
+            || self.userhash != challenge.userhash
+        {
+            return Err(DigestError::ChallengeMismatch);
+        }
+        if self.uri != uri {
+            return Err(DigestError::UriMismatch);
+        }
+        if !challenge.qop.contains(&self.qop) {
+            return Err(DigestError::UnsupportedQop);
+        }
+        let expected = self.expected_response(&ha1.to_ascii_lowercase(), method, body);
+        if !crate::util::ct_eq(expected.as_bytes(), self.response.as_bytes()) {
+            return Err(DigestError::InvalidResponse);
+        }
+        Ok(())
+    }
+
+    fn expected_response(&self, ha1: &str, method: &str, body: &[u8]) -> String {
+        let alg = self.algorithm;
+        let ha1 = if alg.is_session() {
+            alg.h(&format!("{}:{}:{}", ha1, self.nonce, self.cnonce))
+        } else {
+            ha1.to_string()
+        };
+        let ha2 = match self.qop {
+            Qop::Auth => alg.h(&format!("{}:{}", method, self.uri)),
+            Qop::AuthInt => alg.h(&format!(
+                "{}:{}:{}",
+                method,
+                self.uri,
+                encoding::hex(&alg.algorithm().hash(body))
+            )),
+        };
+        alg.h(&format!(
+            "{}:{}:{:08x}:{}:{}:{}",
+            ha1,
+            self.nonce,
+            self.nc,
+            self.cnonce,
+            self.qop.name(),
+            ha2
+        ))
+    }
+}
+
+impl fmt::Display for Authorization {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // a quoted string can only hold ISO 8859-1, and not all of that
+        // makes it through unscathed
+        if self.username.bytes().all(|c| (0x20..0x7f).contains(&c)) {
+            write!(f, "Digest username={}", quote(&self.username))?;
+        } else {
+            write!(
+                f,
+                "Digest username*=UTF-8''{}",
+                encoding::percent_encode(self.username.as_bytes())
+            )?;
+        }
+        write!(
+            f,
+            ", realm={}, uri={}, algorithm={}, nonce={}, nc={:08x}, cnonce={}, qop={}, response={}",
+            quote(&self.realm),
+            quote(&self.uri),
+            self.algorithm.name(),
+            quote(&self.nonce),
+            self.nc,
+            quote(&self.cnonce),
+            self.qop.name(),
+            quote(&self.response)
+        )?;
+        if let Some(opaque) = &self.opaque {
+            write!(f, ", opaque={}", quote(opaque))?;
+        }
+        if self.userhash {
+            f.write_str(", userhash=true")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Authorization {
+    type Err = DigestError;
+
+    fn from_str(s: &str) -> Result<Authorization, DigestError> {
+        let mut auth = parse_auth_header(s)?.into_iter();
+        let (scheme, params) = match (auth.next(), auth.next()) {
+            (Some(only), None) => only,
+            _ => return Err(DigestError::Malformed),
+        };
+        if !scheme.eq_ignore_ascii_case("Digest") {
+            return Err(DigestError::Malformed);
+        }
+        let required = |name| param(&params, name).ok_or(DigestError::Malformed);
+
+        let username = match (param(&params, "username"), param(&params, "username*")) {
+            (Some(username), None) => username.to_string(),
+            (None, Some(username)) => decode_ext_value(username)?,
+            _ => return Err(DigestError::Malformed),
+        };
+        let nc = required("nc")?;
+        if nc.len() != 8 {
+            return Err(DigestError::Malformed);
+        }
+        let userhash = flag(&params, "userhash");
+        if userhash && param(&params, "username*").is_some() {
+            return Err(DigestError::Malformed);
+        }
+        Ok(Authorization {
+            username,
+            realm: required("realm")?.to_string(),
+            uri: required("uri")?.to_string(),
+            algorithm: param(&params, "algorithm").unwrap_or("MD5").parse()?,
+            nonce: required("nonce")?.to_string(),
+            nc: u32::from_str_radix(nc, 16).map_err(|_| DigestError::Malformed)?,
+            cnonce: required("cnonce")?.to_string(),
+            qop: match required("qop")? {
+                "auth" => Qop::Auth,
+                "auth-int" => Qop::AuthInt,
+                _ => return Err(DigestError::UnsupportedQop),
+            },
+            response: required("response")?.to_ascii_lowercase(),
+            opaque: param(&params, "opaque").map(str::to_string),
+            userhash,
+        })
+    }
+}
+
+fn param<'a>(params: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    params
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+// true or false, which is case-insensitive like every token
+fn flag(params: &[(String, String)], name: &str) -> bool {
+    param(params, name).is_some_and(|s| s.eq_ignore_ascii_case("true"))
+}
+
+fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+// UTF-8'language'percent-encoded, from RFC 8187 3.2
+fn decode_ext_value(value: &str) -> Result<String, DigestError> {
+    let mut parts = value.splitn(3, '\'');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(charset), Some(_), Some(encoded)) if charset.eq_ignore_ascii_case("UTF-8") => {
+            let bytes = encoding::percent_decode(encoded).map_err(|_| DigestError::Malformed)?;
+            String::from_utf8(bytes).map_err(|_| DigestError::Malformed)
+        }
+        _ => Err(DigestError::Malformed),
+    }
+}
+
+// The challenges or credentials in a header, as schemes with their
+// auth-params (RFC 7235 2.1). A token68, as Bearer and Basic use, is
+// skipped over.
+type AuthParams = Vec<(String, String)>;
+
+fn parse_auth_header(header: &str) -> Result<Vec<(String, AuthParams)>, DigestError> {
+    let mut reader = AuthReader {
+        s: header.as_bytes(),
+        pos: 0,
+    };
+    let mut schemes: Vec<(String, AuthParams)> = Vec::new();
+    loop {
+        reader.skip_list_separators();
+        if reader.at_end() {
+            return Ok(schemes);
+        }
+        let token = reader.token().ok_or(DigestError::Malformed)?;
+        reader.skip_whitespace();
+        if reader.peek() == Some(b'=') {
+            // another param of the current scheme
+            let (_, params) = schemes.last_mut().ok_or(DigestError::Malformed)?;
+            reader.pos += 1;
+            reader.skip_whitespace();
+            params.push((token, reader.value()?));
+            continue;
+        }
+
+        // a new scheme, followed by its first param or a token68
+        schemes.push((token, Vec::new()));
+        if reader.at_end() {
+            continue;
+        }
+        let name = reader.token().ok_or(DigestError::Malformed)?;
+        reader.skip_whitespace();
+        if reader.peek() == Some(b'=') {
+            reader.pos += 1;
+            reader.skip_whitespace();
+            if let None | Some(b'=') | Some(b',') = reader.peek() {
+                // the padding of a token68
+                while reader.peek() == Some(b'=') {
+                    reader.pos += 1;
+                }
+                reader.skip_whitespace();
+            } else {
+                let (_, params) = schemes.last_mut().unwrap();
+                params.push((name, reader.value()?));
+            }
+        }
+        if !reader.at_end() {
+            return Err(DigestError::Malformed);
+        }
+    }
+}
+
+struct AuthReader<'a> {
+    s: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> AuthReader<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.s.get(self.pos).copied()
+    }
+
+    // the end of the header or of a list element
+    fn at_end(&self) -> bool {
+        matches!(self.peek(), None | Some(b','))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn skip_list_separators(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b',') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    // a token, or the part of a token68 before the padding
+    fn token(&mut self) -> Option<String> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if !(c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~/".contains(&c)) {
+                break;
+            }
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return None;
+        }
+        Some(String::from_utf8_lossy(&self.s[start..self.pos]).into_owned())
+    }
+
+    // a token or quoted string, followed by the end of the param
+    fn value(&mut self) -> Result<String, DigestError> {
+        let value = if self.peek() == Some(b'"') {
+            self.pos += 1;
+            let mut value = Vec::new();
+            loop {
+                match self.peek().ok_or(DigestError::Malformed)? {
+                    b'"' => break,
+                    b'\\' => {
+                        self.pos += 1;
+                        value.push(self.peek().ok_or(DigestError::Malformed)?);
+                    }
+                    c => value.push(c),
+                }
+                self.pos += 1;
+            }
+            self.pos += 1;
+            String::from_utf8(value).map_err(|_| DigestError::Malformed)?
+        } else {
+            self.token().ok_or(DigestError::Malformed)?
+        };
+        self.skip_whitespace();
+        if !self.at_end() {
+            return Err(DigestError::Malformed);
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 7616 3.9.1, both challenges in one header, the MD5 one last
+    const CHALLENGES: &str = "Digest realm=\"http-auth@example.org\", qop=\"auth, auth-int\", \
+                              algorithm=SHA-256, nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", \
+                              opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\", \
+                              Digest realm=\"http-auth@example.org\", qop=\"auth, auth-int\", \
+                              algorithm=MD5, nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", \
+                              opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\"";
+    const CNONCE: &str = "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ";
+
+    #[test]
+    fn test_rfc7616_sha256() {
+        let challenges = Challenge::parse_all(CHALLENGES).unwrap();
+        assert_eq!(challenges.len(), 1);
+        let challenge = &challenges[0];
+        assert_eq!(challenge.realm(), "http-auth@example.org");
+        assert_eq!(challenge.algorithm(), DigestAlgorithm::Sha256);
+        assert_eq!(challenge.qop(), [Qop::Auth, Qop::AuthInt]);
+        assert_eq!(
+            challenge.opaque(),
+            Some("FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS")
+        );
+        assert_eq!(CHALLENGES.parse::<Challenge>().unwrap(), *challenge);
+
+        let mut client = DigestClient::new(challenge.clone(), "Mufasa", "Circle of Life");
+        let authorization = client
+            .authorize("GET", "/dir/index.html", None, CNONCE)
+            .unwrap();
+        let header = authorization.to_string();
+        assert_eq!(
+            header,
+            "Digest username=\"Mufasa\", realm=\"http-auth@example.org\", \
+             uri=\"/dir/index.html\", algorithm=SHA-256, \
+             nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", nc=00000001, \
+             cnonce=\"f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ\", qop=auth, \
+             response=\"753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1\", \
+             opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\""
+        );
+
+        // the server's side
+        let received = header.parse::<Authorization>().unwrap();
+        assert_eq!(received, authorization);
+        assert!(received.is_user("Mufasa"));
+        let ha1 = ha1(
+            DigestAlgorithm::Sha256,
+            "Mufasa",
+            "http-auth@example.org",
+            "Circle of Life",
+        );
+        assert_eq!(
+            received.verify(challenge, "GET", "/dir/index.html", &ha1, b""),
+            Ok(())
+        );
+        assert_eq!(
+            received.verify(challenge, "POST", "/dir/index.html", &ha1, b""),
+            Err(DigestError::InvalidResponse)
+        );
+        let wrong = super::ha1(
+            DigestAlgorithm::Sha256,
+            "Mufasa",
+            "http-auth@example.org",
+            "x",
+        );
+        assert_eq!(
+            received.verify(challenge, "GET", "/dir/index.html", &wrong, b""),
+            Err(DigestError::InvalidResponse)
+        );
+        let mut other = challenge.clone();
+        other.nonce = "another nonce".to_string();
+        assert_eq!(
+            received.verify(&other, "GET", "/dir/index.html", &ha1, b""),
+            Err(DigestError::ChallengeMismatch)
+        );
+
+        // the same header replayed for another resource
+        assert_eq!(
+            received.verify(challenge, "GET", "/dir/secret.html", &ha1, b""),
+            Err(DigestError::UriMismatch)
+        );
+        assert_eq!(
+            received.verify(challenge, "GET", "/dir/index.html?x", &wrong, b""),
+            Err(DigestError::UriMismatch)
+        );
+    }
+
+    // RFC 7616 3.9.2. The username and response printed in the RFC can't
+    // be reproduced from its inputs, so these were computed with Python's
+    // hashlib.
+    #[test]
+    fn test_rfc7616_userhash() {
+        let header = "Digest realm=\"api@example.org\", qop=\"auth\", algorithm=SHA-512-256, \
+                      nonce=\"5TsQWLVdgBdmrQ0XsxbDODV+57QdFR34I9HAbC/RVvkK\", \
+                      opaque=\"HRPCssKJSGjCrkzDg8OhwpzCiGPChXYjwrI2QmXDnsOS\", \
+                      charset=UTF-8, userhash=true";
+        let challenge = header.parse::<Challenge>().unwrap();
+        assert!(challenge.userhash());
+        assert_eq!(challenge.to_string(), header);
+
+        let username = "J\u{e4}s\u{f8}n Doe";
+        let mut client = DigestClient::new(challenge.clone(), username, "Secret, or not?");
+        let authorization = client
+            .authorize(
+                "GET",
+                "/doe.json",
+                None,
+                "NTg6RKcb9boFIAS3KrFK9BGeh+iDa/sm6jUMp2wds69v",
+            )
+            .unwrap();
+        assert_eq!(
+            authorization.username,
+            "793263caabb707a56211940d90411ea4a575adeccb7e360aeb624ed06ece9b0b"
+        );
+        assert_eq!(
+            authorization.response,
+            "3798d4131c277846293534c3edc11bd8a5e4cdcbff78b05db9d95eeb1cec68a5"
+        );
+        assert!(authorization.to_string().ends_with(", userhash=true"));
+
+        let received = authorization.to_string().parse::<Authorization>().unwrap();
+        assert!(received.is_user(username));
+        assert!(!received.is_user("Jason Doe"));
+        let ha1 = ha1(
+            DigestAlgorithm::Sha512_256,
+            username,
+            "api@example.org",
+            "Secret, or not?",
+        );
+        assert_eq!(
+            received.verify(&challenge, "GET", "/doe.json", &ha1, b""),
+            Ok(())
+        );
+
+        // without userhash the name goes in username*
+        let mut challenge = challenge;
+        challenge.set_userhash(false);
+        let mut client = DigestClient::new(challenge, username, "Secret, or not?");
+        let header = client
+            .authorize("GET", "/doe.json", None, "cnonce")
+            .unwrap()
+            .to_string();
+        assert!(header.starts_with("Digest username*=UTF-8''J%C3%A4s%C3%B8n%20Doe, "));
+        assert!(header.parse::<Authorization>().unwrap().is_user(username));
+    }
+
+    // checked against Python's hashlib
+    #[test]
+    fn test_auth_int_sess() {
+        let challenge = CHALLENGES
+            .replace("SHA-256", "SHA-256-sess")
+            .parse::<Challenge>()
+            .unwrap();
+        let mut client = DigestClient::new(challenge.clone(), "Mufasa", "Circle of Life");
+        let body = br#"{"hello":"world"}"#;
+        client
+            .authorize("POST", "/dir/index.html", Some(body), CNONCE)
+            .unwrap();
+        let authorization = client
+            .authorize("POST", "/dir/index.html", Some(body), CNONCE)
+            .unwrap();
+        assert_eq!(client.nc(), 2);
+        assert_eq!(authorization.qop, Qop::AuthInt);
+        assert_eq!(
+            authorization.response,
+            "8248e13d18032300e7e9067fb2109f2545697cf5a74a2f200cc91fb0bdddd460"
+        );
+
+        let ha1 = ha1(
+            DigestAlgorithm::Sha256Sess,
+            "Mufasa",
+            "http-auth@example.org",
+            "Circle of Life",
+        );
+        assert_eq!(
+            authorization.verify(&challenge, "POST", "/dir/index.html", &ha1, body),
+            Ok(())
+        );
+        assert_eq!(
+            authorization.verify(&challenge, "POST", "/dir/index.html", &ha1, b"{}"),
+            Err(DigestError::InvalidResponse)
+        );
+
+        // auth-int only, which needs the body
+        let mut challenge = challenge;
+        challenge.set_qop(&[Qop::AuthInt]);
+        let mut client = DigestClient::new(challenge, "Mufasa", "Circle of Life");
+        assert_eq!(
+            client.authorize("GET", "/", None, CNONCE),
+            Err(DigestError::UnsupportedQop)
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        // other schemes, with a token68 or params, are skipped
+        let challenges = Challenge::parse_all(
+            "Basic realm=\"x\", Bearer dG9rZW4=, Newauth realm=\"apps\", type=1,\
+             title=\"Login to \\\"apps\\\"\" , Digest realm = \"a\\\\b\" ,nonce=n, \
+             qop=\"auth-int, future\", stale=TRUE, algorithm=sha-256",
+        )
+        .unwrap();
+        assert_eq!(challenges.len(), 1);
+        assert_eq!(challenges[0].realm(), "a\\b");
+        assert_eq!(challenges[0].nonce(), "n");
+        assert_eq!(challenges[0].qop(), [Qop::AuthInt]);
+        assert!(challenges[0].stale());
+        assert_eq!(challenges[0].algorithm(), DigestAlgorithm::Sha256);
+
+        assert_eq!(Challenge::parse_all("Basic realm=\"x\"").unwrap(), []);
+        assert_eq!(
+            "Digest realm=\"x\", nonce=\"n\", qop=\"auth\"".parse::<Challenge>(),
+            Err(DigestError::UnsupportedAlgorithm("MD5".to_string()))
+        );
+
+        let mut challenge = Challenge::new("a \"realm\"", "n", DigestAlgorithm::Sha512_256Sess);
+        challenge.set_stale(true);
+        assert_eq!(
+            challenge.to_string(),
+            "Digest realm=\"a \\\"realm\\\"\", qop=\"auth\", algorithm=SHA-512-256-sess, \
+             nonce=\"n\", stale=true"
+        );
+        assert_eq!(
+            challenge.to_string().parse::<Challenge>().unwrap(),
+            challenge
+        );
+
+        for header in [
+            "Digest realm=\"x\", nonce=\"n\", algorithm=SHA-256",
+            "Digest realm=\"x\", qop=\"auth\", algorithm=SHA-256",
+            "Digest realm=\"x, nonce=n, qop=auth, algorithm=SHA-256",
+            "Digest realm=\"x\" nonce=n, qop=auth, algorithm=SHA-256",
+            "realm=\"x\"",
+        ]
+        .iter()
+        {
+            assert!(Challenge::parse_all(header).is_err(), "{}", header);
+        }
+        assert_eq!(
+            "Digest realm=\"x\", nonce=\"n\", algorithm=SHA-256, qop=\"future\""
+                .parse::<Challenge>(),
+            Err(DigestError::UnsupportedQop)
+        );
+
+        for header in [
+            "Basic dXNlcjpwYXNz",
+            "Digest realm=\"x\", uri=\"/\", algorithm=SHA-256, nonce=\"n\", nc=1, \
+             cnonce=\"c\", qop=auth, response=\"r\", username=\"u\"",
+            "Digest username=\"u\", username*=UTF-8''u, realm=\"x\", uri=\"/\", \
+             algorithm=SHA-256, nonce=\"n\", nc=00000001, cnonce=\"c\", qop=auth, \
+             response=\"r\"",
+        ]
+        .iter()
+        {
+            assert_eq!(
+                header.parse::<Authorization>(),
+                Err(DigestError::Malformed),
+                "{}",
+                header
+            );
+        }
+    }
+}
@@ -0,0 +1,562 @@
+/*
+
+The integrity fields of RFC 9530. Content-Digest holds digests of the
+message content, after any content coding, and Repr-Digest digests of
+the selected representation, which is what a range or a HEAD response
+is part of. Both are Structured Field dictionaries (RFC 8941) from
+algorithm to byte sequence:
+
+    Content-Digest: sha-256=:X48E9qOokqqrvdts8nOJRJN3OWDUoyWxBf7kbu9DBPE=:
+
+Want-Content-Digest and Want-Repr-Digest ask for them, weighting each
+algorithm from 1 to 10, with 0 meaning not acceptable:
+
+    Want-Content-Digest: sha-512=3, sha-256=10
+
+Only sha-256 and sha-512 are active in the IANA registry, the others
+being deprecated, so only they are produced or checked; others are
+ignored.
+
+*/
+
+use crate::algorithm::Algorithm;
+use crate::encoding;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+pub const CONTENT_DIGEST: &str = "Content-Digest";
+pub const REPR_DIGEST: &str = "Repr-Digest";
+pub const WANT_CONTENT_DIGEST: &str = "Want-Content-Digest";
+pub const WANT_REPR_DIGEST: &str = "Want-Repr-Digest";
+
+// strongest first
+const SUPPORTED: [(Algorithm, &str); 2] = [
+    (Algorithm::Sha512, "sha-512"),
+    (Algorithm::Sha256, "sha-256"),
+];
+
+#[derive(Debug, PartialEq)]
+pub enum DigestFieldError {
+    // not a Structured Field dictionary, or a supported algorithm whose
+    // value isn't a byte sequence, or a weight outside 0 to 10
+    Malformed,
+    UnsupportedAlgorithm(Algorithm),
+    // none of the digests are for a supported algorithm
+    NoSupportedAlgorithm,
+    Mismatch(Algorithm),
+}
+
+impl fmt::Display for DigestFieldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DigestFieldError::Malformed => f.write_str("malformed digest field"),
+            DigestFieldError::UnsupportedAlgorithm(alg) => {
+                write!(f, "{} can't be used in a digest field", alg.name())
+            }
+            DigestFieldError::NoSupportedAlgorithm => {
+                f.write_str("no digest with a supported algorithm")
+            }
+            DigestFieldError::Mismatch(alg) => write!(f, "the {} digest doesn't match", alg.name()),
+        }
+    }
+}
+
+impl Error for DigestFieldError {}
+
+fn key(algorithm: Algorithm) -> Result<&'static str, DigestFieldError> {
+    SUPPORTED
+        .iter()
+        .find(|(alg, _)| *alg == algorithm)
+        .map(|(_, key)| *key)
+        .ok_or(DigestFieldError::UnsupportedAlgorithm(algorithm))
+}
+
+// The value of a Content-Digest or Repr-Digest field
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DigestField {
+    digests: Vec<(Algorithm, Vec<u8>)>,
+}
+
+impl DigestField {
+    // digests of the content for each of the algorithms, in order
+    pub fn new(algorithms: &[Algorithm], content: &[u8]) -> Result<DigestField, DigestFieldError> {
+        let mut field = DigestField::default();
+        for alg in algorithms.iter() {
+            field.insert(*alg, alg.hash(content))?;
+        }
+        Ok(field)
+    }
+
+    // for a digest which has been worked out already, e.g. while streaming
+    // the content
+    pub fn insert(
+        &mut self,
+        algorithm: Algorithm,
+        digest: Vec<u8>,
+    ) -> Result<(), DigestFieldError> {
+        key(algorithm)?;
+        self.digests.retain(|(alg, _)| *alg != algorithm);
+        self.digests.push((algorithm, digest));
+        Ok(())
+    }
+
+    pub fn get(&self, algorithm: Algorithm) -> Option<&[u8]> {
+        self.digests
+            .iter()
+            .find(|(alg, _)| *alg == algorithm)
+            .map(|(_, digest)| digest.as_slice())
+    }
+
+    pub fn strongest(&self) -> Option<(Algorithm, &[u8])> {
+        SUPPORTED
+            .iter()
+            .find_map(|(alg, _)| self.get(*alg).map(|digest| (*alg, digest)))
+    }
+
+    // Checks the content against the strongest of the digests, in constant
+    // time, returning the algorithm it was checked with
+    pub fn verify(&self, content: &[u8]) -> Result<Algorithm, DigestFieldError> {
+        let (alg, digest) = self
+            .strongest()
+            .ok_or(DigestFieldError::NoSupportedAlgorithm)?;
+        if !crate::util::ct_eq(&alg.hash(content), digest) {
+            return Err(DigestFieldError::Mismatch(alg));
+        }
+        Ok(alg)
+    }
+}
+
+impl fmt::Display for DigestField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (alg, digest)) in self.digests.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            // only supported algorithms get in
+            write!(f, "{}=:{}:", key(*alg).unwrap(), encoding::base64(digest))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for DigestField {
+    type Err = DigestFieldError;
+
+    fn from_str(s: &str) -> Result<DigestField, DigestFieldError> {
+        let mut field = DigestField::default();
+        for (name, item) in parse_dictionary(s)? {
+            if let Some((alg, _)) = SUPPORTED.iter().find(|(_, key)| *key == name) {
+                match item {
+                    Item::Bytes(digest) => field.insert(*alg, digest)?,
+                    _ => return Err(DigestFieldError::Malformed),
+                }
+            }
+        }
+        Ok(field)
+    }
+}
+
+// The value of a Want-Content-Digest or Want-Repr-Digest field
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WantDigest {
+    weights: Vec<(Algorithm, u8)>,
+}
+
+impl WantDigest {
+    pub fn new() -> WantDigest {
+        WantDigest::default()
+    }
+
+    // weight is 1 to 10, or 0 for an algorithm which isn't acceptable
+    pub fn insert(&mut self, algorithm: Algorithm, weight: u8) -> Result<(), DigestFieldError> {
+        key(algorithm)?;
+        if weight > 10 {
+            return Err(DigestFieldError::Malformed);
+        }
+        self.weights.retain(|(alg, _)| *alg != algorithm);
+        self.weights.push((algorithm, weight));
+        Ok(())
+    }
+
+    pub fn weight(&self, algorithm: Algorithm) -> Option<u8> {
+        self.weights
+            .iter()
+            .find(|(alg, _)| *alg == algorithm)
+            .map(|(_, weight)| *weight)
+    }
+
+    // the supported algorithm with the most weight, the stronger one if
+    // they tie, and None if neither is acceptable
+    pub fn choose(&self) -> Option<Algorithm> {
+        SUPPORTED
+            .iter()
+            .filter_map(|(alg, _)| self.weight(*alg).map(|weight| (*alg, weight)))
+            .filter(|(_, weight)| *weight > 0)
+            .fold(
+                None,
+                |best: Option<(Algorithm, u8)>, (alg, weight)| match best {
+                    Some((_, best_weight)) if best_weight >= weight => best,
+                    _ => Some((alg, weight)),
+                },
+            )
+            .map(|(alg, _)| alg)
+    }
+}
+
+impl fmt::Display for WantDigest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (alg, weight)) in self.weights.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}={}", key(*alg).unwrap(), weight)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for WantDigest {
+    type Err = DigestFieldError;
+
+    fn from_str(s: &str) -> Result<WantDigest, DigestFieldError> {
+        let mut want = WantDigest::new();
+        for (name, item) in parse_dictionary(s)? {
+            let weight = match item {
+                Item::Integer(weight) if (0..=10).contains(&weight) => weight as u8,
+                _ => return Err(DigestFieldError::Malformed),
+            };
+            if let Some((alg, _)) = SUPPORTED.iter().find(|(_, key)| *key == name) {
+                want.insert(*alg, weight)?;
+            }
+        }
+        Ok(want)
+    }
+}
+
+// the values of dictionary members which matter here, the parameters
+// being dropped
+#[derive(Debug, PartialEq)]
+enum Item {
+    Bytes(Vec<u8>),
+    Integer(i64),
+    Other,
+}
+
+// RFC 8941 4.2.2, leaving out what the fields above don't need
+fn parse_dictionary(s: &str) -> Result<Vec<(String, Item)>, DigestFieldError> {
+    let mut parser = Parser {
+        s: s.as_bytes(),
+        pos: 0,
+    };
+    let mut members = Vec::new();
+    parser.skip(b" \t");
+    while parser.pos < parser.s.len() {
+        let key = parser.key()?;
+        let item = if parser.eat(b'=') {
+            if parser.peek() == Some(b'(') {
+                parser.inner_list()?;
+                Item::Other
+            } else {
+                parser.bare_item()?
+            }
+        } else {
+            // a bare key is boolean true
+            Item::Other
+        };
+        parser.parameters()?;
+        members.push((key, item));
+
+        parser.skip(b" \t");
+        if parser.pos == parser.s.len() {
+            break;
+        }
+        if !parser.eat(b',') {
+            return Err(DigestFieldError::Malformed);
+        }
+        parser.skip(b" \t");
+        if parser.pos == parser.s.len() {
+            return Err(DigestFieldError::Malformed);
+        }
+    }
+    Ok(members)
+}
+
+struct Parser<'a> {
+    s: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.s.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        if self.peek() != Some(c) {
+            return false;
+        }
+        self.pos += 1;
+        true
+    }
+
+    fn skip(&mut self, chars: &[u8]) {
+        while self.peek().is_some_and(|c| chars.contains(&c)) {
+            self.pos += 1;
+        }
+    }
+
+    // the characters from pos which are in the set
+    fn take_while(&mut self, f: impl Fn(u8) -> bool) -> &'a str {
+        let start = self.pos;
+        while self.peek().is_some_and(&f) {
+            self.pos += 1;
+        }
+        // only ever ASCII
+        std::str::from_utf8(&self.s[start..self.pos]).unwrap()
+    }
+
+    // ( lcalpha / "*" ) *( lcalpha / DIGIT / "_" / "-" / "." / "*" )
+    fn key(&mut self) -> Result<String, DigestFieldError> {
+        if !self
+            .peek()
+            .is_some_and(|c| c.is_ascii_lowercase() || c == b'*')
+        {
+            return Err(DigestFieldError::Malformed);
+        }
+        Ok(self
+            .take_while(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || b"_-.*".contains(&c))
+            .to_string())
+    }
+
+    fn parameters(&mut self) -> Result<(), DigestFieldError> {
+        while self.eat(b';') {
+            self.skip(b" ");
+            self.key()?;
+            if self.eat(b'=') {
+                self.bare_item()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn inner_list(&mut self) -> Result<(), DigestFieldError> {
+        self.eat(b'(');
+        loop {
+            self.skip(b" ");
+            if self.eat(b')') {
+                return self.parameters();
+            }
+            self.bare_item()?;
+            self.parameters()?;
+            if !matches!(self.peek(), Some(b' ') | Some(b')')) {
+                return Err(DigestFieldError::Malformed);
+            }
+        }
+    }
+
+    fn bare_item(&mut self) -> Result<Item, DigestFieldError> {
+        match self.peek().ok_or(DigestFieldError::Malformed)? {
+            b'-' | b'0'..=b'9' => self.number(),
+            b'"' => {
+                self.pos += 1;
+                loop {
+                    match self.peek().ok_or(DigestFieldError::Malformed)? {
+                        b'"' => break,
+                        b'\\' => {
+                            self.pos += 1;
+                            if !matches!(self.peek(), Some(b'"') | Some(b'\\')) {
+                                return Err(DigestFieldError::Malformed);
+                            }
+                        }
+                        0x20..=0x7e => {}
+                        _ => return Err(DigestFieldError::Malformed),
+                    }
+                    self.pos += 1;
+                }
+                self.pos += 1;
+                Ok(Item::Other)
+            }
+            b':' => {
+                self.pos += 1;
+                let encoded = self.take_while(|c| c.is_ascii_alphanumeric() || b"+/=".contains(&c));
+                let bytes =
+                    encoding::decode_base64(encoded).map_err(|_| DigestFieldError::Malformed)?;
+                if !self.eat(b':') {
+                    return Err(DigestFieldError::Malformed);
+                }
+                Ok(Item::Bytes(bytes))
+            }
+            b'?' => {
+                self.pos += 1;
+                if !(self.eat(b'0') || self.eat(b'1')) {
+                    return Err(DigestFieldError::Malformed);
+                }
+                Ok(Item::Other)
+            }
+            c if c.is_ascii_alphabetic() || c == b'*' => {
+                self.take_while(|c| c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~:/".contains(&c));
+                Ok(Item::Other)
+            }
+            _ => Err(DigestFieldError::Malformed),
+        }
+    }
+
+    // an integer of up to 15 digits, or a decimal of up to 12 digits and
+    // 3 decimal places
+    fn number(&mut self) -> Result<Item, DigestFieldError> {
+        let negative = self.eat(b'-');
+        let integer = self.take_while(|c| c.is_ascii_digit());
+        if integer.is_empty() {
+            return Err(DigestFieldError::Malformed);
+        }
+        if self.eat(b'.') {
+            let fraction = self.take_while(|c| c.is_ascii_digit());
+            if integer.len() > 12 || fraction.is_empty() || fraction.len() > 3 {
+                return Err(DigestFieldError::Malformed);
+            }
+            return Ok(Item::Other);
+        }
+        if integer.len() > 15 {
+            return Err(DigestFieldError::Malformed);
+        }
+        let value: i64 = integer.parse().unwrap();
+        Ok(Item::Integer(if negative { -value } else { value }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the examples in RFC 9530 section 2 and appendix B
+    #[test]
+    fn test_rfc9530() {
+        let content = br#"{"hello": "world"}"#;
+        let field = DigestField::new(&[Algorithm::Sha256], content).unwrap();
+        assert_eq!(
+            field.to_string(),
+            "sha-256=:X48E9qOokqqrvdts8nOJRJN3OWDUoyWxBf7kbu9DBPE=:"
+        );
+
+        let field = DigestField::new(&[Algorithm::Sha256, Algorithm::Sha512], content).unwrap();
+        let header = "sha-256=:X48E9qOokqqrvdts8nOJRJN3OWDUoyWxBf7kbu9DBPE=:, \
+                      sha-512=:WZDPaVn/7XgHaAy8pmojAkGWoRx2UFChF41A2svX+TaPm+AbwAgBWnrIiYllu7BNNyealdVLvRwEmTHWXvJwew==:";
+        assert_eq!(field.to_string(), header);
+        assert_eq!(header.parse::<DigestField>().unwrap(), field);
+        assert_eq!(field.verify(content), Ok(Algorithm::Sha512));
+
+        let empty = "sha-256=:47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=:"
+            .parse::<DigestField>()
+            .unwrap();
+        assert_eq!(empty.verify(b""), Ok(Algorithm::Sha256));
+        assert_eq!(
+            empty.verify(content),
+            Err(DigestFieldError::Mismatch(Algorithm::Sha256))
+        );
+    }
+
+    #[test]
+    fn test_verify_strongest() {
+        let content = b"hello";
+        let sha256 = encoding::base64(&Algorithm::Sha256.hash(content));
+        let sha512 = encoding::base64(&Algorithm::Sha512.hash(b"something else"));
+
+        // unknown and deprecated algorithms, and parameters, are ignored
+        let field = format!(
+            "md5=:AAAA:, unixsum=3, sha-256=:{}:;p=1, x-custom=(a b);q=?1",
+            sha256
+        )
+        .parse::<DigestField>()
+        .unwrap();
+        assert_eq!(field.verify(content), Ok(Algorithm::Sha256));
+
+        // the sha-512 digest is checked, and is wrong
+        let field = format!("sha-256=:{}:, sha-512=:{}:", sha256, sha512)
+            .parse::<DigestField>()
+            .unwrap();
+        assert_eq!(
+            field.verify(content),
+            Err(DigestFieldError::Mismatch(Algorithm::Sha512))
+        );
+
+        let field = "md5=:AAAA:".parse::<DigestField>().unwrap();
+        assert_eq!(
+            field.verify(content),
+            Err(DigestFieldError::NoSupportedAlgorithm)
+        );
+        assert_eq!(
+            DigestField::new(&[Algorithm::Sha384], content),
+            Err(DigestFieldError::UnsupportedAlgorithm(Algorithm::Sha384))
+        );
+        let mut field = DigestField::default();
+        assert_eq!(
+            field.insert(Algorithm::Sha384, Algorithm::Sha384.hash(content)),
+            Err(DigestFieldError::UnsupportedAlgorithm(Algorithm::Sha384))
+        );
+        assert_eq!(field.to_string(), "");
+
+        for header in [
+            "sha-256=X48E9qOokqqrvdts8nOJRJN3OWDUoyWxBf7kbu9DBPE=",
+            "sha-256=\"X48E9qOokqqrvdts8nOJRJN3OWDUoyWxBf7kbu9DBPE=\"",
+            "sha-256=:X48E9qOokqqrvdts8nOJRJN3OWDUoyWxBf7kbu9DBPE=",
+            "sha-256=:!!:",
+            "SHA-256=:47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=:",
+            "sha-256=:47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=:,",
+            "sha-256=:47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=: sha-512=:AA==:",
+        ]
+        .iter()
+        {
+            assert_eq!(
+                header.parse::<DigestField>(),
+                Err(DigestFieldError::Malformed),
+                "{}",
+                header
+            );
+        }
+    }
+
+    #[test]
+    fn test_want_digest() {
+        let want = "sha-256=1, sha-512=3, md5=10"
+            .parse::<WantDigest>()
+            .unwrap();
+        assert_eq!(want.weight(Algorithm::Sha256), Some(1));
+        assert_eq!(want.choose(), Some(Algorithm::Sha512));
+        assert_eq!(want.to_string(), "sha-256=1, sha-512=3");
+
+        let want = "sha-512=0, sha-256=10".parse::<WantDigest>().unwrap();
+        assert_eq!(want.choose(), Some(Algorithm::Sha256));
+        let want = "sha-256=5, sha-512=5".parse::<WantDigest>().unwrap();
+        assert_eq!(want.choose(), Some(Algorithm::Sha512));
+        let want = "sha-256=0, unixsum=4".parse::<WantDigest>().unwrap();
+        assert_eq!(want.choose(), None);
+        assert_eq!(WantDigest::new().choose(), None);
+
+        let mut want = WantDigest::new();
+        want.insert(Algorithm::Sha512, 10).unwrap();
+        want.insert(Algorithm::Sha256, 2).unwrap();
+        assert_eq!(want.to_string(), "sha-512=10, sha-256=2");
+        assert_eq!(
+            want.insert(Algorithm::Sha256, 11),
+            Err(DigestFieldError::Malformed)
+        );
+
+        for header in [
+            "sha-256=11",
+            "sha-256=-1",
+            "sha-256=1.5",
+            "sha-256",
+            "sha-256=:AA==:",
+        ]
+        .iter()
+        {
+            assert_eq!(
+                header.parse::<WantDigest>(),
+                Err(DigestFieldError::Malformed),
+                "{}",
+                header
+            );
+        }
+    }
+}
@@ -0,0 +1,363 @@
+/*
+
+Hex and base64 encoding of byte strings, for digests and for the
+protocols built on top of the hashes which carry them as text.
+
+Base64 comes in the standard alphabet of RFC 4648 section 4, padded or
+not, and the URL and filename safe alphabet of section 5, unpadded.
+Decoding takes the standard alphabet with or without padding, but only
+the canonical encoding: unused bits at the end have to be zero.
+
+Base32 is the alphabet of RFC 4648 section 6, written unpadded in upper
+case as one-time password secrets are, and read in either case with or
+without padding. Percent-encoding escapes everything but the unreserved
+characters of RFC 3986 section 2.3.
+
+*/
+
+const HEX_LOWER: &[u8; 16] = b"0123456789abcdef";
+const HEX_UPPER: &[u8; 16] = b"0123456789ABCDEF";
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    encode_hex(bytes, HEX_LOWER)
+}
+
+pub(crate) fn hex_upper(bytes: &[u8]) -> String {
+    encode_hex(bytes, HEX_UPPER)
+}
+
+fn encode_hex(bytes: &[u8], alphabet: &[u8; 16]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        out.push(alphabet[(byte >> 4) as usize] as char);
+        out.push(alphabet[(byte & 0xf) as usize] as char);
+    }
+    out
+}
+
+// the position and value of the first character which isn't a hex digit
+#[derive(Debug, PartialEq)]
+pub(crate) struct InvalidHex(pub usize, pub char);
+
+fn hex_value(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+// the length has to be checked by the caller, an odd trailing digit is
+// an error
+pub(crate) fn decode_hex(s: &str) -> Result<Vec<u8>, InvalidHex> {
+    let mut out = Vec::with_capacity(s.len() / 2);
+    let mut chars = s.char_indices();
+    while let Some((i, hi)) = chars.next() {
+        let hi_value = hex_value_char(hi).ok_or(InvalidHex(i, hi))?;
+        let (j, lo) = chars.next().ok_or(InvalidHex(i, hi))?;
+        let lo_value = hex_value_char(lo).ok_or(InvalidHex(j, lo))?;
+        out.push(hi_value << 4 | lo_value);
+    }
+    Ok(out)
+}
+
+fn hex_value_char(c: char) -> Option<u8> {
+    if c.is_ascii() {
+        hex_value(c as u8)
+    } else {
+        None
+    }
+}
+
+const BASE64_STANDARD: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const BASE64_URL_SAFE: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+pub(crate) fn base64(bytes: &[u8]) -> String {
+    encode_base64(bytes, BASE64_STANDARD, true)
+}
+
+pub(crate) fn base64_unpadded(bytes: &[u8]) -> String {
+    encode_base64(bytes, BASE64_STANDARD, false)
+}
+
+pub(crate) fn base64_url(bytes: &[u8]) -> String {
+    encode_base64(bytes, BASE64_URL_SAFE, false)
+}
+
+fn encode_base64(bytes: &[u8], alphabet: &[u8; 64], pad: bool) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(alphabet[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else if pad {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+// the position of the first character which doesn't belong, which is the
+// length of the input if it ends too soon
+#[derive(Debug, PartialEq)]
+pub(crate) struct InvalidBase64(pub usize);
+
+pub(crate) fn decode_base64(s: &str) -> Result<Vec<u8>, InvalidBase64> {
+    decode_base64_with(s, BASE64_STANDARD)
+}
+
+pub(crate) fn decode_base64_url(s: &str) -> Result<Vec<u8>, InvalidBase64> {
+    decode_base64_with(s, BASE64_URL_SAFE)
+}
+
+fn decode_base64_with(s: &str, alphabet: &[u8; 64]) -> Result<Vec<u8>, InvalidBase64> {
+    let bytes = s.as_bytes();
+    let data_len = bytes.iter().position(|c| *c == b'=').unwrap_or(bytes.len());
+    if data_len < bytes.len() {
+        // one or two '=', and only as many as make the length a multiple of
+        // four
+        let padding = &bytes[data_len..];
+        if let Some(i) = padding.iter().position(|c| *c != b'=') {
+            return Err(InvalidBase64(data_len + i));
+        }
+        if padding.len() > 2 || !(data_len + padding.len()).is_multiple_of(4) {
+            return Err(InvalidBase64(data_len));
+        }
+    }
+    if data_len % 4 == 1 {
+        return Err(InvalidBase64(data_len));
+    }
+
+    let mut out = Vec::with_capacity(data_len * 3 / 4);
+    let mut n = 0u32;
+    for (i, c) in bytes[..data_len].iter().enumerate() {
+        let value = alphabet
+            .iter()
+            .position(|a| a == c)
+            .ok_or(InvalidBase64(i))?;
+        n = n << 6 | value as u32;
+        if i % 4 == 3 {
+            out.extend_from_slice(&n.to_be_bytes()[1..]);
+            n = 0;
+        }
+    }
+
+    // the last two or three characters, whose spare low bits have to be
+    // zero
+    match data_len % 4 {
+        2 if n & 0xf == 0 => out.push((n >> 4) as u8),
+        3 if n & 0x3 == 0 => out.extend_from_slice(&((n >> 2) as u16).to_be_bytes()),
+        0 => {}
+        _ => return Err(InvalidBase64(data_len - 1)),
+    }
+    Ok(out)
+}
+
+const BASE32: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+pub(crate) fn base32(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity((bytes.len() * 8).div_ceil(5));
+    let mut n = 0u32;
+    let mut bits = 0;
+    for byte in bytes {
+        n = (n << 8 | *byte as u32) & 0xfff;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32[(n >> bits & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32[(n << (5 - bits) & 0x1f) as usize] as char);
+    }
+    out
+}
+
+// the position of the first character which doesn't belong, as with
+// base64
+#[derive(Debug, PartialEq)]
+pub(crate) struct InvalidBase32(pub usize);
+
+pub(crate) fn decode_base32(s: &str) -> Result<Vec<u8>, InvalidBase32> {
+    let bytes = s.as_bytes();
+    let data_len = bytes.iter().position(|c| *c == b'=').unwrap_or(bytes.len());
+    if let Some(i) = bytes[data_len..].iter().position(|c| *c != b'=') {
+        return Err(InvalidBase32(data_len + i));
+    }
+    if data_len < bytes.len() && !bytes.len().is_multiple_of(8) {
+        return Err(InvalidBase32(data_len));
+    }
+    // 1, 3 or 6 characters left over can't come from whole bytes
+    if [1, 3, 6].contains(&(data_len % 8)) {
+        return Err(InvalidBase32(data_len));
+    }
+
+    let mut out = Vec::with_capacity(data_len * 5 / 8);
+    let mut n = 0u32;
+    let mut bits = 0;
+    for (i, c) in bytes[..data_len].iter().enumerate() {
+        let value = BASE32
+            .iter()
+            .position(|a| *a == c.to_ascii_uppercase())
+            .ok_or(InvalidBase32(i))?;
+        n = (n << 5 | value as u32) & 0xfff;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((n >> bits) as u8);
+        }
+    }
+    if n & ((1 << bits) - 1) != 0 {
+        return Err(InvalidBase32(data_len - 1));
+    }
+    Ok(out)
+}
+
+pub(crate) fn percent_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len());
+    for byte in bytes {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(byte) {
+            out.push(*byte as char);
+        } else {
+            out.push('%');
+            out.push(HEX_UPPER[(byte >> 4) as usize] as char);
+            out.push(HEX_UPPER[(byte & 0xf) as usize] as char);
+        }
+    }
+    out
+}
+
+// the position of a '%' which isn't followed by two hex digits
+#[derive(Debug, PartialEq)]
+pub(crate) struct InvalidPercent(pub usize);
+
+pub(crate) fn percent_decode(s: &str) -> Result<Vec<u8>, InvalidPercent> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hi = bytes.get(i + 1).and_then(|c| hex_value(*c));
+            let lo = bytes.get(i + 2).and_then(|c| hex_value(*c));
+            match (hi, lo) {
+                (Some(hi), Some(lo)) => out.push(hi << 4 | lo),
+                _ => return Err(InvalidPercent(i)),
+            }
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    // the test vectors from section 10 of RFC 4648
+    #[test]
+    fn test_base64() {
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (plain, encoded) in vectors.iter() {
+            assert_eq!(super::base64(plain.as_bytes()), *encoded);
+            assert_eq!(super::decode_base64(encoded).unwrap(), plain.as_bytes());
+            let unpadded = super::base64_unpadded(plain.as_bytes());
+            assert_eq!(unpadded, encoded.trim_end_matches('='));
+            assert_eq!(super::decode_base64(&unpadded).unwrap(), plain.as_bytes());
+        }
+
+        assert_eq!(super::base64_url(&[0xfb, 0xff]), "-_8");
+        assert_eq!(super::decode_base64_url("-_8").unwrap(), [0xfb, 0xff]);
+        assert!(super::decode_base64_url("+/8").is_err());
+    }
+
+    #[test]
+    fn test_decode_base64_invalid() {
+        use super::{decode_base64, InvalidBase64};
+
+        assert_eq!(decode_base64("-_8"), Err(InvalidBase64(0)));
+        assert_eq!(decode_base64("Zm9v Zg"), Err(InvalidBase64(4)));
+        // a single character left over, or padding in the wrong place
+        assert_eq!(decode_base64("Zm9vY"), Err(InvalidBase64(5)));
+        assert_eq!(decode_base64("Zg="), Err(InvalidBase64(2)));
+        assert_eq!(decode_base64("Zg=a"), Err(InvalidBase64(3)));
+        assert_eq!(decode_base64("Zm9v===="), Err(InvalidBase64(4)));
+        // non-zero unused bits
+        assert_eq!(decode_base64("Zh=="), Err(InvalidBase64(1)));
+        assert_eq!(decode_base64("Zm9="), Err(InvalidBase64(2)));
+    }
+
+    // the test vectors from section 10 of RFC 4648
+    #[test]
+    fn test_base32() {
+        use super::{base32, decode_base32, InvalidBase32};
+
+        let vectors = [
+            ("", ""),
+            ("f", "MY======"),
+            ("fo", "MZXQ===="),
+            ("foo", "MZXW6==="),
+            ("foob", "MZXW6YQ="),
+            ("fooba", "MZXW6YTB"),
+            ("foobar", "MZXW6YTBOI======"),
+        ];
+        for (plain, encoded) in vectors.iter() {
+            let unpadded = encoded.trim_end_matches('=');
+            assert_eq!(base32(plain.as_bytes()), unpadded);
+            assert_eq!(decode_base32(encoded).unwrap(), plain.as_bytes());
+            assert_eq!(decode_base32(unpadded).unwrap(), plain.as_bytes());
+            assert_eq!(
+                decode_base32(&unpadded.to_lowercase()).unwrap(),
+                plain.as_bytes()
+            );
+        }
+
+        assert_eq!(decode_base32("MZXW1"), Err(InvalidBase32(4)));
+        assert_eq!(decode_base32("MZX"), Err(InvalidBase32(3)));
+        assert_eq!(decode_base32("MY==="), Err(InvalidBase32(2)));
+        assert_eq!(decode_base32("MZ"), Err(InvalidBase32(1)));
+    }
+
+    #[test]
+    fn test_percent() {
+        use super::{percent_decode, percent_encode, InvalidPercent};
+
+        assert_eq!(
+            percent_encode(b"a-Z_0.9~ /:%\xff"),
+            "a-Z_0.9~%20%2F%3A%25%FF"
+        );
+        assert_eq!(
+            percent_decode("a-Z_0.9~%20%2f%3A%25%FF").unwrap(),
+            b"a-Z_0.9~ /:%\xff"
+        );
+        assert_eq!(percent_decode("a%2"), Err(InvalidPercent(1)));
+        assert_eq!(percent_decode("%zz"), Err(InvalidPercent(0)));
+    }
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(super::hex(&[0x01, 0xab, 0xff]), "01abff");
+        assert_eq!(super::hex_upper(&[0x01, 0xab, 0xff]), "01ABFF");
+        assert_eq!(super::decode_hex("01abFF").unwrap(), vec![0x01, 0xab, 0xff]);
+        assert_eq!(super::decode_hex("01ag"), Err(super::InvalidHex(3, 'g')));
+        assert_eq!(super::decode_hex("012"), Err(super::InvalidHex(2, '2')));
+    }
+}
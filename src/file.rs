@@ -0,0 +1,145 @@
+/*
+
+Hashing of files on disk. Regular files are memory-mapped and handed to
+the streaming hasher in one go, which avoids copying multi-gigabyte files
+through a read buffer. Anything which can't be mapped (pipes, sockets,
+character devices, empty files, or a failed mmap) is read through a
+buffer instead, so the same helper works for every kind of path.
+
+*/
+
+use crate::StreamHasher;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum FileError {
+    NotFound(PathBuf),
+    PermissionDenied(PathBuf),
+    Io(PathBuf, io::Error),
+}
+
+impl FileError {
+    pub(crate) fn new(path: &Path, err: io::Error) -> FileError {
+        match err.kind() {
+            io::ErrorKind::NotFound => FileError::NotFound(path.to_path_buf()),
+            io::ErrorKind::PermissionDenied => FileError::PermissionDenied(path.to_path_buf()),
+            _ => FileError::Io(path.to_path_buf(), err),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        match self {
+            FileError::NotFound(path) => path,
+            FileError::PermissionDenied(path) => path,
+            FileError::Io(path, _) => path,
+        }
+    }
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FileError::NotFound(path) => write!(f, "{}: file not found", path.display()),
+            FileError::PermissionDenied(path) => write!(f, "{}: permission denied", path.display()),
+            FileError::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+        }
+    }
+}
+
+impl Error for FileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FileError::Io(_, err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn hash_file<H: StreamHasher>(
+    path: &Path,
+    mut hasher: H,
+) -> Result<H::Output, FileError> {
+    let file = File::open(path).map_err(|err| FileError::new(path, err))?;
+    let metadata = file.metadata().map_err(|err| FileError::new(path, err))?;
+
+    if metadata.is_file() && metadata.len() > 0 {
+        // Safety: the mapping is only read while it is alive. If another
+        // process truncates or rewrites the file underneath us the hash is
+        // meaningless anyway, just as it would be with buffered reads.
+        if let Ok(map) = unsafe { memmap2::Mmap::map(&file) } {
+            hasher.update(&map);
+            return Ok(hasher.finalize());
+        }
+    }
+
+    hash_reader(file, hasher).map_err(|err| FileError::new(path, err))
+}
+
+fn hash_reader<R: Read, H: StreamHasher>(mut reader: R, mut hasher: H) -> io::Result<H::Output> {
+    let mut buffer = vec![0u8; 64 * 1024];
+    let result = loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => break Ok(hasher.finalize()),
+            Ok(n) => hasher.update(&buffer[..n]),
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => break Err(err),
+        }
+    };
+
+    #[cfg(feature = "zeroize")]
+    zeroize::Zeroize::zeroize(&mut buffer);
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FileError;
+    use crate::sha256;
+    use std::io;
+    use std::path::Path;
+
+    #[test]
+    fn test_hash_reader() {
+        let msg: Vec<u8> = (0..200_000u32).map(|i| (i % 253) as u8).collect();
+        let hash = super::hash_reader(io::Cursor::new(&msg), sha256::Sha256::new()).unwrap();
+        assert_eq!(Some(hash), sha256::hash(&msg));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_hash_file_falls_back_for_unmappable_files() {
+        // procfs files report a length of 0, so they are read through the buffer
+        let path = Path::new("/proc/version");
+        let hash = sha256::hash_file(path).unwrap();
+        assert_eq!(Some(hash), sha256::hash(&std::fs::read(path).unwrap()));
+    }
+
+    #[test]
+    fn test_file_errors() {
+        let path = Path::new("/does/not/exist");
+        match sha256::hash_file(path) {
+            Err(FileError::NotFound(p)) => assert_eq!(p, path),
+            other => panic!("expected NotFound, got {:?}", other),
+        }
+
+        let denied = io::Error::from(io::ErrorKind::PermissionDenied);
+        match FileError::new(path, denied) {
+            FileError::PermissionDenied(p) => assert_eq!(p, path),
+            other => panic!("expected PermissionDenied, got {:?}", other),
+        }
+
+        let other = io::Error::from(io::ErrorKind::UnexpectedEof);
+        match FileError::new(path, other) {
+            FileError::Io(p, err) => {
+                assert_eq!(p, path);
+                assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+            }
+            other => panic!("expected Io, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,223 @@
+/*
+
+HMAC, the keyed-hash message authentication code of FIPS 198-1 / RFC
+2104, over any of the crate's algorithms:
+
+    HMAC(K, m) = H((K0 ^ opad) || H((K0 ^ ipad) || m))
+
+where K0 is the key padded with zeros to the block size, after hashing
+it first if it is longer than a block.
+
+Both the inner and outer hashers are primed with their padded key when
+the Hmac is created, so a single key can be used for many messages by
+cloning the primed Hmac rather than starting over.
+
+*/
+
+use crate::algorithm::{Algorithm, DynHasher};
+use crate::digest::{Sha256Digest, Sha512Digest};
+use std::convert::TryFrom;
+
+const IPAD: u8 = 0x36;
+const OPAD: u8 = 0x5c;
+
+#[derive(Clone)]
+pub struct Hmac {
+    algorithm: Algorithm,
+    inner: Box<dyn DynHasher>,
+    outer: Box<dyn DynHasher>,
+}
+
+impl Hmac {
+    pub fn new(algorithm: Algorithm, key: &[u8]) -> Hmac {
+        let mut padded_key = vec![0u8; algorithm.block_size()];
+        if key.len() > algorithm.block_size() {
+            let hashed_key = algorithm.hash(key);
+            padded_key[..hashed_key.len()].copy_from_slice(&hashed_key);
+        } else {
+            padded_key[..key.len()].copy_from_slice(key);
+        }
+
+        let mut inner = algorithm.hasher();
+        let mut outer = algorithm.hasher();
+        for byte in padded_key.iter_mut() {
+            *byte ^= IPAD;
+        }
+        inner.update(&padded_key);
+        for byte in padded_key.iter_mut() {
+            *byte ^= IPAD ^ OPAD;
+        }
+        outer.update(&padded_key);
+
+        #[cfg(feature = "zeroize")]
+        {
+            use zeroize::Zeroize;
+            padded_key.zeroize();
+        }
+
+        Hmac {
+            algorithm,
+            inner,
+            outer,
+        }
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    pub fn update(&mut self, msg: &[u8]) {
+        self.inner.update(msg);
+    }
+
+    // the tag, Algorithm::output_size bytes of it
+    pub fn finalize(self) -> Vec<u8> {
+        let mut outer = self.outer;
+        outer.update(&self.inner.finalize());
+        outer.finalize()
+    }
+
+    // compare the tag with the expected one in constant time, which a
+    // plain == on the bytes doesn't do
+    pub fn verify(self, tag: &[u8]) -> bool {
+        crate::util::ct_eq(&self.finalize(), tag)
+    }
+}
+
+impl crate::StreamHasher for Hmac {
+    type Output = Vec<u8>;
+
+    fn update(&mut self, msg: &[u8]) {
+        Hmac::update(self, msg)
+    }
+
+    fn finalize(self) -> Vec<u8> {
+        Hmac::finalize(self)
+    }
+}
+
+pub fn hmac(algorithm: Algorithm, key: &[u8], msg: &[u8]) -> Vec<u8> {
+    let mut hmac = Hmac::new(algorithm, key);
+    hmac.update(msg);
+    hmac.finalize()
+}
+
+// HMAC-SHA-256 and HMAC-SHA-512 as digests, which compare in constant
+// time
+pub fn hmac_sha256(key: &[u8], msg: &[u8]) -> Sha256Digest {
+    Sha256Digest::try_from(&hmac(Algorithm::Sha256, key, msg)[..]).unwrap()
+}
+
+pub fn hmac_sha512(key: &[u8], msg: &[u8]) -> Sha512Digest {
+    Sha512Digest::try_from(&hmac(Algorithm::Sha512, key, msg)[..]).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::hex;
+
+    // RFC 4231 test cases 1-4, 6 and 7, as (key, data, HMAC-SHA-256,
+    // HMAC-SHA-512). Test case 5 only gives truncated tags.
+    fn test_cases() -> Vec<(Vec<u8>, Vec<u8>, &'static str, &'static str)> {
+        vec![
+            (
+                vec![0x0b; 20],
+                b"Hi There".to_vec(),
+                "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+                "87aa7cdea5ef619d4ff0b4241a1d6cb02379f4e2ce4ec2787ad0b30545e17cde\
+                 daa833b7d6b8a702038b274eaea3f4e4be9d914eeb61f1702e696c203a126854",
+            ),
+            (
+                b"Jefe".to_vec(),
+                b"what do ya want for nothing?".to_vec(),
+                "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+                "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea250554\
+                 9758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737",
+            ),
+            (
+                vec![0xaa; 20],
+                vec![0xdd; 50],
+                "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe",
+                "fa73b0089d56a284efb0f0756c890be9b1b5dbdd8ee81a3655f83e33b2279d39\
+                 bf3e848279a722c806b485a47e67c807b946a337bee8942674278859e13292fb",
+            ),
+            (
+                (1..=25).collect(),
+                vec![0xcd; 50],
+                "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b",
+                "b0ba465637458c6990e5a8c5f61d4af7e576d97ff94b872de76f8050361ee3db\
+                 a91ca5c11aa25eb4d679275cc5788063a5f19741120c4f2de2adebeb10a298dd",
+            ),
+            (
+                vec![0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First".to_vec(),
+                "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+                "80b24263c7c1a3ebb71493c1dd7be8b49b46d1f41b4aeec1121b013783f8f352\
+                 6b56d037e05f2598bd0fd2215d6a1e5295e64f73f63f0aec8b915a985d786598",
+            ),
+            (
+                vec![0xaa; 131],
+                b"This is a test using a larger than block-size key and a larger \
+                  than block-size data. The key needs to be hashed before being \
+                  used by the HMAC algorithm."
+                    .to_vec(),
+                "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2",
+                "e37b6a775dc87dbaa4dfa9f96e5e3ffddebd71f8867289865df5a32d20cdc944\
+                 b6022cac3c4982b10d5eeb55c3e4de15134676fb6de0446065c97440fa8c6a58",
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_hmac_rfc4231() {
+        for (key, data, sha256, sha512) in test_cases() {
+            assert_eq!(hex(&hmac(Algorithm::Sha256, &key, &data)), sha256);
+            assert_eq!(hex(&hmac(Algorithm::Sha512, &key, &data)), sha512);
+            assert_eq!(hmac_sha256(&key, &data).to_hex(), sha256);
+            assert_eq!(hmac_sha512(&key, &data).to_hex(), sha512);
+        }
+
+        // test case 2 for the other two
+        assert_eq!(
+            hex(&hmac(
+                Algorithm::Sha224,
+                b"Jefe",
+                b"what do ya want for nothing?"
+            )),
+            "a30e01098bc6dbbf45690f3a7e9e6d0f8bbea2a39e6148008fd05e44"
+        );
+        assert_eq!(
+            hex(&hmac(
+                Algorithm::Sha384,
+                b"Jefe",
+                b"what do ya want for nothing?"
+            )),
+            "af45d2e376484031617f78d2b58a6b1b9c7ef464f5a01b47\
+             e42ec3736322445e8e2240ca5e69e2c78b3239ecfab21649"
+        );
+    }
+
+    #[test]
+    fn test_hmac_streaming() {
+        let primed = Hmac::new(Algorithm::Sha256, b"Jefe");
+
+        let mut hmac = primed.clone();
+        for chunk in b"what do ya want for nothing?".chunks(5) {
+            hmac.update(chunk);
+        }
+        let tag = hmac.finalize();
+        assert_eq!(
+            tag,
+            super::hmac(Algorithm::Sha256, b"Jefe", b"what do ya want for nothing?")
+        );
+
+        let mut hmac = primed.clone();
+        hmac.update(b"what do ya want for nothing?");
+        assert!(hmac.verify(&tag));
+
+        let mut hmac = primed;
+        hmac.update(b"what do ya want for nothing!");
+        assert!(!hmac.verify(&tag));
+    }
+}
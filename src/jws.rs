@@ -0,0 +1,678 @@
+/*
+
+JSON Web Signatures (RFC 7515) in the compact serialization, MACed with
+HMAC-SHA-256/384/512, which RFC 7518 3.2 calls HS256, HS384 and HS512:
+
+    BASE64URL(header) || '.' || BASE64URL(payload) || '.' || BASE64URL(MAC)
+
+where the MAC is over the ASCII of the first two parts, the signing
+input. base64url here never has padding.
+
+A token is only accepted for the algorithm the caller expects. The "alg"
+in the header is checked against it rather than used to pick the
+algorithm, so that "none", or a MAC under some other algorithm, can't be
+slipped in. Headers with "crit" are rejected too, as there are no
+extensions this understands.
+
+The header and the JWT claims (RFC 7519) are read with a small JSON
+reader which only keeps the top level strings and numbers, as that's all
+that's needed from them.
+
+*/
+
+use crate::algorithm::Algorithm;
+use crate::encoding;
+use crate::hmac::{hmac, Hmac};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JwsAlgorithm {
+    Hs256,
+    Hs384,
+    Hs512,
+}
+
+impl JwsAlgorithm {
+    // the "alg" header parameter value, from RFC 7518 3.1
+    pub fn name(self) -> &'static str {
+        match self {
+            JwsAlgorithm::Hs256 => "HS256",
+            JwsAlgorithm::Hs384 => "HS384",
+            JwsAlgorithm::Hs512 => "HS512",
+        }
+    }
+
+    pub fn algorithm(self) -> Algorithm {
+        match self {
+            JwsAlgorithm::Hs256 => Algorithm::Sha256,
+            JwsAlgorithm::Hs384 => Algorithm::Sha384,
+            JwsAlgorithm::Hs512 => Algorithm::Sha512,
+        }
+    }
+}
+
+// "alg" values are case sensitive, so unlike Algorithm this only takes
+// the exact names
+impl FromStr for JwsAlgorithm {
+    type Err = JwsError;
+
+    fn from_str(s: &str) -> Result<JwsAlgorithm, JwsError> {
+        match s {
+            "HS256" => Ok(JwsAlgorithm::Hs256),
+            "HS384" => Ok(JwsAlgorithm::Hs384),
+            "HS512" => Ok(JwsAlgorithm::Hs512),
+            _ => Err(JwsError::UnsupportedAlgorithm(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum JwsError {
+    // not three base64url parts, or a header or claims set which isn't a
+    // JSON object
+    Malformed,
+    MissingAlgorithm,
+    // "none" included
+    UnsupportedAlgorithm(String),
+    AlgorithmMismatch {
+        expected: JwsAlgorithm,
+        found: JwsAlgorithm,
+    },
+    CriticalHeader,
+    InvalidSignature,
+    // "exp" or "nbf" which isn't a number
+    InvalidClaim(&'static str),
+    Expired,
+    NotYetValid,
+}
+
+impl fmt::Display for JwsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JwsError::Malformed => f.write_str("malformed JWS"),
+            JwsError::MissingAlgorithm => f.write_str("the JWS header has no algorithm"),
+            JwsError::UnsupportedAlgorithm(name) => write!(f, "unsupported algorithm {}", name),
+            JwsError::AlgorithmMismatch { expected, found } => write!(
+                f,
+                "expected algorithm {}, found {}",
+                expected.name(),
+                found.name()
+            ),
+            JwsError::CriticalHeader => f.write_str("unsupported critical header parameters"),
+            JwsError::InvalidSignature => f.write_str("invalid signature"),
+            JwsError::InvalidClaim(name) => write!(f, "invalid {} claim", name),
+            JwsError::Expired => f.write_str("the token has expired"),
+            JwsError::NotYetValid => f.write_str("the token isn't valid yet"),
+        }
+    }
+}
+
+impl Error for JwsError {}
+
+// A token with the header {"alg":"HS256","typ":"JWT"}, or whichever
+// algorithm it is
+pub fn sign(alg: JwsAlgorithm, key: &[u8], payload: &[u8]) -> String {
+    let header = format!(r#"{{"alg":"{}","typ":"JWT"}}"#, alg.name());
+    sign_encoded(alg, key, header.as_bytes(), payload)
+}
+
+// A token with the caller's own header, which has to be a JSON object
+// whose "alg" is the algorithm
+pub fn sign_with_header(
+    alg: JwsAlgorithm,
+    key: &[u8],
+    header: &str,
+    payload: &[u8],
+) -> Result<String, JwsError> {
+    check_header(alg, header.as_bytes())?;
+    Ok(sign_encoded(alg, key, header.as_bytes(), payload))
+}
+
+fn sign_encoded(alg: JwsAlgorithm, key: &[u8], header: &[u8], payload: &[u8]) -> String {
+    let mut token = encoding::base64_url(header);
+    token.push('.');
+    token.push_str(&encoding::base64_url(payload));
+    let mac = hmac(alg.algorithm(), key, token.as_bytes());
+    token.push('.');
+    token.push_str(&encoding::base64_url(&mac));
+    token
+}
+
+// The payload of a token MACed with the algorithm and key. The header is
+// checked before the MAC, but nothing is returned from it, so a token
+// whose header is forged only gets an error back.
+pub fn verify(alg: JwsAlgorithm, key: &[u8], token: &str) -> Result<Vec<u8>, JwsError> {
+    let mut parts = token.split('.');
+    let (header, payload, signature) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(header), Some(payload), Some(signature)) if parts.next().is_none() => {
+            (header, payload, signature)
+        }
+        _ => return Err(JwsError::Malformed),
+    };
+
+    check_header(alg, &decode_part(header)?)?;
+    let signature = decode_part(signature)?;
+    let mut mac = Hmac::new(alg.algorithm(), key);
+    mac.update(&token.as_bytes()[..header.len() + 1 + payload.len()]);
+    if !mac.verify(&signature) {
+        return Err(JwsError::InvalidSignature);
+    }
+    decode_part(payload)
+}
+
+// verify, then check the "exp" and "nbf" claims of the payload at the
+// time, a unix time in seconds
+pub fn verify_jwt(
+    alg: JwsAlgorithm,
+    key: &[u8],
+    token: &str,
+    now: u64,
+    leeway: u64,
+) -> Result<Vec<u8>, JwsError> {
+    let payload = verify(alg, key, token)?;
+    Claims::from_payload(&payload)?.validate(now, leeway)?;
+    Ok(payload)
+}
+
+// base64url without padding, which RFC 7515 2 requires
+fn decode_part(part: &str) -> Result<Vec<u8>, JwsError> {
+    if part.contains('=') {
+        return Err(JwsError::Malformed);
+    }
+    encoding::decode_base64_url(part).map_err(|_| JwsError::Malformed)
+}
+
+fn check_header(alg: JwsAlgorithm, header: &[u8]) -> Result<(), JwsError> {
+    let header = parse_object(header).ok_or(JwsError::Malformed)?;
+    let found = match lookup(&header, "alg") {
+        Some(Value::String(name)) => name.parse::<JwsAlgorithm>()?,
+        Some(_) => return Err(JwsError::Malformed),
+        None => return Err(JwsError::MissingAlgorithm),
+    };
+    if found != alg {
+        return Err(JwsError::AlgorithmMismatch {
+            expected: alg,
+            found,
+        });
+    }
+    if lookup(&header, "crit").is_some() {
+        return Err(JwsError::CriticalHeader);
+    }
+    Ok(())
+}
+
+// The registered time claims of a JWT, as NumericDates, which may have a
+// fraction
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Claims {
+    exp: Option<f64>,
+    nbf: Option<f64>,
+}
+
+impl Claims {
+    pub fn from_payload(payload: &[u8]) -> Result<Claims, JwsError> {
+        let claims = parse_object(payload).ok_or(JwsError::Malformed)?;
+        let date = |name| match lookup(&claims, name) {
+            Some(Value::Number(date)) => Ok(Some(*date)),
+            Some(_) => Err(JwsError::InvalidClaim(name)),
+            None => Ok(None),
+        };
+        Ok(Claims {
+            exp: date("exp")?,
+            nbf: date("nbf")?,
+        })
+    }
+
+    pub fn exp(&self) -> Option<f64> {
+        self.exp
+    }
+
+    pub fn nbf(&self) -> Option<f64> {
+        self.nbf
+    }
+
+    // RFC 7519 4.1.4 and 4.1.5, allowing leeway seconds of clock skew
+    // either way
+    pub fn validate(&self, now: u64, leeway: u64) -> Result<(), JwsError> {
+        if let Some(exp) = self.exp {
+            validate_exp(exp, now, leeway)?;
+        }
+        if let Some(nbf) = self.nbf {
+            validate_nbf(nbf, now, leeway)?;
+        }
+        Ok(())
+    }
+}
+
+// the token expires at exp, so it has to be used before then
+pub fn validate_exp(exp: f64, now: u64, leeway: u64) -> Result<(), JwsError> {
+    if now as f64 >= exp + leeway as f64 {
+        return Err(JwsError::Expired);
+    }
+    Ok(())
+}
+
+pub fn validate_nbf(nbf: f64, now: u64, leeway: u64) -> Result<(), JwsError> {
+    if (now as f64) < nbf - leeway as f64 {
+        return Err(JwsError::NotYetValid);
+    }
+    Ok(())
+}
+
+// the top level members of a JSON object, anything but strings and
+// numbers being checked and skipped
+#[derive(Debug, PartialEq)]
+enum Value {
+    String(String),
+    Number(f64),
+    Other,
+}
+
+fn lookup<'a>(object: &'a [(String, Value)], name: &str) -> Option<&'a Value> {
+    object.iter().find(|(n, _)| n == name).map(|(_, v)| v)
+}
+
+// None for anything which isn't a single JSON object, or has the same
+// member twice, which RFC 7515 4 allows rejecting
+fn parse_object(json: &[u8]) -> Option<Vec<(String, Value)>> {
+    let mut reader = JsonReader { json, pos: 0 };
+    reader.skip_whitespace();
+    if reader.peek()? != b'{' {
+        return None;
+    }
+    let mut members: Vec<(String, Value)> = Vec::new();
+    reader.members(0, |name, value| {
+        if lookup(&members, &name).is_some() {
+            return None;
+        }
+        members.push((name, value));
+        Some(())
+    })?;
+    reader.skip_whitespace();
+    if reader.pos != json.len() {
+        return None;
+    }
+    Some(members)
+}
+
+// deep enough for any header, without letting a crafted one overflow the
+// stack
+const MAX_DEPTH: usize = 64;
+
+struct JsonReader<'a> {
+    json: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> JsonReader<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.json.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn expect(&mut self, c: u8) -> Option<()> {
+        self.skip_whitespace();
+        if self.next()? != c {
+            return None;
+        }
+        Some(())
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    // the members of the object at pos, passing each to f
+    fn members<F>(&mut self, depth: usize, mut f: F) -> Option<()>
+    where
+        F: FnMut(String, Value) -> Option<()>,
+    {
+        self.expect(b'{')?;
+        self.skip_whitespace();
+        if self.peek()? == b'}' {
+            self.pos += 1;
+            return Some(());
+        }
+        loop {
+            self.expect(b'"')?;
+            let name = self.string()?;
+            self.expect(b':')?;
+            let value = self.value(depth)?;
+            f(name, value)?;
+            self.skip_whitespace();
+            match self.next()? {
+                b',' => {}
+                b'}' => return Some(()),
+                _ => return None,
+            }
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Option<Value> {
+        if depth == MAX_DEPTH {
+            return None;
+        }
+        self.skip_whitespace();
+        match self.peek()? {
+            b'"' => {
+                self.pos += 1;
+                self.string().map(Value::String)
+            }
+            b'{' => {
+                self.members(depth + 1, |_, _| Some(()))?;
+                Some(Value::Other)
+            }
+            b'[' => {
+                self.pos += 1;
+                self.skip_whitespace();
+                if self.peek()? == b']' {
+                    self.pos += 1;
+                    return Some(Value::Other);
+                }
+                loop {
+                    self.value(depth + 1)?;
+                    self.skip_whitespace();
+                    match self.next()? {
+                        b',' => {}
+                        b']' => return Some(Value::Other),
+                        _ => return None,
+                    }
+                }
+            }
+            b't' => self.literal(b"true"),
+            b'f' => self.literal(b"false"),
+            b'n' => self.literal(b"null"),
+            _ => self.number().map(Value::Number),
+        }
+    }
+
+    fn literal(&mut self, literal: &[u8]) -> Option<Value> {
+        if !self.json[self.pos..].starts_with(literal) {
+            return None;
+        }
+        self.pos += literal.len();
+        Some(Value::Other)
+    }
+
+    // -?(0|[1-9][0-9]*)(.[0-9]+)?([eE][+-]?[0-9]+)?
+    fn number(&mut self) -> Option<f64> {
+        let start = self.pos;
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        if self.peek()? == b'0' {
+            self.pos += 1;
+        } else {
+            self.digits()?;
+        }
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            self.digits()?;
+        }
+        if let Some(b'e') | Some(b'E') = self.peek() {
+            self.pos += 1;
+            if let Some(b'+') | Some(b'-') = self.peek() {
+                self.pos += 1;
+            }
+            self.digits()?;
+        }
+        std::str::from_utf8(&self.json[start..self.pos])
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    // at least one
+    fn digits(&mut self) -> Option<()> {
+        let start = self.pos;
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return None;
+        }
+        Some(())
+    }
+
+    // the rest of a string, after the opening quote
+    fn string(&mut self) -> Option<String> {
+        let mut out = Vec::new();
+        loop {
+            match self.next()? {
+                b'"' => return String::from_utf8(out).ok(),
+                b'\\' => {
+                    let c = match self.next()? {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.escaped_char()?,
+                        _ => return None,
+                    };
+                    out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                c if c < 0x20 => return None,
+                c => out.push(c),
+            }
+        }
+    }
+
+    // \uXXXX, or two of them for a surrogate pair
+    fn escaped_char(&mut self) -> Option<char> {
+        let high = self.hex4()?;
+        if !(0xd800..0xdc00).contains(&high) {
+            return char::from_u32(high);
+        }
+        if self.next()? != b'\\' || self.next()? != b'u' {
+            return None;
+        }
+        let low = self.hex4()?;
+        if !(0xdc00..0xe000).contains(&low) {
+            return None;
+        }
+        char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00))
+    }
+
+    fn hex4(&mut self) -> Option<u32> {
+        let hex = self.json.get(self.pos..self.pos + 4)?;
+        self.pos += 4;
+        u32::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 7515 appendix A.1, whose header and payload have line breaks
+    const A1_KEY: &str =
+        "AyM1SysPpbyDfgZld3umj1qzKObwVMkoqQ-EstJQLr_T-1qS0gZH75aKtMN3Yj0iPS4hcgUuTwjAzZr1Z9CAow";
+    const A1_TOKEN: &str = "eyJ0eXAiOiJKV1QiLA0KICJhbGciOiJIUzI1NiJ9\
+                            .eyJpc3MiOiJqb2UiLA0KICJleHAiOjEzMDA4MTkzODAsDQogImh0dHA6Ly9leGFtcGxlLmNvbS9pc19yb290Ijp0cnVlfQ\
+                            .dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+    const A1_PAYLOAD: &str =
+        "{\"iss\":\"joe\",\r\n \"exp\":1300819380,\r\n \"http://example.com/is_root\":true}";
+
+    #[test]
+    fn test_rfc7515_a1() {
+        let key = encoding::decode_base64_url(A1_KEY).unwrap();
+        let header = "{\"typ\":\"JWT\",\r\n \"alg\":\"HS256\"}";
+        assert_eq!(
+            sign_with_header(JwsAlgorithm::Hs256, &key, header, A1_PAYLOAD.as_bytes()).unwrap(),
+            A1_TOKEN
+        );
+        assert_eq!(
+            verify(JwsAlgorithm::Hs256, &key, A1_TOKEN).unwrap(),
+            A1_PAYLOAD.as_bytes()
+        );
+
+        let claims = Claims::from_payload(A1_PAYLOAD.as_bytes()).unwrap();
+        assert_eq!(claims.exp(), Some(1300819380.0));
+        assert_eq!(claims.nbf(), None);
+        assert_eq!(
+            verify_jwt(JwsAlgorithm::Hs256, &key, A1_TOKEN, 1300819379, 0).unwrap(),
+            A1_PAYLOAD.as_bytes()
+        );
+        assert_eq!(
+            verify_jwt(JwsAlgorithm::Hs256, &key, A1_TOKEN, 1300819380, 0),
+            Err(JwsError::Expired)
+        );
+        assert!(verify_jwt(JwsAlgorithm::Hs256, &key, A1_TOKEN, 1300819380, 60).is_ok());
+    }
+
+    // checked against Python's hmac module
+    #[test]
+    fn test_hs384_hs512() {
+        let payload = br#"{"sub":"1234567890","iat":1516239022}"#;
+        let tokens = [
+            (
+                JwsAlgorithm::Hs384,
+                "eyJhbGciOiJIUzM4NCIsInR5cCI6IkpXVCJ9\
+                 .eyJzdWIiOiIxMjM0NTY3ODkwIiwiaWF0IjoxNTE2MjM5MDIyfQ\
+                 .kz_5PDFCSr3AyfdMR2ZAPimzLK56y7ZQmxiBS8TpnUpz30a5jCmPNzBezw_WLBjk",
+            ),
+            (
+                JwsAlgorithm::Hs512,
+                "eyJhbGciOiJIUzUxMiIsInR5cCI6IkpXVCJ9\
+                 .eyJzdWIiOiIxMjM0NTY3ODkwIiwiaWF0IjoxNTE2MjM5MDIyfQ\
+                 .GgbfXCUWGw8nhYm9dgkwi7aF9joWBRe71paFNhaTdbkijHQeyIV1ltkfl3vCmPn5YfcmoXFiYiTvQK5NS8VKrg",
+            ),
+        ];
+        for (alg, token) in tokens.iter() {
+            assert_eq!(sign(*alg, b"secret", payload), *token);
+            assert_eq!(verify(*alg, b"secret", token).unwrap(), payload);
+        }
+    }
+
+    #[test]
+    fn test_verify_rejects() {
+        let key = encoding::decode_base64_url(A1_KEY).unwrap();
+        let payload = A1_TOKEN.split('.').nth(1).unwrap();
+        let forged =
+            |header: &str| format!("{}.{}.", encoding::base64_url(header.as_bytes()), payload);
+
+        assert_eq!(
+            verify(JwsAlgorithm::Hs256, &key, &forged(r#"{"alg":"none"}"#)),
+            Err(JwsError::UnsupportedAlgorithm("none".to_string()))
+        );
+        assert_eq!(
+            verify(JwsAlgorithm::Hs256, &key, &forged(r#"{"typ":"JWT"}"#)),
+            Err(JwsError::MissingAlgorithm)
+        );
+        assert_eq!(
+            verify(JwsAlgorithm::Hs512, &key, A1_TOKEN),
+            Err(JwsError::AlgorithmMismatch {
+                expected: JwsAlgorithm::Hs512,
+                found: JwsAlgorithm::Hs256
+            })
+        );
+        assert_eq!(
+            verify(
+                JwsAlgorithm::Hs256,
+                &key,
+                &forged(r#"{"alg":"HS256","crit":["exp"],"exp":1}"#)
+            ),
+            Err(JwsError::CriticalHeader)
+        );
+        assert_eq!(
+            verify(JwsAlgorithm::Hs256, &key, &forged(r#"{"alg":"HS256"}"#)),
+            Err(JwsError::InvalidSignature)
+        );
+        assert_eq!(
+            verify(JwsAlgorithm::Hs256, b"wrong key", A1_TOKEN),
+            Err(JwsError::InvalidSignature)
+        );
+        assert_eq!(
+            sign_with_header(JwsAlgorithm::Hs384, &key, r#"{"alg":"HS256"}"#, b""),
+            Err(JwsError::AlgorithmMismatch {
+                expected: JwsAlgorithm::Hs384,
+                found: JwsAlgorithm::Hs256
+            })
+        );
+
+        // the wrong number of parts, padding and bad headers
+        for token in [
+            "",
+            "a.b",
+            &format!("{}.", A1_TOKEN),
+            &format!("{}=", A1_TOKEN),
+            &forged(r#"{"alg":"HS256","alg":"HS256"}"#),
+            &forged(r#"{"alg":"HS256"} {}"#),
+            &forged(r#"["HS256"]"#),
+            &forged(r#"{"alg":HS256}"#),
+        ]
+        .iter()
+        {
+            assert_eq!(
+                verify(JwsAlgorithm::Hs256, &key, token),
+                Err(JwsError::Malformed)
+            );
+        }
+    }
+
+    #[test]
+    fn test_claims() {
+        let claims =
+            Claims::from_payload(br#"{"nbf":1000,"exp":2000.5,"aud":["a",{"b":[]}]}"#).unwrap();
+        assert_eq!(claims.validate(999, 0), Err(JwsError::NotYetValid));
+        assert!(claims.validate(999, 1).is_ok());
+        assert!(claims.validate(1000, 0).is_ok());
+        assert!(claims.validate(2000, 0).is_ok());
+        assert_eq!(claims.validate(2001, 0), Err(JwsError::Expired));
+        assert!(Claims::default().validate(0, 0).is_ok());
+
+        assert_eq!(
+            Claims::from_payload(br#"{"exp":"2000"}"#),
+            Err(JwsError::InvalidClaim("exp"))
+        );
+        assert_eq!(Claims::from_payload(b"2000"), Err(JwsError::Malformed));
+    }
+
+    #[test]
+    fn test_parse_object() {
+        let object = parse_object(
+            br#" { "a" : "\u00e9\ud83d\ude00\n" , "b":-1.5e2, "c":[true,false,null,{}] } "#,
+        )
+        .unwrap();
+        assert_eq!(
+            object,
+            [
+                (
+                    "a".to_string(),
+                    Value::String("\u{e9}\u{1f600}\n".to_string())
+                ),
+                ("b".to_string(), Value::Number(-150.0)),
+                ("c".to_string(), Value::Other),
+            ]
+        );
+        assert_eq!(parse_object(b"{}").unwrap(), []);
+
+        for json in [
+            &br#"{"a":01}"#[..],
+            br#"{"a":1,}"#,
+            br#"{"a":"\ud83d"}"#,
+            b"{\"a\":\"\n\"}",
+            br#"{"a":[1 2]}"#,
+            br#"{a:1}"#,
+        ]
+        .iter()
+        {
+            assert_eq!(parse_object(json), None);
+        }
+        let deep = format!("{{\"a\":{}{}}}", "[".repeat(100), "]".repeat(100));
+        assert_eq!(parse_object(deep.as_bytes()), None);
+    }
+}
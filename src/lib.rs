@@ -4,6 +4,50 @@ Shared library code which is common across different SHA algorithms
 
 */
 
+#[macro_use]
+mod truncated;
+
+pub mod algorithm;
+pub mod digest;
+pub mod digest_auth;
+pub mod digest_fields;
+pub mod drbg;
+mod encoding;
+pub mod file;
+pub mod hmac;
+pub mod jws;
+pub mod mgf;
+pub mod oaep;
+pub mod observer;
+pub mod oci;
+pub mod otp;
+pub mod pbkdf2;
+pub mod pkcs1;
+pub mod pss;
+pub mod rfc6979;
+pub mod scram;
+pub mod scrypt;
+pub mod sha224;
 pub mod sha256;
+pub mod sha384;
 pub mod sha512;
+pub mod sha512_224;
+pub mod sha512_256;
+pub mod sha_crypt;
+pub mod sigv4;
+pub mod sri;
+pub mod trace;
 pub mod util;
+pub mod webhook;
+
+#[cfg(feature = "tokio")]
+pub mod async_io;
+
+// The interface shared by the streaming hashers, so that code which only
+// feeds bytes through a hasher can be written once for every algorithm
+pub trait StreamHasher {
+    type Output;
+
+    fn update(&mut self, msg: &[u8]);
+    fn finalize(self) -> Self::Output;
+}
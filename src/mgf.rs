@@ -0,0 +1,72 @@
+/*
+
+MGF1, the mask generation function of RFC 8017 B.2.1, used by both
+EMSA-PSS (crate::pss) and EME-OAEP (crate::oaep).
+
+The mask is the concatenation of Hash(seed || counter) for a 32-bit big
+endian counter counting up from zero, cut down to the length asked for.
+
+*/
+
+use crate::algorithm::Algorithm;
+
+pub fn mgf1(algorithm: Algorithm, seed: &[u8], mask_len: usize) -> Vec<u8> {
+    // "mask too long", which no RSA modulus comes anywhere near
+    assert!(
+        (mask_len as u64) <= (1u64 << 32) * algorithm.output_size() as u64,
+        "mask too long"
+    );
+
+    let mut mask = Vec::with_capacity(mask_len + algorithm.output_size());
+    let mut counter = 0u32;
+    while mask.len() < mask_len {
+        let mut hasher = algorithm.hasher();
+        hasher.update(seed);
+        hasher.update(&counter.to_be_bytes());
+        mask.extend_from_slice(&hasher.finalize());
+        counter = counter.wrapping_add(1);
+    }
+    mask.truncate(mask_len);
+    mask
+}
+
+// xor the mask into the bytes, which are the same length as the mask
+pub(crate) fn apply_mask(bytes: &mut [u8], mask: &[u8]) {
+    for (byte, mask) in bytes.iter_mut().zip(mask.iter()) {
+        *byte ^= mask;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mgf1;
+    use crate::algorithm::Algorithm;
+
+    #[test]
+    fn test_mgf1() {
+        // the first block is just Hash(seed || 00000000)
+        let mask = mgf1(Algorithm::Sha256, b"seed", 100);
+        assert_eq!(mask.len(), 100);
+        assert_eq!(mask[..32], Algorithm::Sha256.hash(b"seed\0\0\0\0")[..]);
+        assert_eq!(mask[32..64], Algorithm::Sha256.hash(b"seed\0\0\0\x01")[..]);
+        assert_eq!(mask[96..], Algorithm::Sha256.hash(b"seed\0\0\0\x03")[..4]);
+
+        // shorter masks are prefixes of longer ones
+        assert_eq!(
+            mgf1(Algorithm::Sha512, b"seed", 10),
+            mgf1(Algorithm::Sha512, b"seed", 200)[..10]
+        );
+        assert!(mgf1(Algorithm::Sha512, b"seed", 0).is_empty());
+    }
+
+    // the MGF1-SHA-256 example in Wikipedia's Mask generation function
+    // article
+    #[test]
+    fn test_mgf1_known_answer() {
+        assert_eq!(
+            crate::encoding::hex(&mgf1(Algorithm::Sha256, b"bar", 50)),
+            "382576a7841021cc28fc4c0948753fb8312090cea942ea4c\
+             4e735d10dc724b155f9f6069f289d61daca0cb814502ef04eae1"
+        );
+    }
+}
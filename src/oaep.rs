@@ -0,0 +1,250 @@
+/*
+
+EME-OAEP encoding and decoding, see RFC 8017 7.1.1 step 2 and 7.1.2
+step 3. This is the padding transform of RSAES-OAEP, without the RSA
+arithmetic: encode produces the encoded message to be encrypted, and
+decode takes the encoded message recovered by decryption.
+
+    EM = 0x00 || maskedSeed || maskedDB
+    DB = Hash(label) || PS || 0x01 || M
+
+k is the length of the RSA modulus in bytes. The seed is passed in
+rather than generated here, since the crate has no source of randomness,
+and has to be fresh random bytes, as many as the hash output.
+
+Decoding reports every kind of malformed padding as the same error and
+doesn't branch on where the padding goes wrong, since telling them apart
+is the oracle in Manger's attack.
+
+*/
+
+use crate::algorithm::Algorithm;
+use crate::mgf::{apply_mask, mgf1};
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum OaepError {
+    // k < 2 * hLen + 2, which leaves no room for a message at all
+    ModulusTooShort { minimum: usize },
+    MessageTooLong { maximum: usize },
+    SeedLength { expected: usize, found: usize },
+    Decryption,
+}
+
+impl fmt::Display for OaepError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OaepError::ModulusTooShort { minimum } => write!(
+                f,
+                "the modulus is too short, at least {} bytes are needed",
+                minimum
+            ),
+            OaepError::MessageTooLong { maximum } => write!(
+                f,
+                "message too long, at most {} bytes fit in the modulus",
+                maximum
+            ),
+            OaepError::SeedLength { expected, found } => write!(
+                f,
+                "invalid seed length: expected {} bytes, found {}",
+                expected, found
+            ),
+            OaepError::Decryption => f.write_str("decryption error"),
+        }
+    }
+}
+
+impl Error for OaepError {}
+
+pub fn encode(
+    algorithm: Algorithm,
+    msg: &[u8],
+    label: &[u8],
+    seed: &[u8],
+    k: usize,
+) -> Result<Vec<u8>, OaepError> {
+    let h_len = algorithm.output_size();
+    if k < 2 * h_len + 2 {
+        return Err(OaepError::ModulusTooShort {
+            minimum: 2 * h_len + 2,
+        });
+    }
+    if msg.len() > k - 2 * h_len - 2 {
+        return Err(OaepError::MessageTooLong {
+            maximum: k - 2 * h_len - 2,
+        });
+    }
+    if seed.len() != h_len {
+        return Err(OaepError::SeedLength {
+            expected: h_len,
+            found: seed.len(),
+        });
+    }
+
+    let mut em = vec![0u8; k];
+    let (masked_seed, masked_db) = em[1..].split_at_mut(h_len);
+
+    masked_db[..h_len].copy_from_slice(&algorithm.hash(label));
+    masked_db[k - h_len - 2 - msg.len()] = 0x01;
+    masked_db[k - h_len - 1 - msg.len()..].copy_from_slice(msg);
+    apply_mask(masked_db, &mgf1(algorithm, seed, k - h_len - 1));
+
+    masked_seed.copy_from_slice(seed);
+    apply_mask(masked_seed, &mgf1(algorithm, masked_db, h_len));
+    Ok(em)
+}
+
+pub fn decode(
+    algorithm: Algorithm,
+    em: &[u8],
+    label: &[u8],
+    k: usize,
+) -> Result<Vec<u8>, OaepError> {
+    let h_len = algorithm.output_size();
+    if k < 2 * h_len + 2 || em.len() != k {
+        return Err(OaepError::Decryption);
+    }
+
+    let mut seed = em[1..1 + h_len].to_vec();
+    let mut db = em[1 + h_len..].to_vec();
+    apply_mask(&mut seed, &mgf1(algorithm, &db, h_len));
+    apply_mask(&mut db, &mgf1(algorithm, &seed, k - h_len - 1));
+
+    // bad is nonzero once anything is wrong, and the scan for the 0x01
+    // separator runs over the whole of PS || 0x01 || M whatever it finds
+    let mut bad = em[0] as usize;
+    bad |= !crate::util::ct_eq(&db[..h_len], &algorithm.hash(label)) as usize;
+
+    let mut found = 0usize;
+    let mut index = 0usize;
+    for (i, byte) in db[h_len..].iter().enumerate() {
+        let zero = is_zero(*byte as usize);
+        let one = is_zero(*byte as usize ^ 1);
+        // the first 0x01, before which everything has to be zero
+        let first = one & !found & 1;
+        index |= i & first.wrapping_neg();
+        bad |= !found & !zero & !one & 1;
+        found |= one;
+    }
+    bad |= !found & 1;
+
+    let result = if bad == 0 {
+        Ok(db[h_len + index + 1..].to_vec())
+    } else {
+        Err(OaepError::Decryption)
+    };
+
+    #[cfg(feature = "zeroize")]
+    {
+        use zeroize::Zeroize;
+        seed.zeroize();
+        db.zeroize();
+    }
+
+    result
+}
+
+// 1 if x is zero, 0 otherwise, without a branch
+fn is_zero(x: usize) -> usize {
+    let x = std::hint::black_box(x);
+    1 & !((x | x.wrapping_neg()) >> (usize::BITS - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::decode_hex;
+
+    const MSG: &[u8] = b"the quick brown fox";
+    const LABEL: &[u8] = b"label";
+
+    // Encoded messages recovered from RSAES-OAEP ciphertexts made by
+    // OpenSSL with 2048 bit keys, with MSG and LABEL. The RFC 8017 and
+    // mbed TLS vectors use SHA-1, which isn't an Algorithm here.
+    const VECTORS: [(Algorithm, &str); 2] = [
+        (
+            Algorithm::Sha256,
+            "00299927a50bc4c056f38698b4e7f1958c39acc83ceb6cf83b22d3d0db62ec82\
+             ae27f0507fb3327ddb431983eddfdf42d19eb83d249a67168557c0dad2faf97b\
+             3c539aca890e741d1d1c18d250f99a51e507ec506e5d37a8d610a1b74f5a94ba\
+             992dd428b546beaa1ec62108c2f7c58f41f4cecc402d1493d371ea646109eb50\
+             a18dab432451abaf5c425b037c5a2988588975505478de4e91c96f68eb3e2382\
+             521dfad5d2e2ccf9b10ad4d2b5e971f97e28bae1ac12d44e026edc3e46bff858\
+             79024b4a2eaf9cc0e0bb19268764935e2ddde49ba110075962eb33a6ae0b29fd\
+             c6af559192d2e1f70d6f5b7801c2749d92b0233df8dcfd04bfa0797c750089b1",
+        ),
+        (
+            Algorithm::Sha512,
+            "00af87ed157d5fa88837350898cda1543e335fbf0146e3dc209d0aba5c79a937\
+             be9811fca117870d444599022fcefffa632d1d8f6e49ff93dd0041735d1384ba\
+             f2c1f1f5e77bb3a3f7a5197ab1713aeeb6df044dc0a2075831f5af5964e34f75\
+             00a3e49780a04822b758af79b307af2086d82dca2dfaa1b3ddf85358575db1a3\
+             c97ca416f378d8d0f1846b5964bce3d269f136926026d2084eaafc2cc66b70b7\
+             a2187d673e0eaee5d15dfb47d9265bd60b6fe79e31222bfcda6aa858792b3b27\
+             41e5ee0941ebc9a14443dcf79d6b73829c965b541673b77d11898d6494420f19\
+             2d624df45816633438d30d692bf99f369f36a0d695828e824c691175d450b3f5",
+        ),
+    ];
+
+    #[test]
+    fn test_oaep_decode() {
+        for (algorithm, em) in VECTORS.iter() {
+            let em = decode_hex(em).unwrap();
+            assert_eq!(decode(*algorithm, &em, LABEL, 256), Ok(MSG.to_vec()));
+
+            assert_eq!(
+                decode(*algorithm, &em, b"other label", 256),
+                Err(OaepError::Decryption)
+            );
+            assert_eq!(
+                decode(*algorithm, &em[1..], LABEL, 255),
+                Err(OaepError::Decryption)
+            );
+            for i in [0, 1, 100, 255].iter() {
+                let mut em = em.clone();
+                em[*i] ^= 0x01;
+                assert_eq!(
+                    decode(*algorithm, &em, LABEL, 256),
+                    Err(OaepError::Decryption)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_oaep_encode() {
+        for algorithm in [Algorithm::Sha256, Algorithm::Sha512].iter() {
+            let h_len = algorithm.output_size();
+            let seed = vec![0x5a; h_len];
+
+            // the empty message and the longest which fits
+            for len in [0, 256 - 2 * h_len - 2].iter() {
+                let msg = vec![0xa5; *len];
+                let em = encode(*algorithm, &msg, LABEL, &seed, 256).unwrap();
+                assert_eq!(em.len(), 256);
+                assert_eq!(em[0], 0);
+                assert_eq!(decode(*algorithm, &em, LABEL, 256), Ok(msg));
+            }
+
+            assert_eq!(
+                encode(*algorithm, &vec![0; 256 - 2 * h_len - 1], LABEL, &seed, 256),
+                Err(OaepError::MessageTooLong {
+                    maximum: 256 - 2 * h_len - 2
+                })
+            );
+            assert_eq!(
+                encode(*algorithm, MSG, LABEL, &seed[1..], 256),
+                Err(OaepError::SeedLength {
+                    expected: h_len,
+                    found: h_len - 1
+                })
+            );
+        }
+
+        assert_eq!(
+            encode(Algorithm::Sha512, b"", LABEL, &[0; 64], 129),
+            Err(OaepError::ModulusTooShort { minimum: 130 })
+        );
+    }
+}
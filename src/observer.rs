@@ -0,0 +1,152 @@
+/*
+
+Hooks into the hash computation, for looking at the internals of the
+compression function: each block as it is parsed, the message schedule
+built from it, the working variables A-H after every round and the
+intermediate hash value after the final additions.
+
+Every method has an empty default, and the compression function is generic
+over the observer, so hashing with NoObserver compiles down to exactly the
+same code as if the hooks weren't there.
+
+W is the word type, u32 for SHA-256 and u64 for SHA-512.
+
+*/
+
+pub trait Observer<W> {
+    // a new block is about to be compressed, hashes is the intermediate
+    // hash value going into it
+    fn block(&mut self, _block: &[u8], _hashes: &[W; 8]) {}
+
+    // W[0..64] for SHA-256, W[0..80] for SHA-512
+    fn message_schedule(&mut self, _schedule: &[W]) {}
+
+    // the working variables A, B, C, D, E, F, G, H after round t
+    fn round(&mut self, _t: usize, _vars: &[W; 8]) {}
+
+    // the intermediate hash value once the working variables have been
+    // added back in at the end of the block
+    fn block_done(&mut self, _hashes: &[W; 8]) {}
+}
+
+pub struct NoObserver;
+
+impl<W> Observer<W> for NoObserver {}
+
+#[cfg(test)]
+mod tests {
+    use super::Observer;
+    use crate::{sha256, sha512};
+
+    #[derive(Default)]
+    struct Recorder<W> {
+        blocks: Vec<Vec<u8>>,
+        schedules: Vec<Vec<W>>,
+        rounds: Vec<(usize, [W; 8])>,
+        hashes: Vec<[W; 8]>,
+    }
+
+    impl<W: Copy> Observer<W> for Recorder<W> {
+        fn block(&mut self, block: &[u8], _hashes: &[W; 8]) {
+            self.blocks.push(block.to_vec());
+        }
+
+        fn message_schedule(&mut self, schedule: &[W]) {
+            self.schedules.push(schedule.to_vec());
+        }
+
+        fn round(&mut self, t: usize, vars: &[W; 8]) {
+            self.rounds.push((t, *vars));
+        }
+
+        fn block_done(&mut self, hashes: &[W; 8]) {
+            self.hashes.push(*hashes);
+        }
+    }
+
+    // the "abc" examples from the NIST example computations for FIPS 180-4
+    #[test]
+    fn test_sha256_observer() {
+        let mut recorder = Recorder::default();
+        let hash = sha256::hash_observed(b"abc", &mut recorder);
+
+        assert_eq!(recorder.blocks.len(), 1);
+        assert_eq!(&recorder.blocks[0][..4], b"abc\x80");
+        assert_eq!(recorder.schedules[0].len(), 64);
+        assert_eq!(recorder.schedules[0][0], 0x61626380);
+        assert_eq!(recorder.schedules[0][15], 0x00000018);
+
+        assert_eq!(recorder.rounds.len(), 64);
+        assert_eq!(
+            recorder.rounds[0],
+            (
+                0,
+                [
+                    0x5d6aebcd, 0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xfa2a4622, 0x510e527f,
+                    0x9b05688c, 0x1f83d9ab
+                ]
+            )
+        );
+        assert_eq!(
+            recorder.rounds[63],
+            (
+                63,
+                [
+                    0x506e3058, 0xd39a2165, 0x04d24d6c, 0xb85e2ce9, 0x5ef50f24, 0xfb121210,
+                    0x948d25b6, 0x961f4894
+                ]
+            )
+        );
+
+        assert_eq!(Some(recorder.hashes[0].into()), hash);
+    }
+
+    #[test]
+    fn test_sha512_observer() {
+        let msg = [b'a'; 200];
+        let mut recorder = Recorder::default();
+        let hash = sha512::hash_observed(&msg, &mut recorder);
+
+        assert_eq!(recorder.blocks.len(), 2);
+        assert_eq!(recorder.schedules.len(), 2);
+        assert!(recorder.schedules.iter().all(|w| w.len() == 80));
+        assert_eq!(recorder.rounds.len(), 160);
+        assert_eq!(recorder.hashes.len(), 2);
+        assert_eq!(Some(recorder.hashes[1].into()), hash);
+
+        let mut recorder = Recorder::default();
+        sha512::hash_observed(b"abc", &mut recorder);
+        assert_eq!(
+            recorder.rounds[0],
+            (
+                0,
+                [
+                    0xf6afceb8bcfcddf5,
+                    0x6a09e667f3bcc908,
+                    0xbb67ae8584caa73b,
+                    0x3c6ef372fe94f82b,
+                    0x58cb02347ab51f91,
+                    0x510e527fade682d1,
+                    0x9b05688c2b3e6c1f,
+                    0x1f83d9abfb41bd6b
+                ]
+            )
+        );
+        assert_eq!(
+            recorder.rounds[79],
+            (
+                79,
+                [
+                    0x73a54f399fa4b1b2,
+                    0x10d9c4c4295599f6,
+                    0xd67806db8b148677,
+                    0x654ef9abec389ca9,
+                    0xd08446aa79693ed7,
+                    0x9bb4d39778c07f9e,
+                    0x25c96a7768fb2aa3,
+                    0xceb9fc3691ce8326
+                ]
+            )
+        );
+    }
+}
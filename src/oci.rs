@@ -0,0 +1,513 @@
+/*
+
+The content addresses of the OCI image spec (and Docker registries),
+such as
+
+    sha256:44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a
+
+which is the digest of the two bytes {}. The grammar is
+
+    digest                ::= algorithm ":" encoded
+    algorithm             ::= algorithm-component (algorithm-separator algorithm-component)*
+    algorithm-component   ::= [a-z0-9]+
+    algorithm-separator   ::= [+._-]
+    encoded               ::= [a-zA-Z0-9=_-]+
+
+and the registered algorithms constrain the encoded part further: sha256
+is 64 and sha512 128 lower-case hex digits. Digests with unregistered
+algorithms are valid, as the spec allows for new ones, but can't be
+verified.
+
+A descriptor gives a blob's digest and size. Verification checks the
+size before hashing, and a reader is never read past the size, so a
+blob which is too large is rejected without being read in full.
+
+*/
+
+use crate::algorithm::{Algorithm, DynHasher};
+use crate::encoding;
+use crate::file::FileError;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+// registered algorithms which can be verified
+const SUPPORTED: [(Algorithm, &str); 2] =
+    [(Algorithm::Sha256, "sha256"), (Algorithm::Sha512, "sha512")];
+
+#[derive(Debug)]
+pub enum OciError {
+    // not in the grammar, or not the encoding of a registered algorithm
+    Malformed,
+    UnsupportedAlgorithm(String),
+    // the expected size
+    SizeMismatch(u64),
+    DigestMismatch,
+    Io(io::Error),
+    File(FileError),
+}
+
+impl fmt::Display for OciError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OciError::Malformed => f.write_str("malformed digest"),
+            OciError::UnsupportedAlgorithm(alg) => {
+                write!(f, "unsupported digest algorithm {}", alg)
+            }
+            OciError::SizeMismatch(size) => write!(f, "the blob isn't the expected {} bytes", size),
+            OciError::DigestMismatch => f.write_str("the blob doesn't match the digest"),
+            OciError::Io(err) => err.fmt(f),
+            OciError::File(err) => err.fmt(f),
+        }
+    }
+}
+
+impl Error for OciError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            OciError::Io(err) => Some(err),
+            OciError::File(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for OciError {
+    fn from(err: io::Error) -> OciError {
+        OciError::Io(err)
+    }
+}
+
+impl From<FileError> for OciError {
+    fn from(err: FileError) -> OciError {
+        OciError::File(err)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Digest {
+    algorithm: String,
+    encoded: String,
+}
+
+impl Digest {
+    // the sha256 or sha512 digest of the data
+    pub fn new(algorithm: Algorithm, data: &[u8]) -> Result<Digest, OciError> {
+        let name = SUPPORTED
+            .iter()
+            .find(|(alg, _)| *alg == algorithm)
+            .map(|(_, name)| *name)
+            .ok_or_else(|| OciError::UnsupportedAlgorithm(algorithm.name().to_string()))?;
+        Ok(Digest {
+            algorithm: name.to_string(),
+            encoded: encoding::hex(&algorithm.hash(data)),
+        })
+    }
+
+    // as written, e.g. sha256
+    pub fn algorithm(&self) -> &str {
+        &self.algorithm
+    }
+
+    pub fn encoded(&self) -> &str {
+        &self.encoded
+    }
+
+    // the hash algorithm, if it's one which can be verified
+    pub fn hash_algorithm(&self) -> Option<Algorithm> {
+        SUPPORTED
+            .iter()
+            .find(|(_, name)| *name == self.algorithm)
+            .map(|(alg, _)| *alg)
+    }
+
+    pub fn hasher(&self) -> Result<Box<dyn DynHasher>, OciError> {
+        self.hash_algorithm()
+            .map(Algorithm::hasher)
+            .ok_or_else(|| OciError::UnsupportedAlgorithm(self.algorithm.clone()))
+    }
+
+    // compared in constant time
+    pub fn matches(&self, digest: &[u8]) -> bool {
+        crate::util::ct_eq(encoding::hex(digest).as_bytes(), self.encoded.as_bytes())
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.algorithm, self.encoded)
+    }
+}
+
+impl FromStr for Digest {
+    type Err = OciError;
+
+    fn from_str(s: &str) -> Result<Digest, OciError> {
+        let mut parts = s.splitn(2, ':');
+        let algorithm = parts.next().unwrap();
+        let encoded = parts.next().ok_or(OciError::Malformed)?;
+
+        let component = |c: &str| {
+            !c.is_empty()
+                && c.bytes()
+                    .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit())
+        };
+        if !algorithm.split(['+', '.', '_', '-']).all(component) {
+            return Err(OciError::Malformed);
+        }
+        if encoded.is_empty()
+            || !encoded
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"=_-".contains(&b))
+        {
+            return Err(OciError::Malformed);
+        }
+
+        let digest = Digest {
+            algorithm: algorithm.to_string(),
+            encoded: encoded.to_string(),
+        };
+        if let Some(alg) = digest.hash_algorithm() {
+            if encoded.len() != alg.output_size() * 2
+                || !encoded
+                    .bytes()
+                    .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+            {
+                return Err(OciError::Malformed);
+            }
+        }
+        Ok(digest)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Digest {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Digest {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Digest, D::Error> {
+        let digest = String::deserialize(deserializer)?;
+        digest.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+// The parts of a descriptor which identify the content. Other fields, such
+// as urls and annotations, are ignored when deserializing.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Descriptor {
+    #[cfg_attr(feature = "serde", serde(rename = "mediaType"))]
+    pub media_type: String,
+    pub digest: Digest,
+    pub size: u64,
+}
+
+impl Descriptor {
+    // a descriptor of the data, with its sha256 digest
+    pub fn new(media_type: &str, data: &[u8]) -> Descriptor {
+        Descriptor {
+            media_type: media_type.to_string(),
+            digest: Digest::new(Algorithm::Sha256, data).unwrap(),
+            size: data.len() as u64,
+        }
+    }
+
+    pub fn verify(&self, data: &[u8]) -> Result<(), OciError> {
+        self.verify_reader(data)
+    }
+
+    // Reads the blob to the end, but no further than one byte past the size
+    pub fn verify_reader<R: Read>(&self, reader: R) -> Result<(), OciError> {
+        let mut hasher = self.digest.hasher()?;
+        let mut reader = reader.take(self.size.saturating_add(1));
+        let mut buffer = vec![0u8; 64 * 1024];
+        let mut size = 0u64;
+        loop {
+            match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => {
+                    hasher.update(&buffer[..n]);
+                    size += n as u64;
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        self.check(size, hasher.finalize())
+    }
+
+    pub fn verify_file<P: AsRef<Path>>(&self, path: P) -> Result<(), OciError> {
+        let path = path.as_ref();
+        let hasher = self.digest.hasher()?;
+        let size = path
+            .metadata()
+            .map_err(|err| FileError::new(path, err))?
+            .len();
+        if size != self.size {
+            return Err(OciError::SizeMismatch(self.size));
+        }
+        let digest = crate::file::hash_file(path, hasher)?;
+        self.check(size, digest)
+    }
+
+    fn check(&self, size: u64, digest: Vec<u8>) -> Result<(), OciError> {
+        if size != self.size {
+            return Err(OciError::SizeMismatch(self.size));
+        }
+        if !self.digest.matches(&digest) {
+            return Err(OciError::DigestMismatch);
+        }
+        Ok(())
+    }
+}
+
+// where an OCI image layout keeps a blob, blobs/<algorithm>/<encoded>
+pub fn blob_path<P: AsRef<Path>>(layout: P, digest: &Digest) -> PathBuf {
+    layout
+        .as_ref()
+        .join("blobs")
+        .join(&digest.algorithm)
+        .join(&digest.encoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // the empty descriptor of the image spec
+    const EMPTY: &str = "sha256:44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a";
+
+    #[test]
+    fn test_parse() {
+        let digest = EMPTY.parse::<Digest>().unwrap();
+        assert_eq!(digest, Digest::new(Algorithm::Sha256, b"{}").unwrap());
+        assert_eq!(digest.algorithm(), "sha256");
+        assert_eq!(digest.hash_algorithm(), Some(Algorithm::Sha256));
+        assert_eq!(digest.to_string(), EMPTY);
+
+        let sha512 = Digest::new(Algorithm::Sha512, b"{}").unwrap();
+        assert_eq!(sha512.encoded().len(), 128);
+        assert_eq!(sha512.to_string().parse::<Digest>().unwrap(), sha512);
+
+        // the spec's examples of other algorithms, which are valid but
+        // can't be verified
+        for s in [
+            "multihash+base58:QmRZxt2b1FVZPNqd8hsiykDL3TdBDeTSPX9Kv46HmX4Gx8",
+            "sha256+b64u:LCa0a2j_xo_5m0U8HTBBNBNCLXBkg7-g-YpeiGJm564",
+        ]
+        .iter()
+        {
+            let digest = s.parse::<Digest>().unwrap();
+            assert_eq!(digest.hash_algorithm(), None);
+            assert!(matches!(
+                digest.hasher(),
+                Err(OciError::UnsupportedAlgorithm(_))
+            ));
+        }
+
+        for s in [
+            "",
+            "sha256",
+            "sha256:",
+            ":abc",
+            "SHA256:44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a",
+            "sha256:44136FA355B3678A1146AD16F7E8649E94FB4FC21FE77E8310C060F61CAAFF8A",
+            "sha256:44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8",
+            "sha512:44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a",
+            "sha256+:abc",
+            "sha256..b64:abc",
+            "foo:a/b",
+            "foo:abc:def",
+        ]
+        .iter()
+        {
+            assert!(
+                matches!(s.parse::<Digest>(), Err(OciError::Malformed)),
+                "{}",
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn test_verify() {
+        let descriptor = Descriptor {
+            media_type: "application/vnd.oci.empty.v1+json".to_string(),
+            digest: EMPTY.parse().unwrap(),
+            size: 2,
+        };
+        assert!(descriptor.verify(b"{}").is_ok());
+        assert!(matches!(
+            descriptor.verify(b"[]"),
+            Err(OciError::DigestMismatch)
+        ));
+        assert!(matches!(
+            descriptor.verify(b"{ }"),
+            Err(OciError::SizeMismatch(2))
+        ));
+        assert!(matches!(
+            descriptor.verify(b"{"),
+            Err(OciError::SizeMismatch(2))
+        ));
+
+        // an endless reader is cut off just past the size
+        let reader = io::repeat(b'x');
+        assert!(matches!(
+            descriptor.verify_reader(reader),
+            Err(OciError::SizeMismatch(2))
+        ));
+
+        let blob: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let descriptor = Descriptor::new("application/octet-stream", &blob);
+        assert!(descriptor.verify_reader(io::Cursor::new(&blob)).is_ok());
+    }
+
+    // a directory which is removed again when the test ends, even when it
+    // fails
+    struct ScratchDir(PathBuf);
+
+    impl ScratchDir {
+        fn new(name: &str) -> ScratchDir {
+            let path = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+            fs::create_dir_all(&path).unwrap();
+            ScratchDir(path)
+        }
+    }
+
+    impl Drop for ScratchDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn read_json(path: &Path) -> serde_json::Value {
+        serde_json::from_slice(&fs::read(path).unwrap()).unwrap()
+    }
+
+    fn descriptor(value: &serde_json::Value) -> Descriptor {
+        Descriptor {
+            media_type: value["mediaType"].as_str().unwrap().to_string(),
+            digest: value["digest"].as_str().unwrap().parse().unwrap(),
+            size: value["size"].as_u64().unwrap(),
+        }
+    }
+
+    // the image layout in tests/fixtures/oci, checked from index.json down
+    #[test]
+    fn test_image_layout() {
+        let layout = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/oci");
+        assert_eq!(
+            read_json(&layout.join("oci-layout"))["imageLayoutVersion"],
+            "1.0.0"
+        );
+
+        let index = read_json(&layout.join("index.json"));
+        let manifests: Vec<Descriptor> = index["manifests"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(descriptor)
+            .collect();
+        assert_eq!(
+            manifests,
+            vec![Descriptor {
+                media_type: "application/vnd.oci.image.manifest.v1+json".to_string(),
+                digest: "sha256:d73afd104f1cc3c1f961fe7a36fb1a1c322ed5a16903652caa492f2d723cbcac"
+                    .parse()
+                    .unwrap(),
+                size: 401,
+            }]
+        );
+
+        let mut blobs = Vec::new();
+        for manifest in manifests.iter() {
+            let path = blob_path(&layout, &manifest.digest);
+            manifest.verify_file(&path).unwrap();
+            let manifest = read_json(&path);
+            blobs.push(descriptor(&manifest["config"]));
+            blobs.extend(
+                manifest["layers"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(descriptor),
+            );
+        }
+        assert_eq!(
+            blobs
+                .iter()
+                .map(|blob| (blob.media_type.as_str(), blob.digest.to_string(), blob.size))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "application/vnd.oci.image.config.v1+json",
+                    "sha256:7b009222ed88394dc9005871f55cae9a30c00ee05cf2449e7da24fe32110bafd"
+                        .to_string(),
+                    352
+                ),
+                (
+                    "application/vnd.oci.image.layer.v1.tar+gzip",
+                    "sha256:b89fa47ed3f070594686630b4e4b1923a1746e50e3989169b614ba6108c770be"
+                        .to_string(),
+                    148
+                ),
+            ]
+        );
+        for blob in blobs.iter() {
+            let path = blob_path(&layout, &blob.digest);
+            blob.verify_file(&path).unwrap();
+            blob.verify_reader(fs::File::open(&path).unwrap()).unwrap();
+        }
+
+        // a corrupted layer, then a truncated one, in a copy of the layout
+        let layer = &blobs[1];
+        let scratch = ScratchDir::new("oci_layout");
+        let layer_path = blob_path(&scratch.0, &layer.digest);
+        fs::create_dir_all(layer_path.parent().unwrap()).unwrap();
+        let mut data = fs::read(blob_path(&layout, &layer.digest)).unwrap();
+        data[100] ^= 1;
+        fs::write(&layer_path, &data).unwrap();
+        assert!(matches!(
+            layer.verify_file(&layer_path),
+            Err(OciError::DigestMismatch)
+        ));
+        fs::write(&layer_path, &data[1..]).unwrap();
+        assert!(matches!(
+            layer.verify_file(&layer_path),
+            Err(OciError::SizeMismatch(148))
+        ));
+        assert!(matches!(
+            layer.verify_file(scratch.0.join("blobs/sha256/missing")),
+            Err(OciError::File(FileError::NotFound(_)))
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_descriptor_serde() {
+        let json = format!(
+            r#"{{"mediaType":"application/vnd.oci.empty.v1+json","digest":"{}","size":2,"data":"e30="}}"#,
+            EMPTY
+        );
+        let descriptor: Descriptor = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            descriptor,
+            Descriptor::new("application/vnd.oci.empty.v1+json", b"{}")
+        );
+        assert_eq!(
+            serde_json::to_string(&descriptor).unwrap(),
+            format!(
+                r#"{{"mediaType":"application/vnd.oci.empty.v1+json","digest":"{}","size":2}}"#,
+                EMPTY
+            )
+        );
+        assert!(serde_json::from_str::<Descriptor>(&json.replace("sha256:", "sha256:x")).is_err());
+    }
+}
@@ -0,0 +1,519 @@
+/*
+
+One-time passwords: HOTP from RFC 4226 and TOTP from RFC 6238, with
+HMAC over any of the crate's algorithms. RFC 6238 allows HMAC-SHA-256
+and HMAC-SHA-512 besides the original HMAC-SHA-1, which this crate
+doesn't have.
+
+HOTP truncates HMAC(K, C), C being an 8 byte big-endian counter, to a
+31 bit number with the dynamic truncation of RFC 4226 5.3, and takes its
+last few decimal digits. TOTP is HOTP with the counter taken from the
+time, T = (unix time - T0) / step.
+
+Secrets are exchanged as otpauth:// URIs, the "Key Uri Format" of Google
+Authenticator:
+
+    otpauth://totp/Example:alice@example.com?secret=JBSWY3DPEHPK3PXP&issuer=Example&algorithm=SHA256&digits=8&period=30
+
+with the secret in unpadded base32. An absent algorithm means SHA1,
+which is reported as UnsupportedAlgorithm.
+
+*/
+
+use crate::algorithm::Algorithm;
+use crate::encoding;
+use crate::hmac::hmac;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+pub const DEFAULT_DIGITS: u32 = 6;
+pub const DEFAULT_STEP: u64 = 30;
+// Each password tried is an HMAC to compute and another chance for a guess
+// to match, so verifying only looks this many counters or steps away.
+pub const MAX_LOOK_AHEAD: u64 = 10;
+pub const MAX_WINDOW: u64 = MAX_LOOK_AHEAD / 2;
+
+#[derive(Debug, PartialEq)]
+pub enum OtpError {
+    // RFC 4226 asks for at least 6, and 31 bits only give 10
+    InvalidDigits,
+    InvalidStep,
+    // an otpauth:// URI which can't be parsed, or lacks the secret or an
+    // HOTP counter
+    InvalidUri,
+    UnsupportedAlgorithm(String),
+}
+
+impl fmt::Display for OtpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OtpError::InvalidDigits => f.write_str("the number of digits has to be 6 to 10"),
+            OtpError::InvalidStep => f.write_str("the time step has to be positive"),
+            OtpError::InvalidUri => f.write_str("invalid otpauth URI"),
+            OtpError::UnsupportedAlgorithm(name) => write!(f, "unsupported algorithm {}", name),
+        }
+    }
+}
+
+impl Error for OtpError {}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hotp {
+    algorithm: Algorithm,
+    secret: Vec<u8>,
+    digits: u32,
+}
+
+impl Hotp {
+    pub fn new(algorithm: Algorithm, secret: &[u8]) -> Hotp {
+        Hotp {
+            algorithm,
+            secret: secret.to_vec(),
+            digits: DEFAULT_DIGITS,
+        }
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    pub fn secret(&self) -> &[u8] {
+        &self.secret
+    }
+
+    pub fn digits(&self) -> u32 {
+        self.digits
+    }
+
+    pub fn set_digits(&mut self, digits: u32) -> Result<(), OtpError> {
+        if !(6..=10).contains(&digits) {
+            return Err(OtpError::InvalidDigits);
+        }
+        self.digits = digits;
+        Ok(())
+    }
+
+    // the password for the counter, zero padded to the number of digits
+    pub fn generate(&self, counter: u64) -> String {
+        let mac = hmac(self.algorithm, &self.secret, &counter.to_be_bytes());
+        // the low 4 bits of the last byte pick where the 31 bits come from
+        let offset = (mac[mac.len() - 1] & 0xf) as usize;
+        let code = u32::from_be_bytes([
+            mac[offset] & 0x7f,
+            mac[offset + 1],
+            mac[offset + 2],
+            mac[offset + 3],
+        ]);
+        format!(
+            "{:0width$}",
+            code as u64 % 10u64.pow(self.digits),
+            width = self.digits as usize
+        )
+    }
+
+    // The counter of the first password from counter to counter +
+    // look_ahead which matches, each compared in constant time. The
+    // caller has to move its counter past the one returned, so that the
+    // password can't be used again. A look-ahead over MAX_LOOK_AHEAD never
+    // matches.
+    pub fn verify(&self, code: &str, counter: u64, look_ahead: u64) -> Option<u64> {
+        if look_ahead > MAX_LOOK_AHEAD {
+            return None;
+        }
+        (0..=look_ahead)
+            .filter_map(|i| counter.checked_add(i))
+            .find(|counter| crate::util::ct_eq(self.generate(*counter).as_bytes(), code.as_bytes()))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Totp {
+    hotp: Hotp,
+    step: u64,
+    t0: u64,
+}
+
+impl Totp {
+    // 6 digits with a 30 second step from the unix epoch, the defaults of
+    // RFC 6238
+    pub fn new(algorithm: Algorithm, secret: &[u8]) -> Totp {
+        Totp {
+            hotp: Hotp::new(algorithm, secret),
+            step: DEFAULT_STEP,
+            t0: 0,
+        }
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.hotp.algorithm
+    }
+
+    pub fn secret(&self) -> &[u8] {
+        &self.hotp.secret
+    }
+
+    pub fn digits(&self) -> u32 {
+        self.hotp.digits
+    }
+
+    pub fn set_digits(&mut self, digits: u32) -> Result<(), OtpError> {
+        self.hotp.set_digits(digits)
+    }
+
+    pub fn step(&self) -> u64 {
+        self.step
+    }
+
+    pub fn set_step(&mut self, step: u64) -> Result<(), OtpError> {
+        if step == 0 {
+            return Err(OtpError::InvalidStep);
+        }
+        self.step = step;
+        Ok(())
+    }
+
+    pub fn t0(&self) -> u64 {
+        self.t0
+    }
+
+    pub fn set_t0(&mut self, t0: u64) {
+        self.t0 = t0;
+    }
+
+    // T for a unix time in seconds, which is 0 before T0
+    pub fn counter(&self, time: u64) -> u64 {
+        time.saturating_sub(self.t0) / self.step
+    }
+
+    pub fn generate(&self, time: u64) -> String {
+        self.hotp.generate(self.counter(time))
+    }
+
+    // Whether the password matches at the time or up to window steps
+    // either side of it, for clocks which have drifted, as the offset in
+    // steps of the match. A password should only be accepted once, so the
+    // caller has to remember the last counter used. A window over
+    // MAX_WINDOW never matches.
+    pub fn verify(&self, code: &str, time: u64, window: u64) -> Option<i64> {
+        if window > MAX_WINDOW {
+            return None;
+        }
+        let counter = self.counter(time);
+        let first = counter.saturating_sub(window);
+        let matched = self
+            .hotp
+            .verify(code, first, (counter - first).saturating_add(window))?;
+        if matched >= counter {
+            i64::try_from(matched - counter).ok()
+        } else {
+            i64::try_from(counter - matched).ok().map(|offset| -offset)
+        }
+    }
+}
+
+// Which kind of password an otpauth:// URI is for, with the HOTP counter
+#[derive(Clone, Debug, PartialEq)]
+pub enum Otp {
+    Hotp { hotp: Hotp, counter: u64 },
+    Totp(Totp),
+}
+
+// An otpauth:// URI, parsed with FromStr and written with Display
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyUri {
+    pub issuer: Option<String>,
+    pub account: String,
+    pub otp: Otp,
+}
+
+impl fmt::Display for KeyUri {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (kind, hotp) = match &self.otp {
+            Otp::Hotp { hotp, .. } => ("hotp", hotp),
+            Otp::Totp(totp) => ("totp", &totp.hotp),
+        };
+
+        write!(f, "otpauth://{}/", kind)?;
+        if let Some(issuer) = &self.issuer {
+            write!(f, "{}:", encoding::percent_encode(issuer.as_bytes()))?;
+        }
+        write!(
+            f,
+            "{}?secret={}",
+            encoding::percent_encode(self.account.as_bytes()),
+            encoding::base32(&hotp.secret)
+        )?;
+        if let Some(issuer) = &self.issuer {
+            write!(f, "&issuer={}", encoding::percent_encode(issuer.as_bytes()))?;
+        }
+        write!(
+            f,
+            "&algorithm={}&digits={}",
+            encoding::percent_encode(hotp.algorithm.name().replace('-', "").as_bytes()),
+            hotp.digits
+        )?;
+        match &self.otp {
+            Otp::Hotp { counter, .. } => write!(f, "&counter={}", counter),
+            Otp::Totp(totp) => write!(f, "&period={}", totp.step),
+        }
+    }
+}
+
+fn decode(s: &str) -> Result<String, OtpError> {
+    let bytes = encoding::percent_decode(s).map_err(|_| OtpError::InvalidUri)?;
+    String::from_utf8(bytes).map_err(|_| OtpError::InvalidUri)
+}
+
+fn parse_number<T: FromStr>(s: &str) -> Result<T, OtpError> {
+    if s.is_empty() || !s.bytes().all(|c| c.is_ascii_digit()) {
+        return Err(OtpError::InvalidUri);
+    }
+    s.parse().map_err(|_| OtpError::InvalidUri)
+}
+
+impl FromStr for KeyUri {
+    type Err = OtpError;
+
+    fn from_str(s: &str) -> Result<KeyUri, OtpError> {
+        let rest = s.strip_prefix("otpauth://").ok_or(OtpError::InvalidUri)?;
+        let (path, query) = rest.split_once('?').ok_or(OtpError::InvalidUri)?;
+        let (kind, label) = path.split_once('/').ok_or(OtpError::InvalidUri)?;
+
+        // the label is "issuer:account" or just the account, with the ':'
+        // possibly escaped
+        let label = decode(label)?;
+        let (mut issuer, account) = match label.split_once(':') {
+            Some((issuer, account)) => (Some(issuer.to_string()), account.trim_start()),
+            None => (None, label.as_str()),
+        };
+
+        let mut secret = None;
+        let mut algorithm = None;
+        let mut digits = DEFAULT_DIGITS;
+        let mut counter = None;
+        let mut period = DEFAULT_STEP;
+        for parameter in query.split('&') {
+            let (name, value) = parameter.split_once('=').ok_or(OtpError::InvalidUri)?;
+            let value = decode(value)?;
+            match name {
+                "secret" => {
+                    let value: String = value.chars().filter(|c| *c != ' ').collect();
+                    secret =
+                        Some(encoding::decode_base32(&value).map_err(|_| OtpError::InvalidUri)?);
+                }
+                // the parameter wins over the label
+                "issuer" => issuer = Some(value),
+                "algorithm" => {
+                    algorithm = Some(
+                        value
+                            .parse::<Algorithm>()
+                            .map_err(|_| OtpError::UnsupportedAlgorithm(value.clone()))?,
+                    )
+                }
+                "digits" => digits = parse_number(&value)?,
+                "counter" => counter = Some(parse_number(&value)?),
+                "period" => period = parse_number(&value)?,
+                _ => {}
+            }
+        }
+
+        let secret = secret.ok_or(OtpError::InvalidUri)?;
+        let algorithm =
+            algorithm.ok_or_else(|| OtpError::UnsupportedAlgorithm("SHA1".to_string()))?;
+        let mut hotp = Hotp::new(algorithm, &secret);
+        hotp.set_digits(digits)?;
+
+        let otp = match kind {
+            "hotp" => Otp::Hotp {
+                hotp,
+                counter: counter.ok_or(OtpError::InvalidUri)?,
+            },
+            "totp" => {
+                let mut totp = Totp {
+                    hotp,
+                    step: DEFAULT_STEP,
+                    t0: 0,
+                };
+                totp.set_step(period)?;
+                Otp::Totp(totp)
+            }
+            _ => return Err(OtpError::InvalidUri),
+        };
+
+        Ok(KeyUri {
+            issuer,
+            account: account.to_string(),
+            otp,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA256_SEED: &[u8] = b"12345678901234567890123456789012";
+    const SHA512_SEED: &[u8] = b"1234567890123456789012345678901234567890123456789012345678901234";
+
+    // RFC 6238 appendix B, as (time, SHA-256, SHA-512). The SHA-1 column
+    // needs a hash the crate doesn't have.
+    const VECTORS: [(u64, &str, &str); 6] = [
+        (59, "46119246", "90693936"),
+        (1111111109, "68084774", "25091201"),
+        (1111111111, "67062674", "99943326"),
+        (1234567890, "91819424", "93441116"),
+        (2000000000, "90698825", "38618901"),
+        (20000000000, "77737706", "47863826"),
+    ];
+
+    #[test]
+    fn test_totp_rfc6238() {
+        let mut sha256 = Totp::new(Algorithm::Sha256, SHA256_SEED);
+        let mut sha512 = Totp::new(Algorithm::Sha512, SHA512_SEED);
+        sha256.set_digits(8).unwrap();
+        sha512.set_digits(8).unwrap();
+        for (time, sha256_code, sha512_code) in VECTORS.iter() {
+            assert_eq!(sha256.generate(*time), *sha256_code);
+            assert_eq!(sha512.generate(*time), *sha512_code);
+            assert_eq!(sha256.verify(sha256_code, *time, 0), Some(0));
+            assert_eq!(sha512.verify(sha512_code, *time, 0), Some(0));
+        }
+    }
+
+    #[test]
+    fn test_totp_window() {
+        let mut totp = Totp::new(Algorithm::Sha256, SHA256_SEED);
+        totp.set_digits(8).unwrap();
+
+        // the code for 1111111109 is valid from 1111111080 to 1111111109
+        assert_eq!(totp.verify("68084774", 1111111080, 0), Some(0));
+        assert_eq!(totp.verify("68084774", 1111111110, 0), None);
+        assert_eq!(totp.verify("68084774", 1111111110, 1), Some(-1));
+        assert_eq!(totp.verify("68084774", 1111111050, 1), Some(1));
+        assert_eq!(totp.verify("68084774", 1111111200, 2), None);
+        assert_eq!(totp.verify("6808477", 1111111109, 1), None);
+
+        // at the start of time there is nothing before
+        assert_eq!(totp.verify(&totp.generate(0), 0, 5), Some(0));
+
+        // and at the end of time nothing after
+        let mut end = totp.clone();
+        end.set_step(1).unwrap();
+        let last = Hotp::generate(&end.hotp, u64::MAX);
+        assert_eq!(end.verify(&last, u64::MAX, 2), Some(0));
+        assert_eq!(end.verify(&last, u64::MAX - 1, 1), Some(1));
+
+        // wider windows are refused, even for the current password
+        assert_eq!(totp.verify("68084774", 1111111080, MAX_WINDOW), Some(0));
+        assert_eq!(totp.verify("68084774", 1111111080, MAX_WINDOW + 1), None);
+        assert_eq!(totp.verify("68084774", 1111111080, u64::MAX), None);
+
+        // a later T0 and a longer step
+        totp.set_step(60).unwrap();
+        totp.set_t0(1000);
+        assert_eq!(totp.counter(999), 0);
+        assert_eq!(totp.counter(1119), 1);
+        assert_eq!(totp.generate(1119), Hotp::generate(&totp.hotp, 1));
+        assert_eq!(totp.set_step(0), Err(OtpError::InvalidStep));
+    }
+
+    #[test]
+    fn test_hotp() {
+        // the RFC 4226 secret with HMAC-SHA-256, checked against Python's
+        // hmac module
+        let mut hotp = Hotp::new(Algorithm::Sha256, b"12345678901234567890");
+        let codes = [
+            "875740", "247374", "254785", "496144", "480556", "697997", "191609", "579288",
+            "895912", "184989",
+        ];
+        for (counter, code) in codes.iter().enumerate() {
+            assert_eq!(hotp.generate(counter as u64), *code);
+        }
+        assert_eq!(hotp.verify("496144", 1, 5), Some(3));
+        assert_eq!(hotp.verify("496144", 4, 5), None);
+        assert_eq!(hotp.verify("875740", u64::MAX, 5), None);
+        assert_eq!(hotp.verify("496144", 1, MAX_LOOK_AHEAD), Some(3));
+        assert_eq!(hotp.verify("496144", 1, MAX_LOOK_AHEAD + 1), None);
+        assert_eq!(hotp.verify("496144", 3, u64::MAX), None);
+
+        assert_eq!(hotp.set_digits(5), Err(OtpError::InvalidDigits));
+        assert_eq!(hotp.set_digits(11), Err(OtpError::InvalidDigits));
+        hotp.set_digits(10).unwrap();
+        assert_eq!(hotp.generate(0).len(), 10);
+    }
+
+    #[test]
+    fn test_key_uri() {
+        let mut totp = Totp::new(Algorithm::Sha256, b"Hello!\xde\xad\xbe\xef");
+        totp.set_digits(8).unwrap();
+        let uri = KeyUri {
+            issuer: Some("ACME Co".to_string()),
+            account: "alice@example.com".to_string(),
+            otp: Otp::Totp(totp),
+        };
+        let s = uri.to_string();
+        assert_eq!(
+            s,
+            "otpauth://totp/ACME%20Co:alice%40example.com?secret=JBSWY3DPEHPK3PXP\
+             &issuer=ACME%20Co&algorithm=SHA256&digits=8&period=30"
+        );
+        assert_eq!(s.parse::<KeyUri>(), Ok(uri));
+
+        let uri = KeyUri {
+            issuer: None,
+            account: "bob".to_string(),
+            otp: Otp::Hotp {
+                hotp: Hotp::new(Algorithm::Sha512, b"12345678901234567890"),
+                counter: 7,
+            },
+        };
+        assert_eq!(uri.to_string().parse::<KeyUri>(), Ok(uri));
+
+        // written by other tools: an escaped ':', the issuer only in the
+        // label, a lower case secret, defaults
+        let parsed: KeyUri =
+            "otpauth://totp/Example%3A%20alice?secret=jbswy3dpehpk3pxp&algorithm=SHA512"
+                .parse()
+                .unwrap();
+        assert_eq!(parsed.issuer.as_deref(), Some("Example"));
+        assert_eq!(parsed.account, "alice");
+        match parsed.otp {
+            Otp::Totp(totp) => {
+                assert_eq!(totp.algorithm(), Algorithm::Sha512);
+                assert_eq!(totp.secret(), b"Hello!\xde\xad\xbe\xef");
+                assert_eq!(totp.digits(), 6);
+                assert_eq!(totp.step(), 30);
+            }
+            _ => panic!("expected TOTP"),
+        }
+
+        assert_eq!(
+            "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP".parse::<KeyUri>(),
+            Err(OtpError::UnsupportedAlgorithm("SHA1".to_string()))
+        );
+        assert_eq!(
+            "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&algorithm=SHA1".parse::<KeyUri>(),
+            Err(OtpError::UnsupportedAlgorithm("SHA1".to_string()))
+        );
+        for s in [
+            "https://totp/alice?secret=JBSWY3DPEHPK3PXP&algorithm=SHA256",
+            "otpauth://totp/alice?algorithm=SHA256",
+            "otpauth://totp/alice?secret=JBSWY3DPEHPK3PX1&algorithm=SHA256",
+            "otpauth://hotp/alice?secret=JBSWY3DPEHPK3PXP&algorithm=SHA256",
+            "otpauth://motp/alice?secret=JBSWY3DPEHPK3PXP&algorithm=SHA256",
+            "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&algorithm=SHA256&period=x",
+            "otpauth://totp/al%2ice?secret=JBSWY3DPEHPK3PXP&algorithm=SHA256",
+        ]
+        .iter()
+        {
+            assert_eq!(s.parse::<KeyUri>(), Err(OtpError::InvalidUri), "{}", s);
+        }
+        assert_eq!(
+            "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&algorithm=SHA256&digits=4"
+                .parse::<KeyUri>(),
+            Err(OtpError::InvalidDigits)
+        );
+    }
+}
@@ -0,0 +1,136 @@
+/*
+
+PBKDF2 from RFC 8018 5.2, with HMAC over any of the crate's algorithms
+as the pseudorandom function:
+
+    DK = T_1 || T_2 || ... truncated to dkLen bytes
+    T_i = U_1 ^ U_2 ^ ... ^ U_c
+    U_1 = PRF(P, S || INT(i)), U_j = PRF(P, U_{j-1})
+
+The HMAC is primed with the password once and cloned for every U_j,
+rather than hashing the padded password again c times per block.
+
+*/
+
+use crate::algorithm::Algorithm;
+use crate::hmac::Hmac;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum Pbkdf2Error {
+    ZeroIterations,
+    // more than (2^32 - 1) * hLen bytes
+    OutputTooLong { maximum: u64 },
+}
+
+impl fmt::Display for Pbkdf2Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pbkdf2Error::ZeroIterations => f.write_str("the iteration count has to be positive"),
+            Pbkdf2Error::OutputTooLong { maximum } => write!(
+                f,
+                "derived key too long, at most {} bytes can be derived",
+                maximum
+            ),
+        }
+    }
+}
+
+impl Error for Pbkdf2Error {}
+
+pub fn pbkdf2(
+    algorithm: Algorithm,
+    password: &[u8],
+    salt: &[u8],
+    iterations: u32,
+    dk_len: usize,
+) -> Result<Vec<u8>, Pbkdf2Error> {
+    if iterations == 0 {
+        return Err(Pbkdf2Error::ZeroIterations);
+    }
+    let h_len = algorithm.output_size();
+    let maximum = u32::MAX as u64 * h_len as u64;
+    if dk_len as u64 > maximum {
+        return Err(Pbkdf2Error::OutputTooLong { maximum });
+    }
+
+    let primed = Hmac::new(algorithm, password);
+    let mut dk = Vec::with_capacity(dk_len + h_len);
+    let mut block = 1u32;
+    while dk.len() < dk_len {
+        let mut hmac = primed.clone();
+        hmac.update(salt);
+        hmac.update(&block.to_be_bytes());
+        let mut u = hmac.finalize();
+        let mut t = u.clone();
+        for _ in 1..iterations {
+            let mut hmac = primed.clone();
+            hmac.update(&u);
+            u = hmac.finalize();
+            for (t, u) in t.iter_mut().zip(u.iter()) {
+                *t ^= u;
+            }
+        }
+        dk.extend_from_slice(&t);
+        block += 1;
+
+        #[cfg(feature = "zeroize")]
+        {
+            use zeroize::Zeroize;
+            u.zeroize();
+            t.zeroize();
+        }
+    }
+
+    #[cfg(feature = "zeroize")]
+    zeroize::Zeroize::zeroize(&mut dk[dk_len..]);
+    dk.truncate(dk_len);
+    Ok(dk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::hex;
+
+    #[test]
+    fn test_pbkdf2() {
+        // RFC 7914 section 11, PBKDF2-HMAC-SHA256
+        assert_eq!(
+            hex(&pbkdf2(Algorithm::Sha256, b"passwd", b"salt", 1, 64).unwrap()),
+            "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc\
+             49ca9cccf179b645991664b39d77ef317c71b845b1e30bd509112041d3a19783"
+        );
+        assert_eq!(
+            hex(&pbkdf2(Algorithm::Sha256, b"Password", b"NaCl", 80000, 64).unwrap()),
+            "4ddcd8f60b98be21830cee5ef22701f9641a4418d04c0414aeff08876b34ab56\
+             a1d425a1225833549adb841b51c9b3176a272bdebba1d078478f62b397f33c8d"
+        );
+
+        // checked against Python's hashlib.pbkdf2_hmac, including a key
+        // which isn't a whole number of blocks
+        assert_eq!(
+            hex(&pbkdf2(Algorithm::Sha512, b"password", b"salt", 4096, 64).unwrap()),
+            "d197b1b33db0143e018b12f3d1d1479e6cdebdcc97c5c0f87f6902e072f457b5\
+             143f30602641b3d55cd335988cb36b84376060ecd532e039b742a239434af2d5"
+        );
+        assert_eq!(
+            hex(&pbkdf2(Algorithm::Sha256, b"password", b"salt", 2, 70).unwrap()),
+            "ae4d0c95af6b46d32d0adff928f06dd02a303f8ef3c251dfd6e2d85a95474c43\
+             830651afcb5c862f0b249bd031f7a67520d136470f5ec271ece91c07773253d9\
+             3e676b079cae"
+        );
+    }
+
+    #[test]
+    fn test_pbkdf2_errors() {
+        assert_eq!(
+            pbkdf2(Algorithm::Sha256, b"password", b"salt", 0, 32),
+            Err(Pbkdf2Error::ZeroIterations)
+        );
+        assert!(pbkdf2(Algorithm::Sha256, b"password", b"salt", 1, 0)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,416 @@
+/*
+
+The hashing side of PKCS #1 v1.5 signatures, see RFC 8017 9.2.
+
+The message is hashed, and the digest wrapped in a DER encoded
+DigestInfo:
+
+    DigestInfo ::= SEQUENCE {
+        digestAlgorithm AlgorithmIdentifier,
+        digest OCTET STRING
+    }
+
+where the AlgorithmIdentifier is the algorithm's object identifier with
+NULL parameters. EMSA-PKCS1-v1_5 then pads the DigestInfo out to the
+length of the RSA modulus, which is all there is to do before the RSA
+arithmetic proper.
+
+Decoding is strict: only the exact DER encoding written by to_der is
+accepted, i.e. short form lengths, the NULL parameters present,
+a digest of the algorithm's size and no trailing bytes. Anything looser
+has a long history of signature forgeries.
+
+*/
+
+use crate::algorithm::Algorithm;
+use std::error::Error;
+use std::fmt;
+
+// DER tags
+const SEQUENCE: u8 = 0x30;
+const OBJECT_IDENTIFIER: u8 = 0x06;
+const NULL: u8 = 0x05;
+const OCTET_STRING: u8 = 0x04;
+
+// The DER encodings of the object identifiers, tag and length included,
+// see Algorithm::oid for the dotted forms. They all share the NIST
+// algorithms arc 2.16.840.1.101.3.4.2.
+pub const SHA224_OID: &[u8] = &[
+    0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x04,
+];
+pub const SHA256_OID: &[u8] = &[
+    0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01,
+];
+pub const SHA384_OID: &[u8] = &[
+    0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02,
+];
+pub const SHA512_OID: &[u8] = &[
+    0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03,
+];
+pub const SHA512_224_OID: &[u8] = &[
+    0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x05,
+];
+pub const SHA512_256_OID: &[u8] = &[
+    0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x06,
+];
+
+pub fn oid(algorithm: Algorithm) -> &'static [u8] {
+    match algorithm {
+        Algorithm::Sha224 => SHA224_OID,
+        Algorithm::Sha256 => SHA256_OID,
+        Algorithm::Sha384 => SHA384_OID,
+        Algorithm::Sha512 => SHA512_OID,
+        Algorithm::Sha512_224 => SHA512_224_OID,
+        Algorithm::Sha512_256 => SHA512_256_OID,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Pkcs1Error {
+    // ran out of bytes part way through an element
+    Truncated,
+    UnexpectedTag { expected: u8, found: u8 },
+    // a long form length, which no DigestInfo needs
+    InvalidLength,
+    UnknownAlgorithm,
+    // the parameters have to be an explicit NULL
+    InvalidParameters,
+    DigestLength { expected: usize, found: usize },
+    TrailingData,
+    // the modulus is too short for the DigestInfo plus 11 bytes of padding
+    // ("intended encoded message length too short")
+    ModulusTooShort { minimum: usize },
+}
+
+impl fmt::Display for Pkcs1Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pkcs1Error::Truncated => f.write_str("truncated DER"),
+            Pkcs1Error::UnexpectedTag { expected, found } => write!(
+                f,
+                "unexpected DER tag: expected {:#04x}, found {:#04x}",
+                expected, found
+            ),
+            Pkcs1Error::InvalidLength => f.write_str("invalid DER length"),
+            Pkcs1Error::UnknownAlgorithm => f.write_str("unknown digest algorithm"),
+            Pkcs1Error::InvalidParameters => {
+                f.write_str("digest algorithm parameters are not NULL")
+            }
+            Pkcs1Error::DigestLength { expected, found } => write!(
+                f,
+                "invalid digest length: expected {} bytes, found {}",
+                expected, found
+            ),
+            Pkcs1Error::TrailingData => f.write_str("trailing data after DigestInfo"),
+            Pkcs1Error::ModulusTooShort { minimum } => write!(
+                f,
+                "intended encoded message length too short, at least {} bytes are needed",
+                minimum
+            ),
+        }
+    }
+}
+
+impl Error for Pkcs1Error {}
+
+// Always holds a digest of the algorithm's size, so that to_der can't
+// write a DigestInfo which from_der would reject
+#[derive(Clone, Debug, PartialEq)]
+pub struct DigestInfo {
+    algorithm: Algorithm,
+    digest: Vec<u8>,
+}
+
+impl DigestInfo {
+    // hash the message with the algorithm
+    pub fn new(algorithm: Algorithm, msg: &[u8]) -> DigestInfo {
+        DigestInfo {
+            algorithm,
+            digest: algorithm.hash(msg),
+        }
+    }
+
+    // for a message which has already been hashed
+    pub fn from_digest(algorithm: Algorithm, digest: &[u8]) -> Result<DigestInfo, Pkcs1Error> {
+        if digest.len() != algorithm.output_size() {
+            return Err(Pkcs1Error::DigestLength {
+                expected: algorithm.output_size(),
+                found: digest.len(),
+            });
+        }
+        Ok(DigestInfo {
+            algorithm,
+            digest: digest.to_vec(),
+        })
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    pub fn digest(&self) -> &[u8] {
+        &self.digest
+    }
+
+    pub fn to_der(&self) -> Vec<u8> {
+        let mut der = prefix(self.algorithm);
+        der.extend_from_slice(&self.digest);
+        der
+    }
+
+    pub fn from_der(der: &[u8]) -> Result<DigestInfo, Pkcs1Error> {
+        let (digest_info, rest) = read(der, SEQUENCE)?;
+        if !rest.is_empty() {
+            return Err(Pkcs1Error::TrailingData);
+        }
+
+        let (algorithm_identifier, rest) = read(digest_info, SEQUENCE)?;
+        let (digest, rest) = read(rest, OCTET_STRING)?;
+        if !rest.is_empty() {
+            return Err(Pkcs1Error::TrailingData);
+        }
+
+        let (_, parameters) = read(algorithm_identifier, OBJECT_IDENTIFIER)?;
+        let oid = &algorithm_identifier[..algorithm_identifier.len() - parameters.len()];
+        let algorithm = Algorithm::ALL
+            .iter()
+            .copied()
+            .find(|algorithm| self::oid(*algorithm) == oid)
+            .ok_or(Pkcs1Error::UnknownAlgorithm)?;
+
+        match read(parameters, NULL) {
+            Ok((null, rest)) if null.is_empty() && rest.is_empty() => {}
+            _ => return Err(Pkcs1Error::InvalidParameters),
+        }
+
+        DigestInfo::from_digest(algorithm, digest)
+    }
+}
+
+// The DER encoding of a DigestInfo up to the digest itself, i.e. the
+// bytes which RFC 8017 9.2 note 1 lists for each hash function. Every
+// length involved is below 128, so they all take the short form.
+pub fn prefix(algorithm: Algorithm) -> Vec<u8> {
+    let oid = oid(algorithm);
+    let digest_len = algorithm.output_size();
+    let algorithm_identifier_len = oid.len() + 2;
+
+    let mut der = vec![
+        SEQUENCE,
+        (2 + algorithm_identifier_len + 2 + digest_len) as u8,
+        SEQUENCE,
+        algorithm_identifier_len as u8,
+    ];
+    der.extend_from_slice(oid);
+    der.extend_from_slice(&[NULL, 0x00, OCTET_STRING, digest_len as u8]);
+    der
+}
+
+// read one element with the given tag, returning its contents and the
+// bytes which follow it
+fn read(der: &[u8], tag: u8) -> Result<(&[u8], &[u8]), Pkcs1Error> {
+    if der.len() < 2 {
+        return Err(Pkcs1Error::Truncated);
+    }
+    if der[0] != tag {
+        return Err(Pkcs1Error::UnexpectedTag {
+            expected: tag,
+            found: der[0],
+        });
+    }
+    // nothing in a DigestInfo is long enough to need the long form, and
+    // DER requires the shortest form
+    let len = der[1] as usize;
+    if len >= 0x80 {
+        return Err(Pkcs1Error::InvalidLength);
+    }
+    if der.len() - 2 < len {
+        return Err(Pkcs1Error::Truncated);
+    }
+    Ok((&der[2..2 + len], &der[2 + len..]))
+}
+
+// EMSA-PKCS1-v1_5 encoding (RFC 8017 9.2) of the message for a modulus
+// of em_len bytes:
+//
+//     EM = 0x00 || 0x01 || PS || 0x00 || T
+//
+// where T is the DigestInfo and PS at least 8 bytes of 0xff.
+pub fn emsa_pkcs1_v1_5_encode(
+    algorithm: Algorithm,
+    msg: &[u8],
+    em_len: usize,
+) -> Result<Vec<u8>, Pkcs1Error> {
+    encode_digest_info(&DigestInfo::new(algorithm, msg), em_len)
+}
+
+// as emsa_pkcs1_v1_5_encode, for a message which has already been hashed
+pub fn encode_digest_info(digest_info: &DigestInfo, em_len: usize) -> Result<Vec<u8>, Pkcs1Error> {
+    let t = digest_info.to_der();
+    if em_len < t.len() + 11 {
+        return Err(Pkcs1Error::ModulusTooShort {
+            minimum: t.len() + 11,
+        });
+    }
+
+    let mut em = vec![0xff; em_len];
+    em[0] = 0x00;
+    em[1] = 0x01;
+    em[em_len - t.len() - 1] = 0x00;
+    em[em_len - t.len()..].copy_from_slice(&t);
+    Ok(em)
+}
+
+// Check an encoded message recovered from a signature against the
+// message, by encoding the message afresh and comparing the two in
+// constant time, as RFC 8017 8.2.2 recommends over parsing the encoded
+// message.
+pub fn emsa_pkcs1_v1_5_verify(algorithm: Algorithm, msg: &[u8], em: &[u8]) -> bool {
+    match emsa_pkcs1_v1_5_encode(algorithm, msg, em.len()) {
+        Ok(expected) => crate::util::ct_eq(&expected, em),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding;
+
+    #[test]
+    fn test_digest_info_prefix() {
+        // RFC 8017 9.2 note 1
+        let prefixes = [
+            (Algorithm::Sha224, "302d300d06096086480165030402040500041c"),
+            (Algorithm::Sha256, "3031300d060960864801650304020105000420"),
+            (Algorithm::Sha384, "3041300d060960864801650304020205000430"),
+            (Algorithm::Sha512, "3051300d060960864801650304020305000440"),
+            (
+                Algorithm::Sha512_224,
+                "302d300d06096086480165030402050500041c",
+            ),
+            (
+                Algorithm::Sha512_256,
+                "3031300d060960864801650304020605000420",
+            ),
+        ];
+
+        for (algorithm, prefix) in prefixes.iter() {
+            assert_eq!(encoding::hex(&super::prefix(*algorithm)), *prefix);
+
+            let digest_info = DigestInfo::new(*algorithm, b"abc");
+            let der = digest_info.to_der();
+            assert_eq!(der.len(), prefix.len() / 2 + algorithm.output_size());
+            assert_eq!(DigestInfo::from_der(&der), Ok(digest_info));
+        }
+    }
+
+    #[test]
+    fn test_digest_info_strict_decoding() {
+        let der = DigestInfo::new(Algorithm::Sha256, b"abc").to_der();
+
+        let mut trailing = der.clone();
+        trailing.push(0);
+        assert_eq!(
+            DigestInfo::from_der(&trailing),
+            Err(Pkcs1Error::TrailingData)
+        );
+        assert_eq!(
+            DigestInfo::from_der(&der[..der.len() - 1]),
+            Err(Pkcs1Error::Truncated)
+        );
+
+        // the same DigestInfo with the outer length in long form
+        let mut long_form = vec![0x30, 0x81];
+        long_form.extend_from_slice(&der[1..]);
+        assert_eq!(
+            DigestInfo::from_der(&long_form),
+            Err(Pkcs1Error::InvalidLength)
+        );
+
+        // absent parameters, which BER allows but RFC 8017 doesn't
+        let mut absent = vec![0x30, 0x2f, 0x30, 0x0b];
+        absent.extend_from_slice(SHA256_OID);
+        absent.extend_from_slice(&der[17..]);
+        assert_eq!(
+            DigestInfo::from_der(&absent),
+            Err(Pkcs1Error::InvalidParameters)
+        );
+
+        let mut unknown = der.clone();
+        unknown[14] = 0x07;
+        assert_eq!(
+            DigestInfo::from_der(&unknown),
+            Err(Pkcs1Error::UnknownAlgorithm)
+        );
+
+        // a SHA-384 identifier on a SHA-256 digest
+        let mut mismatched = der.clone();
+        mismatched[14] = 0x02;
+        assert_eq!(
+            DigestInfo::from_der(&mismatched),
+            Err(Pkcs1Error::DigestLength {
+                expected: 48,
+                found: 32
+            })
+        );
+
+        let mut tag = der;
+        tag[17] = 0x03;
+        assert_eq!(
+            DigestInfo::from_der(&tag),
+            Err(Pkcs1Error::UnexpectedTag {
+                expected: 0x04,
+                found: 0x03
+            })
+        );
+    }
+
+    #[test]
+    fn test_emsa_pkcs1_v1_5() {
+        // a 2048 bit modulus
+        let em = emsa_pkcs1_v1_5_encode(Algorithm::Sha256, b"abc", 256).unwrap();
+        assert_eq!(em.len(), 256);
+        assert_eq!(em[..2], [0x00, 0x01]);
+        assert!(em[2..256 - 52].iter().all(|byte| *byte == 0xff));
+        assert_eq!(em[256 - 52], 0x00);
+        assert_eq!(
+            encoding::hex(&em[256 - 51..]),
+            "3031300d060960864801650304020105000420\
+             ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        assert!(emsa_pkcs1_v1_5_verify(Algorithm::Sha256, b"abc", &em));
+        assert!(!emsa_pkcs1_v1_5_verify(Algorithm::Sha256, b"abd", &em));
+        assert!(!emsa_pkcs1_v1_5_verify(Algorithm::Sha384, b"abc", &em));
+        assert!(!emsa_pkcs1_v1_5_verify(Algorithm::Sha256, b"abc", &em[1..]));
+
+        // a digest which was hashed elsewhere, which has to be of the
+        // algorithm's size
+        let digest_info = DigestInfo::from_digest(Algorithm::Sha256, &em[256 - 32..]).unwrap();
+        assert_eq!(digest_info, DigestInfo::new(Algorithm::Sha256, b"abc"));
+        assert_eq!(digest_info.algorithm(), Algorithm::Sha256);
+        assert_eq!(encode_digest_info(&digest_info, 256), Ok(em.clone()));
+        assert_eq!(
+            DigestInfo::from_digest(Algorithm::Sha512, &em[256 - 32..]),
+            Err(Pkcs1Error::DigestLength {
+                expected: 64,
+                found: 32
+            })
+        );
+        assert_eq!(
+            DigestInfo::from_digest(Algorithm::Sha256, &[]),
+            Err(Pkcs1Error::DigestLength {
+                expected: 32,
+                found: 0
+            })
+        );
+
+        // SHA-512 needs 83 bytes of DigestInfo and 11 of padding
+        assert!(emsa_pkcs1_v1_5_encode(Algorithm::Sha512, b"abc", 94).is_ok());
+        assert_eq!(
+            emsa_pkcs1_v1_5_encode(Algorithm::Sha512, b"abc", 93),
+            Err(Pkcs1Error::ModulusTooShort { minimum: 94 })
+        );
+    }
+}
@@ -0,0 +1,643 @@
+/*
+
+EMSA-PSS encoding and verification, see RFC 8017 9.1. This is the
+hashing side of RSASSA-PSS signatures, everything up to and after the
+RSA arithmetic.
+
+The lengths follow the RFC: em_bits is one less than the bit length of
+the RSA modulus, and the encoded message is em_bits rounded up to whole
+bytes, which is one byte shorter than the modulus when its bit length is
+one more than a multiple of 8.
+
+The salt is passed in rather than generated here, since the crate has no
+source of randomness. It should be fresh random bytes, usually as many as
+the hash output.
+
+*/
+
+use crate::algorithm::Algorithm;
+use crate::mgf::{apply_mask, mgf1};
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum PssError {
+    // the encoded message has no room for the hash, the salt and the two
+    // fixed bytes ("encoding error")
+    ModulusTooShort { minimum_bits: usize },
+}
+
+impl fmt::Display for PssError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PssError::ModulusTooShort { minimum_bits } => write!(
+                f,
+                "encoding error: the encoded message needs at least {} bits",
+                minimum_bits
+            ),
+        }
+    }
+}
+
+impl Error for PssError {}
+
+// the length of the encoded message in bytes
+fn em_len(em_bits: usize) -> usize {
+    em_bits.div_ceil(8)
+}
+
+// H = Hash(00 00 00 00 00 00 00 00 || mHash || salt)
+fn salted_hash(algorithm: Algorithm, m_hash: &[u8], salt: &[u8]) -> Vec<u8> {
+    let mut hasher = algorithm.hasher();
+    hasher.update(&[0u8; 8]);
+    hasher.update(m_hash);
+    hasher.update(salt);
+    hasher.finalize()
+}
+
+pub fn encode(
+    algorithm: Algorithm,
+    msg: &[u8],
+    salt: &[u8],
+    em_bits: usize,
+) -> Result<Vec<u8>, PssError> {
+    let h_len = algorithm.output_size();
+    let em_len = em_len(em_bits);
+    if em_len < h_len + salt.len() + 2 {
+        return Err(PssError::ModulusTooShort {
+            minimum_bits: 8 * (h_len + salt.len() + 2) - 7,
+        });
+    }
+
+    let m_hash = algorithm.hash(msg);
+    let h = salted_hash(algorithm, &m_hash, salt);
+
+    // DB = PS || 0x01 || salt, PS being zeros
+    let db_len = em_len - h_len - 1;
+    let mut em = vec![0u8; em_len];
+    em[db_len - salt.len() - 1] = 0x01;
+    em[db_len - salt.len()..db_len].copy_from_slice(salt);
+    apply_mask(&mut em[..db_len], &mgf1(algorithm, &h, db_len));
+    // the bits beyond em_bits
+    em[0] &= 0xff >> (8 * em_len - em_bits);
+
+    em[db_len..em_len - 1].copy_from_slice(&h);
+    em[em_len - 1] = 0xbc;
+    Ok(em)
+}
+
+// Whether the encoded message recovered from a signature is consistent
+// with the message, for a salt of salt_len bytes. Everything checked here
+// is public, so this returns as soon as anything is out of place.
+pub fn verify(
+    algorithm: Algorithm,
+    msg: &[u8],
+    em: &[u8],
+    em_bits: usize,
+    salt_len: usize,
+) -> bool {
+    let h_len = algorithm.output_size();
+    let em_len = em_len(em_bits);
+    if em.len() != em_len || em_len < h_len + salt_len + 2 || em[em_len - 1] != 0xbc {
+        return false;
+    }
+
+    let db_len = em_len - h_len - 1;
+    let (masked_db, h) = em[..em_len - 1].split_at(db_len);
+    let top_bits = !(0xffu8 >> (8 * em_len - em_bits));
+    if masked_db[0] & top_bits != 0 {
+        return false;
+    }
+
+    let mut db = masked_db.to_vec();
+    apply_mask(&mut db, &mgf1(algorithm, h, db_len));
+    db[0] &= !top_bits;
+
+    let ps_len = db_len - salt_len - 1;
+    if db[..ps_len].iter().any(|byte| *byte != 0) || db[ps_len] != 0x01 {
+        return false;
+    }
+
+    let m_hash = algorithm.hash(msg);
+    crate::util::ct_eq(&salted_hash(algorithm, &m_hash, &db[ps_len + 1..]), h)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::decode_hex;
+
+    // Published RSASSA-PSS signatures, as (algorithm, modulus, public
+    // exponent, message, salt, signature). In order: mbed TLS "RSASSA-PSS
+    // verify ext, 521-bit key, SHA-256, empty salt", the first SHA-256
+    // case of NIST CAVP SigGenPSS_186-3, and mbed TLS "RSASSA-PSS
+    // Signature RSA-1024, SHA-512" and "RSA-1040, SHA-512", where the salt
+    // is as much of the 64 random bytes as fits. These need the RSA
+    // arithmetic to get at the encoded message, see mod_pow below.
+    const VECTORS: [(Algorithm, &str, u32, &str, &str, &str); 4] = [
+        (
+            Algorithm::Sha256,
+            "0131b69860f3cb9bf85ea358fdf2bd2990f1b77a80d6a4fdf817a43dd896bdf7\
+             dd26af8ac0237f526e0d33b105c971fdbd4ffa9ece99fc469f31ecf429e8f562\
+             c1c3",
+            65537,
+            "41",
+            "",
+            "009c4941157fa36288e467310b198ab0c615c40963d611ffeef03000549ded80\
+             9235955ecc57adba44782e9497c004f480ba2b3d58db8335fe0b391075c02c84\
+             3a6d",
+        ),
+        (
+            Algorithm::Sha256,
+            "a7a1882a7fb896786034d07fb1b9f6327c27bdd7ce6fe39c285ae3b6c34259ad\
+             c0dc4f7b9c7dec3ca4a20d3407339eedd7a12a421da18f5954673cac2ff05915\
+             6ecc73c6861ec761e6a0f2a5a033a6768c6a42d8b459e1b4932349e84efd92df\
+             59b45935f3d0e30817c66201aa99d07ae36c5d74f408d69cc08f044151ff4960\
+             e531360cb19077833adf7bce77ecfaa133c0ccc63c93b856814569e0b9884ee5\
+             54061b9a20ab46c38263c094dae791aa61a17f8d16f0e85b7e5ce3b067ece89e\
+             20bc4e8f1ae814b276d234e04f4e766f501da74ea7e3817c24ea35d016676cec\
+             e652b823b051625573ca92757fc720d254ecf1dcbbfd21d98307561ecaab5454\
+             80c7c52ad7e9fa6b597f5fe550559c2fe923205ac1761a99737ca02d7b19822e\
+             008a8969349c87fb874c81620e38f613c8521f0381fe5ba55b74827dad3e1cf2\
+             aa29c6933629f2b286ad11be88fa6436e7e3f64a75e3595290dc0d1cd5eee7aa\
+             ac54959cc53bd5a934a365e72dd81a2bd4fb9a67821bffedf2ef2bd94913de8b",
+            1316263,
+            "c16499110ed577202aed2d3e4d51ded6c66373faef6533a860e1934c63484f87\
+             a8d9b92f3ac45197b2909710abba1daf759fe0510e9bd8dd4d73cec961f06ee0\
+             7acd9d42c6d40dac9f430ef90374a7e944bde5220096737454f96b614d0f6cdd\
+             9f08ed529a4ad0e759cf3a023dc8a30b9a872974af9b2af6dc3d111d0feb7006",
+            "3e07ade72a3f52530f53135a5d7d93217435ba001ea55a8f5d5d1304684874bc",
+            "4335707da735cfd10411c9c048ca9b60bb46e2fe361e51fbe336f9508dc945af\
+             e075503d24f836610f2178996b52c411693052d5d7aed97654a40074ed20ed66\
+             89c0501b7fbac21dc46b665ac079760086414406cd66f8537d1ebf0dce4cf0c9\
+             8d4c30c71da359e9cd401ff49718fdd4d0f99efe70ad8dd8ba1304cefb88f24b\
+             0eedf70116da15932c76f0069551a245b5fc3b91ec101f1d63b9853b598c6fa1\
+             c1acdbacf9626356c760119be0955644301896d9d0d3ea5e6443cb72ca29f4d4\
+             5246d16d74d00568c219182feb191179e4593dc152c608fd80536329a533b3a6\
+             31566814cd654f587c2d8ce696085e6ed1b0b0278e60a049ec7a399f94fccae6\
+             462371a69695ef525e00936fa7d9781f9ee289d4105ee827a27996583033cedb\
+             2f297e7b4926d906ce0d09d84128406ab33d7da0f8a1d4d2f666568686c394d1\
+             39b0e5e99337758de85910a5fa25ca2aa6d8fb1c777244e7d98de4c79bbd426a\
+             5e6f657e37477e01247432f83797fbf31b50d02b83f69ded26d4945b2bc3f86e",
+        ),
+        (
+            Algorithm::Sha512,
+            "c3c9873548543591c1f947e412c33da56b9d1b94a58c2f410a8a620e9b4f1d91\
+             97643ebf527f5f62b202b9d67a32654d05f326a9b61e0106efdf4829673c4f3d\
+             23655996e2424059916ab47aa67e406c129679e5979ca46708866608ffa21f61\
+             9843b959b4442e422598a2faab54a8cef1f131992677d2cf5bcaf2b5564f7419",
+            65537,
+            "e35c6ed98f64a6d5a648fcab8adb16331db32e5d15c74a40edf94c3dc4a4de79\
+             2d190889f20f1e24ed12054a6b28798fcb42d1c548769b734c96373142092aed\
+             277603f4738df4dc1446586d0ec64da4fb60536db2ae17fc7e3c04bbfbbbd907\
+             bf117c08636fa16f95f51a6216934d3e34f85030f17bbbc5ba69144058aff081\
+             e0b19cf03c17195c5e888ba58f6fe0a02e5c3bda9719a7",
+            "653df9730e14e03f2ffb3374d6b75295aa4a52c38540b2d501adc1eb659a4d7a\
+             050769a3d11d0d5d6f3efb734200ade241fdc271c0f5eeed85b4bf00b232",
+            "655d1cf86a7af5113d1791ab7b6627845ea2aa7efbae82705a3563e5ba0337a1\
+             d033cb9283b38c042056e0a1d0529891173e3df6621dd8b184930caec8b3cbe4\
+             d1068524dab0ec6854f6638d86b77434cd792ddec0d02327a9eebffcd6911ffd\
+             32ad9bcb569d3237398c8169d9c62e7eea81c1b456fd36019aad1e4b268c604d",
+        ),
+        (
+            Algorithm::Sha512,
+            "d2340538231dcd5a61edf83ab94b2e4b3a784394c4ed35a424c050c294157b76\
+             25f9aca8258c21e2d0a7aa9b7c9db576404e63090dba50d998f9a3ec72b1a5cf\
+             28d83251ab93341c7d2c1a90403d70f67bc1a9e413bc62facccb52441e24c3f2\
+             bc9fdeca1a783012e70b9528176260580c4e1026c58209e8dcc4de3bf3f5be55\
+             65e9",
+            65537,
+            "e35c6ed98f64a6d5a648fcab8adb16331db32e5d15c74a40edf94c3dc4a4de79\
+             2d190889f20f1e24ed12054a6b28798fcb42d1c548769b734c96373142092aed\
+             277603f4738df4dc1446586d0ec64da4fb60536db2ae17fc7e3c04bbfbbbd907\
+             bf117c08636fa16f95f51a6216934d3e34f85030f17bbbc5ba69144058aff081\
+             e0b19cf03c17195c5e888ba58f6fe0a02e5c3bda9719a7",
+            "653df9730e14e03f2ffb3374d6b75295aa4a52c38540b2d501adc1eb659a4d7a\
+             050769a3d11d0d5d6f3efb734200ade241fdc271c0f5eeed85b4bf00b2327bc8",
+            "13e695948d59ded5a975cd9fb14bffc48e4ff9725576a96a6693da1a3c4c90d1\
+             7d6811a97a633180d76dba5b957d2244e3b97e7bf3463a77d0b6c39b28a88e0b\
+             6739113726cd74937ad5f693ae5a8fd77febc270a115df05c344ddffebc2438a\
+             e67a5eea6572f434881bdf350aed4ec8f3a530d279d3fff07bb78e510807114e\
+             6ee7",
+        ),
+    ];
+
+    // NIST CAVP SigVerPSS_186-3 cases for 2048 bit keys and salts as
+    // long as the hash, as kept in ring's rsa_pss_verify_tests.txt: one
+    // SHA-256 and one SHA-512 group, each with a valid signature and one
+    // for each way the test vectors break it. The keys are the moduli
+    // below; the cases are (key, public exponent, message, signature,
+    // valid).
+    const SIG_VER_KEYS: [(Algorithm, &str); 2] = [
+        (
+            Algorithm::Sha256,
+            "a47d04e7cacdba4ea26eca8a4c6e14563c2ce03b623b768c0d49868a57121301\
+             dbf783d82f4c055e73960e70550187d0af62ac3496f0a3d9103c2eb7919a7275\
+             2fa7ce8c688d81e3aee99468887a15288afbb7acb845b7c522b5c64e678fcd3d\
+             22feb84b44272700be527d2b2025a3f83c2383bf6a39cf5b4e48b3cf2f56eef0\
+             dfff18555e31037b915248694876f3047814415164f2c660881e694b58c28038\
+             a032ad25634aad7b39171dee368e3d59bfb7299e4601d4587e68caaf8db457b7\
+             5af42fc0cf1ae7caced286d77fac6cedb03ad94f1433d2c94d08e60bc1fdef05\
+             43cd2951e765b38230fdd18de5d2ca627ddc032fe05bbd2ff21e2db1c2f94d8b",
+        ),
+        (
+            Algorithm::Sha512,
+            "a677525e1a69546a96dc7b112350d5e4864f0f82e999a714fa9f43ac681517d3\
+             975910c2d806bb3ee6dbf5dba1d969b38889e113c2da76eef4412a60cbd89faf\
+             35b2bdcb0de36a2cb762cd8f2f29aed9982a9ab60886cc8fbfee9b2ae09c8816\
+             1e9159d4fc833adc4f80e4bf629d5a9551acce7a3938630c2bf9956097642e3b\
+             c60ac6522017841b65c7a25197865e697753b08169853681911443a2b25f1b7c\
+             4696f946155b2664b67b40878d3b45c3e0d7034d5b5ee6f5ba8fb3cae7797e85\
+             789902cf8f9f86ed3ef25ae0736178aae260fe875bfef5bcde9ec05f11e18fc7\
+             375edcd4a5533618e6f991dd48aa3062e6031e291dfcdc6e7fc14ec60e539fcb",
+        ),
+    ];
+
+    const SIG_VER: [(usize, u32, &str, &str, bool); 12] = [
+        // 4 - Format of the EM is incorrect - hash moved to left
+        (
+            0,
+            0x10e43f,
+            "7518c85b67e7aef7f26bf006899faef76e076f0c6c946e5dc9c83521771a6d29\
+             8a9cf5adefdb314b5a07a54d8054c22b879fff50ba552c218291033c918401fd\
+             611a7447dddad4815e0f56ded825bfe256557622a385de4b4a69e265c1efd259\
+             e2da6db19aac3fa0e5ca2d42fadb4e24c271fc078feb2be10b9afa256f228844",
+            "992d48b21bb3d2219b44e8fcc8633cf3aeb591de90f4386496ac7ecd284cb63d\
+             7dff81a50b8c4fed9f2ef737692ea6be05248ca138947b49b4e7f3cce6640e04\
+             9ac2154c40f57e22fa14f97e7a9507e1dc98b206ce6ea0e180039199d1be0a15\
+             d1f5093a459e5101aaca2a23cb1f59cad2f1fb99dc956b9d4344bad2c1121d63\
+             b915004acbfc7ac60ac9a7b0b1c6812b30bfe087f7f0c7d1625f9c4f458515e1\
+             1478e3604aa39d14d08bea30b01fcd6189e6f9b701d360e4714d45556b29815c\
+             8d8fa8e46e10749ba5e8d445a4c0f487e70ab5890b7ccc1651282a54e87e7db4\
+             bb2f7d4a671e71c43c55cf6486416f171d1955037474d06a71dd078767848e5d",
+            false,
+        ),
+        (
+            0,
+            0x10e43f,
+            "e002377affb04f0fe4598de9d92d31d6c786040d5776976556a2cfc55e54a1dc\
+             b3cb1b126bd6a4bed2a184990ccea773fcc79d246553e6c64f686d21ad415267\
+             3cafec22aeb40f6a084e8a5b4991f4c64cf8a927effd0fd775e71e8329e41fdd\
+             4457b3911173187b4f09a817d79ea2397fc12dfe3d9c9a0290c8ead31b6690a6",
+            "4f9b425c2058460e4ab2f5c96384da2327fd29150f01955a76b4efe956af06dc\
+             08779a374ee4607eab61a93adc5608f4ec36e47f2a0f754e8ff839a8a19b1db1\
+             e884ea4cf348cd455069eb87afd53645b44e28a0a56808f5031da5ba9112768d\
+             fbfca44ebe63a0c0572b731d66122fb71609be1480faa4e4f75e43955159d70f\
+             081e2a32fbb19a48b9f162cf6b2fb445d2d6994bc58910a26b5943477803cdaa\
+             a1bd74b0da0a5d053d8b1dc593091db5388383c26079f344e2aea600d0e32416\
+             4b450f7b9b465111b7265f3b1b063089ae7e2623fc0fda8052cf4bf3379102fb\
+             f71d7c98e8258664ceed637d20f95ff0111881e650ce61f251d9c3a629ef222d",
+            true,
+        ),
+        // 1 - Message changed
+        (
+            0,
+            0x10e43f,
+            "a3bf44cae8aa8347fd07d84a33eec5dbbdd7b6431368887c988c4be779c5473d\
+             d8c33ec82a35f1d3dddfe55f3eed67179b87ce86a4a50088172538fe9d1b06c6\
+             ef6897eb3c8e3618cfc21353ed4343e7fceb09a2eb035441cd5c8829c79b8158\
+             2dd5d69ae85c5a001bd8e98e069961342a2bee00ad2b8b91015ac5cfc1f0c2d9",
+            "877f20eed60f8ce286108a5dde9b6828b37e3fbdb08fe153e591513897440f21\
+             f81214598fba08ea077394ba8c2a44aa4f0d8f3a5fbaec3dc69b3bddfbe28397\
+             c90adf35d08ea771c7aaa31eb06413c1c62b77618af940f4c71859fa4384d29b\
+             48e5cfc941d69bf0a3804d2008e758742b8ed68754bc71d231623d181347c368\
+             33a7d7160f742a37ce7d432d748e514aa7d8156b50c532151390d086cdcf9d59\
+             f122c6d97f4ccb737289f7b00a237cb6b4aae6ba79d41ff73d019a26b59ade04\
+             c967356e2aad52f115357ffb7676f190db38dcfc98666e5b258559c8c85fa329\
+             42cbcb99d757e8847e56a1687b3302415698708191b136d923349b02fe38b6be",
+            false,
+        ),
+        // 3 - Signature changed
+        (
+            0,
+            0x10e43f,
+            "e1c46c309b6366fb4d56ac08c9393cee9a7c95bbe7b7c0e79a3d9187c0f42bc3\
+             3364c28a770da585e3fe7b4901a3ccd037dfc42aa65a3470521ddafa835ce2d1\
+             6c92ac670bd4d086505e608781736dc4dd64cc5080ee19e586c8fd1d737dade5\
+             d378b32f1d5df1e8dda0e32a125024b2d53334943c18782d7e69825a580093e7",
+            "8ed1f28fd16d45d416a21554e104c006fd7868e5895e8b99831ae0938135b543\
+             610df64a8c3574d08118bfe396f9a5609a8dbda21b9a8530ff0ba90e629d6abe\
+             30d2c1b590600db971fcda80e6eaa84017e209b9bd3b641f3c81d5d27f842bec\
+             8019790ed99a0e5db4aedc1c070b047c19410cbc56e9a0ff12d8f6e5d7371b10\
+             11ecfecf7be7a74f94403590a52f95238dd69e0b5f4c1fcde97ecfdb1acc3803\
+             e59ad8b3088b2bc509e3dd12d40d875625dc8362c579176799c75e4fadcdb392\
+             c68f401f68d854e46377f084c081f9d83743039f6934722e30ef3f0226bc841d\
+             79a4eb68c5cccbb6ae0e9200444e50ff0d0953047ef955d2d39a70c3b837c5f4",
+            false,
+        ),
+        // 5 - Format of the EM is incorrect - 00 on end of pad removed
+        (
+            0,
+            0x10e43f,
+            "925d59f953cb3ffb6d5a3a55c079cb1083997536e33d7c8aed50ed76aebcde45\
+             9938f79229613200c70dde2ceddceae08c10608aab9e30ec51842f14a65e5f8f\
+             553471da3497881927ec400b4207ef3e2dfc2b7fcd318c9520b8b22f69dc8a1a\
+             8efaceb7be93cbad569e67db062362913005dcff902018ed22937fad405fe84e",
+            "62b07f6d1b8f13651d7f22ce2ce01061090029db5af7dcecfeafef20621dd9e2\
+             54a0fb914f76a3d79662257489cb8122708583d30778791a77da83c7bea81140\
+             c61e4d0484806e20fb85f24d1bbf774ef2ed38809c9b14f2a58c6e8649b760ba\
+             a901544522ed94bd405c77201d07c8d12864a8d1e97a4d322c29994b214fc83c\
+             2ecd5c955b9bdec424e7ca5a1325ec0aae4ab0c202b980a2187f096aaaaa5e85\
+             550ebd325799f4f30ef2ead07e79c7a475667f5965e6b50269513659ab5962a3\
+             91c43cbc3a3da34c0fdd1546c40ea7e2eb5352ce6a06ce6a6385ca0ddd5d162c\
+             137836df9ea1f89cfa00c3eb1671a43bd625526f3b6ba8e48a7a2d56fb4f01c6",
+            false,
+        ),
+        // 2 - Public Key e changed
+        (
+            0,
+            0x3c6cd1,
+            "08d3ac24a595da811cc9bba78828f1452ff390ae653f22d1ad91ef6b22aa7b7b\
+             15a44ff7f83efcbd7a755eccf4541eb4040c7a4b774749a26dba3937b7f95c6c\
+             8490e3383d4f291dade5f35a65b1f6615fd4998be18bfd0ba4bc3a2136ceec90\
+             9dbeef513d6f6689fc4202b9a3e78134877374d76ab246f49cbe7a8f65d034cd",
+            "72ea7d669ac699a149c13aa168eb3e148f590bbc0424951f1c3144c541915573\
+             ecf05cabb5275488c98d0ebe55b1f1af49ef8cc72cf00e849897c6afa53833ca\
+             f9ffc00eb84cfe55b8277e93a6ac3e04b111c9d07ad62082ca32854c40de1e09\
+             56a18f8460438484403abb91e6fad12b28f114157f1b6ef563d2fa438ccb8a16\
+             aa3afe6ec5e98365c30d9a355e7e14b039bbdecf59f91248d0f1e317d2e3c819\
+             096ed68cf98361d7f9310b354f4f45e2f7c4dfb82c218cd438f3174a6f017f0f\
+             148a71c40ad5b7e2c8081ddbaeaa764d601bb2e075eb3618aabaa4b5060022c6\
+             d23ef072643686a759227daef0331b28ee76da4ab3b5ad5dda8991b9937d1a59",
+            false,
+        ),
+        // 2 - Public Key e changed
+        (
+            1,
+            0xeac839,
+            "9c3d5d23d2746d15d616bebf3cf720c6e6012a71cae22002f5021a47d0b8636c\
+             a3bd201357e132a680fc5dec9b28a9db932d08ae8b3d3a37d7e2ee754b342a69\
+             b94fec26b50412289bcf77e6d4095faa545f15a16783d22eae21e18464150174\
+             e6db0b837347d440307655d56f0409db307f9773e81cb19282a93c9ca4c3b135",
+            "98657fd8163967fa7d263bd45bb890035adbcdd1645fd48b28febfb9b4e15172\
+             540e38b7c2f673c40a205fd40b08b60b4b81ed6e236cdf08f0d6b11f50dc74c6\
+             0dc466ac372e0f467883aa9a398f4aeef87b040e14a51502dd467e8e8dd89812\
+             dfaf6b1dc1c2f6c28448af084590c05aec499dd3b148e66f3d71cf75e239db6d\
+             21f4074b8bd9a6bde5ca668634bd47953276ff2d0ebbe01afcfe0e381903736d\
+             6a6c672a45fba4ee326e342dc5925169517c5f57e9290724576a225ba89cb4dd\
+             091f4e6513be10dd4181855bb4045d6ef6437c16d3b5589ef9d6836682711c7d\
+             66025ae37b525580f0dfcf3db7fe57d7c6b15777cc41600307e58a1721b6f7bc",
+            false,
+        ),
+        // 4 - Format of the EM is incorrect - hash moved to left
+        (
+            1,
+            0x8d8853,
+            "7759420c8d1a39ba0e3e1681d9b757fcf30cb40d844f4a71224ff998520e0bb4\
+             4c761611ff4723a455540fe6b8ef32e3d5a23f8da0206b57987ad2ff4c9616ab\
+             1ede493847350dcf1b2ff9b98e813f74f8b68c0615243091501f7f28416c77e1\
+             74f8a32b6cca3e62e7379ca16455c6d8e3b2651eec45e148d2a4c9ba3978767d",
+            "91ce8e921f85de845b9bf7503dc65a328224d15b23e8ea65dc3da6d804fbef19\
+             6c305e0fba059a7d842c8c0f580860cce77652d4dc779073d6a3b0a92bf2dd3e\
+             c6c0618da1485036fd594bd962f7b5a1c61d53f1626912c7e312aa83eb432628\
+             8a52437c7387916bcb11084a40c219c84cab2ff66e83143f858647f2dc914da0\
+             c7858ba39aa8ad8147ea5c9c52069eb2b2ca941d8a5023748dc401e766450084\
+             6c85cadf0287e39b8b8b46794a82bfffa752960ab3b9eaa02530b5903c24aefa\
+             108f10aee35b6a8a3cdf8795a182e5c04ed36bb68c18785623b42f0979fff319\
+             112e1514cee51db437ee32289f61ded61186c6ed44ad722d18d556821f888221",
+            false,
+        ),
+        // 1 - Message changed
+        (
+            1,
+            0x8d8853,
+            "864f4f5890a03653d08daa83d9e2992fab3393b7ee152d6f7b8ea3ad1ec9c121\
+             9a0e0365eef2fbd5d7cbf3a19667d421f3fe46688264191b2583e484d209ebe2\
+             975e4603dbd94015e633febaa43c615aa5cc2cbd69fd6ad9db970ae81bcccdbe\
+             8026625adebdcfbb04ad12f589c70883c9878fdf2a46e750a4b15f03807a2d5e",
+            "245fda2e66d2bab102d0da4098d751650f5ef4738d14dabe866c782009ead9d6\
+             d05d43ac0a3014a382d62ac971ee19798f3b8ef49f9bc247fa07b368ac2d0e8a\
+             4fb210af5acd87b6b512e135861b30103403304732a10b75c9a149552d1ccf0f\
+             9912968c6431398b7d48dff48cc7cb7184e76a0ab94ac9a6b0035b91b3eeb2d1\
+             326ebddfc13250224538c143fc0dfa3995926415a658db8e6691504050a1a30e\
+             34b21af8d12707bc4454f174385cd84c729e2ddfc5cc9178812059b5da4a1a60\
+             5a2a81642ccc1bc5cf953965f705673b12c5b46ac8c1a03c1001ae4df1c39cc8\
+             3cb54b10cb7d33cd5108033c268ceeebba6c6ada92882f46562dc658d3525574",
+            false,
+        ),
+        // 3 - Signature changed
+        (
+            1,
+            0x8d8853,
+            "71abf895e56ba8f6ae8d0e9e6690c09c759270a73db8c1aa95d05980793537fb\
+             fff3472c8d2c34de4abb7e64d216cc952e798314034197d50996a2dcbf4c3348\
+             5e0b68910baebf0e50ea29bacd3060372bd47b13526ec04bdc81b90dc95a8ac2\
+             743b814cc5b9ef8ca9633628bfa4248b55eb7f2d9208e114f4dac69bfe27e472",
+            "243c570cd1bd85522e64817361cbb949c83876d7a3609513aebf506fc06750ba\
+             38e820232c98acbe00ffa50b36c538560069fd13992ca0df7d9f6d956b883334\
+             f1c3af8a74adeed5be15b033d85b207be2fe600a9902d699ccef1f4a8d588672\
+             c446401eb390d174d572b918f9b349e2e704775300023850a534cd14e0046e38\
+             5181d345a01dbd230c7965fffd71d4e9f9bd274d2ef46c556c7332121362e717\
+             06c39f08919efa3a4f2f142150c8cef4b30ef788f6fffc2ed2aca0262eba873a\
+             251795845da526b78e2b24d66f21d40d5fcbe5542a7553a9178be5a9805bb535\
+             79bd108cff733b37640789318db620a6a94444e9203923ae325500b676b14798",
+            false,
+        ),
+        // 5 - Format of the EM is incorrect - 00 on end of pad removed
+        (
+            1,
+            0x8d8853,
+            "4b648de2e390677ce1727191498b76d2d2438d0ceddf3db502ade5c5fd815bc7\
+             0965d42f9b8d04eeb38986445fbe7dd580177c00d0f8a36c24740be3efbf6564\
+             08e6dd73adbd45b4c7196fb86f40da17b29e91bf91ba518b4c68ea18e13fe7d4\
+             cf4d71571f0a71f7a58bf59684f8d5bff04096f2380b8e3b03f14909d9e92c3e",
+            "277fe81a1540341c9c04fb5d3c8f70c1e72f87fa7d04b93ee54d56d1184c141e\
+             c4e125e2e5cfd0acff1055c76697a1c3079c371cce0bd84ee4437bfa1806aa76\
+             df2955935331915fff879d87c7372e75e5e8fecc906f9d34a5adc0b75409a87f\
+             b6411e2990cd237ac8223f7223444f5d527a115c85b9ba10287f3e825e1dc58e\
+             b37c8a9ca055155ff6a5aaa6565690dceef6f647729d071cdd9f0688c7fee8f9\
+             293abe658d0e1ef855c86a28d8d0f8f82f72173186353080b84f142f96590737\
+             7178fc1ea00819d81afc2474becf79e1e3b70c7c2a8407d50795c3131bb6df78\
+             720c31b2f8c43f705464d8982043c45f04e339aa1c6f4e26aa7092b3e6261299",
+            false,
+        ),
+        (
+            1,
+            0x8d8853,
+            "7811a407fe653ad2343d83c0499fc11e2951ef0a4791a3cd9a06396be5f72e78\
+             3cbbca2cc47002babaf09cdeb70194b532ccbfd24691ae8eb598d59f2e6beced\
+             cb4296a1debf417eeddb4d74fb217072091a597ddd0893ff02d6ad61105098db\
+             2e90508bd4b8bec5d6e7deab9e651dfdb8210532955e1bc788a908d7150ef8ec",
+            "3ad1f0faa9a36586154a382a1f2c10e819dc318a68be35f54f95401b0ef2ba5c\
+             c895f0d6bde28c8d3b364f60de03ce75a7af29e5807c1eec4cd70624756e2631\
+             eb46af010ca8cf93ee7d86a4af3ced5dd2a8a41c1feee6b4572070873939ae7a\
+             2ce75193945937461d0064eeba07760c495c5c70bdb04d89951de3f96525f8e4\
+             4612493d490731e7ef7679a24b0b1e2b24c8003c2f94114e4193d6e54c0af9e8\
+             70530b008530210ac0b0e4c7c79f379384e1bb319b5f2a0b622517ae5d27f0ea\
+             af7958cb0b41849126075092e86e7e7be5eeacef9a8e3ec595432ef619858fe0\
+             d0517871b8b495c01af6f18d4e6d250dbd19280ef4f8a37d1b59a48fa41b831d",
+            true,
+        ),
+    ];
+
+    // Just enough arithmetic on big numbers to compute s^e mod n, for the
+    // encoded messages of the vectors above. Numbers are little endian
+    // 64 bit limbs, as many as the modulus needs, and are kept below n.
+    fn from_bytes(bytes: &[u8], limbs: usize) -> Vec<u64> {
+        let mut x = vec![0u64; limbs];
+        for (i, byte) in bytes.iter().rev().enumerate() {
+            x[i / 8] |= (*byte as u64) << (8 * (i % 8));
+        }
+        x
+    }
+
+    fn to_bytes(x: &[u64], len: usize) -> Vec<u8> {
+        (0..len)
+            .rev()
+            .map(|i| (x[i / 8] >> (8 * (i % 8))) as u8)
+            .collect()
+    }
+
+    // x = x + y mod n
+    fn add_mod(x: &mut [u64], y: &[u64], n: &[u64]) {
+        let mut carry = false;
+        for (a, b) in x.iter_mut().zip(y) {
+            let (sum, c1) = a.overflowing_add(*b);
+            let (sum, c2) = sum.overflowing_add(carry as u64);
+            *a = sum;
+            carry = c1 || c2;
+        }
+
+        let below_n = x
+            .iter()
+            .zip(n)
+            .rev()
+            .find(|(a, b)| a != b)
+            .map(|(a, b)| a < b);
+        if carry || below_n != Some(true) {
+            let mut borrow = false;
+            for (a, b) in x.iter_mut().zip(n) {
+                let (diff, b1) = a.overflowing_sub(*b);
+                let (diff, b2) = diff.overflowing_sub(borrow as u64);
+                *a = diff;
+                borrow = b1 || b2;
+            }
+        }
+    }
+
+    // x * y mod n, by doubling and adding
+    fn mul_mod(x: &[u64], y: &[u64], n: &[u64]) -> Vec<u64> {
+        let mut result = vec![0u64; n.len()];
+        for i in (0..64 * y.len()).rev() {
+            let double = result.clone();
+            add_mod(&mut result, &double, n);
+            if (y[i / 64] >> (i % 64)) & 1 == 1 {
+                add_mod(&mut result, x, n);
+            }
+        }
+        result
+    }
+
+    fn mod_pow(base: &[u8], e: u32, n: &[u8]) -> Vec<u8> {
+        let limbs = n.len().div_ceil(8);
+        let base = from_bytes(base, limbs);
+        let n_limbs = from_bytes(n, limbs);
+        let mut result = from_bytes(&[1], limbs);
+        for i in (0..32).rev() {
+            result = mul_mod(&result, &result, &n_limbs);
+            if (e >> i) & 1 == 1 {
+                result = mul_mod(&result, &base, &n_limbs);
+            }
+        }
+        to_bytes(&result, n.len())
+    }
+
+    // (algorithm, em_bits, msg, salt, EM) for each vector, the EM being
+    // s^e mod n written out in em_bits rounded up to bytes
+    type EncodedMessage = (Algorithm, usize, Vec<u8>, Vec<u8>, Vec<u8>);
+
+    fn encoded_messages() -> Vec<EncodedMessage> {
+        VECTORS
+            .iter()
+            .map(|(algorithm, n, e, msg, salt, sig)| {
+                let n = decode_hex(n).unwrap();
+                let em_bits = 8 * n.len() - n[0].leading_zeros() as usize - 1;
+                let em = mod_pow(&decode_hex(sig).unwrap(), *e, &n);
+                let (zeros, em) = em.split_at(n.len() - em_len(em_bits));
+                assert!(zeros.iter().all(|byte| *byte == 0));
+                (
+                    *algorithm,
+                    em_bits,
+                    decode_hex(msg).unwrap(),
+                    decode_hex(salt).unwrap(),
+                    em.to_vec(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_mod_pow() {
+        // 4^13 mod 497, and (n - 2)^(2^32 - 1) mod n for n = 2^128 - 159,
+        // where every addition carries out of the top limb
+        assert_eq!(mod_pow(&[4], 13, &[0x01, 0xf1]), [0x01, 0xbd]);
+        assert_eq!(
+            mod_pow(
+                &decode_hex("ffffffffffffffffffffffffffffff5f").unwrap(),
+                0xffffffff,
+                &decode_hex("ffffffffffffffffffffffffffffff61").unwrap()
+            ),
+            decode_hex("1949ba2f50b408a4910aa9d3cbd39972").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_pss_verify() {
+        for (algorithm, em_bits, msg, salt, em) in encoded_messages().iter() {
+            let salt_len = salt.len();
+            assert!(verify(*algorithm, msg, em, *em_bits, salt_len));
+
+            let mut other = msg.clone();
+            other[0] ^= 0x01;
+            assert!(!verify(*algorithm, &other, em, *em_bits, salt_len));
+            assert!(!verify(*algorithm, msg, em, *em_bits, salt_len + 1));
+            assert!(!verify(*algorithm, msg, &em[1..], *em_bits, salt_len));
+            for i in [0, em.len() / 2, em.len() - 2, em.len() - 1].iter() {
+                let mut em = em.clone();
+                em[*i] ^= 0x04;
+                assert!(!verify(*algorithm, msg, &em, *em_bits, salt_len));
+            }
+        }
+    }
+
+    #[test]
+    fn test_pss_sig_ver() {
+        for (key, e, msg, sig, valid) in SIG_VER.iter() {
+            let (algorithm, n) = SIG_VER_KEYS[*key];
+            let n = decode_hex(n).unwrap();
+            let em_bits = 8 * n.len() - n[0].leading_zeros() as usize - 1;
+            let em = mod_pow(&decode_hex(sig).unwrap(), *e, &n);
+            let em = &em[n.len() - em_len(em_bits)..];
+            let msg = decode_hex(msg).unwrap();
+            assert_eq!(
+                verify(algorithm, &msg, em, em_bits, algorithm.output_size()),
+                *valid,
+                "{}",
+                sig
+            );
+        }
+    }
+
+    #[test]
+    fn test_pss_encode() {
+        // with the salt of the signature the encoding is the same
+        for (algorithm, em_bits, msg, salt, em) in encoded_messages().iter() {
+            assert_eq!(encode(*algorithm, msg, salt, *em_bits).as_ref(), Ok(em));
+        }
+
+        let msg = b"the quick brown fox";
+        for (em_bits, algorithm) in [(1024, Algorithm::Sha256), (2047, Algorithm::Sha512)].iter() {
+            let salt: Vec<u8> = (0..algorithm.output_size() as u8).collect();
+            let em = encode(*algorithm, msg, &salt, *em_bits).unwrap();
+            assert_eq!(em.len(), em_bits.div_ceil(8));
+            assert!(verify(*algorithm, msg, &em, *em_bits, salt.len()));
+        }
+
+        // SHA-512 with a 64 byte salt needs 130 bytes
+        assert!(encode(Algorithm::Sha512, msg, &[0; 64], 1033).is_ok());
+        assert_eq!(
+            encode(Algorithm::Sha512, msg, &[0; 64], 1032),
+            Err(PssError::ModulusTooShort { minimum_bits: 1033 })
+        );
+    }
+}
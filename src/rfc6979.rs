@@ -0,0 +1,420 @@
+/*
+
+Deterministic nonces for DSA and ECDSA, see RFC 6979 3.2. The nonce k is
+derived from the private key x and the hash of the message with an
+HMAC_DRBG-like construction, so signing needs no randomness and the same
+message always gets the same signature:
+
+    K = HMAC_K(V || 0x00 || int2octets(x) || bits2octets(h1)), V = HMAC_K(V)
+    K = HMAC_K(V || 0x01 || int2octets(x) || bits2octets(h1)), V = HMAC_K(V)
+
+after which candidates are generated by iterating V = HMAC_K(V) until
+there are enough bits, and any candidate outside [1, q - 1] is skipped.
+
+The crate does no group arithmetic, so every number is a big-endian byte
+string: the group order q, the private key x and the nonces handed back,
+which are as long as q. h1 is the hash of the message, made with the same
+algorithm as the HMAC.
+
+A candidate can also turn out to be unusable for reasons only the signer
+can see (r or s being zero), in which case it takes the next one from the
+same NonceGenerator, which is the retry loop of step h.3.
+
+*/
+
+use crate::algorithm::Algorithm;
+use crate::hmac::Hmac;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum Rfc6979Error {
+    // q has to be at least 2 for [1, q - 1] to be non empty
+    InvalidOrder,
+    // x not in [1, q - 1]
+    InvalidKey,
+}
+
+impl fmt::Display for Rfc6979Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rfc6979Error::InvalidOrder => f.write_str("invalid group order"),
+            Rfc6979Error::InvalidKey => {
+                f.write_str("the private key has to be between 1 and q - 1")
+            }
+        }
+    }
+}
+
+impl Error for Rfc6979Error {}
+
+pub struct NonceGenerator {
+    algorithm: Algorithm,
+    // q without leading zero bytes, rlen bytes long
+    q: Vec<u8>,
+    qlen: usize,
+    k: Vec<u8>,
+    v: Vec<u8>,
+    started: bool,
+}
+
+impl NonceGenerator {
+    pub fn new(
+        algorithm: Algorithm,
+        q: &[u8],
+        x: &[u8],
+        h1: &[u8],
+    ) -> Result<NonceGenerator, Rfc6979Error> {
+        let q = strip_zeros(q).to_vec();
+        if q.is_empty() || (q.len() == 1 && q[0] == 1) {
+            return Err(Rfc6979Error::InvalidOrder);
+        }
+        let qlen = 8 * q.len() - q[0].leading_zeros() as usize;
+
+        let x = strip_zeros(x);
+        if x.is_empty() || x.len() > q.len() {
+            return Err(Rfc6979Error::InvalidKey);
+        }
+        #[cfg_attr(not(feature = "zeroize"), allow(unused_mut))]
+        let mut x = int2octets(x, q.len());
+        if x[..] >= q[..] {
+            return Err(Rfc6979Error::InvalidKey);
+        }
+
+        let mut h1 = bits2int(h1, qlen);
+        if h1[..] >= q[..] {
+            sub_assign(&mut h1, &q);
+        }
+
+        let h_len = algorithm.output_size();
+        let mut generator = NonceGenerator {
+            algorithm,
+            q,
+            qlen,
+            k: vec![0x00; h_len],
+            v: vec![0x01; h_len],
+            started: false,
+        };
+        generator.update(&[0x00], &x, &h1);
+        generator.update(&[0x01], &x, &h1);
+
+        #[cfg(feature = "zeroize")]
+        {
+            use zeroize::Zeroize;
+            x.zeroize();
+            h1.zeroize();
+        }
+
+        Ok(generator)
+    }
+
+    // K = HMAC_K(V || sep || inputs...), V = HMAC_K(V), steps d to g
+    fn update(&mut self, sep: &[u8], x: &[u8], h1: &[u8]) {
+        let mut hmac = Hmac::new(self.algorithm, &self.k);
+        hmac.update(&self.v);
+        hmac.update(sep);
+        hmac.update(x);
+        hmac.update(h1);
+        self.k = hmac.finalize();
+        self.v = self.hmac_v();
+    }
+
+    fn hmac_v(&self) -> Vec<u8> {
+        let mut hmac = Hmac::new(self.algorithm, &self.k);
+        hmac.update(&self.v);
+        hmac.finalize()
+    }
+
+    // the next nonce in [1, q - 1], as long as q
+    pub fn next_k(&mut self) -> Vec<u8> {
+        loop {
+            if self.started {
+                self.update(&[0x00], &[], &[]);
+            }
+            self.started = true;
+
+            let mut t = Vec::with_capacity(self.q.len() + self.v.len());
+            while t.len() < self.q.len() {
+                self.v = self.hmac_v();
+                t.extend_from_slice(&self.v);
+            }
+            let k = bits2int(&t, self.qlen);
+            if k.iter().any(|byte| *byte != 0) && k[..] < self.q[..] {
+                return k;
+            }
+        }
+    }
+}
+
+impl Iterator for NonceGenerator {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        Some(self.next_k())
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for NonceGenerator {
+    fn zeroize(&mut self) {
+        self.k.zeroize();
+        self.v.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for NonceGenerator {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(self);
+    }
+}
+
+// the first nonce for the message hash h1, which is the one to sign with
+// unless it gives r = 0 or s = 0
+pub fn generate_k(
+    algorithm: Algorithm,
+    q: &[u8],
+    x: &[u8],
+    h1: &[u8],
+) -> Result<Vec<u8>, Rfc6979Error> {
+    Ok(NonceGenerator::new(algorithm, q, x, h1)?.next_k())
+}
+
+fn strip_zeros(bytes: &[u8]) -> &[u8] {
+    let zeros = bytes.iter().take_while(|byte| **byte == 0).count();
+    &bytes[zeros..]
+}
+
+// 2.3.3, x as rlen bytes, x having no more than that without its leading
+// zeros
+fn int2octets(x: &[u8], rlen: usize) -> Vec<u8> {
+    let mut out = vec![0u8; rlen];
+    out[rlen - x.len()..].copy_from_slice(x);
+    out
+}
+
+// 2.3.2, the leftmost qlen bits of b as an integer, as rlen bytes
+fn bits2int(b: &[u8], qlen: usize) -> Vec<u8> {
+    let rlen = qlen.div_ceil(8);
+    if b.len() < rlen {
+        return int2octets(b, rlen);
+    }
+
+    let mut out = b[..rlen].to_vec();
+    let shift = 8 * rlen - qlen;
+    if shift > 0 {
+        for i in (0..rlen).rev() {
+            let carry = if i > 0 { out[i - 1] << (8 - shift) } else { 0 };
+            out[i] = (out[i] >> shift) | carry;
+        }
+    }
+    out
+}
+
+// x -= y for x >= y, both as long as each other
+fn sub_assign(x: &mut [u8], y: &[u8]) {
+    let mut borrow = 0i16;
+    for (a, b) in x.iter_mut().zip(y.iter()).rev() {
+        let diff = *a as i16 - *b as i16 - borrow;
+        *a = diff as u8;
+        borrow = (diff < 0) as i16;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::{decode_hex, hex_upper};
+
+    fn check(q: &str, x: &str, vectors: &[(Algorithm, &str, &str)]) {
+        let q = decode_hex(q).unwrap();
+        let x = decode_hex(x).unwrap();
+        for (algorithm, msg, k) in vectors.iter() {
+            let h1 = algorithm.hash(msg.as_bytes());
+            assert_eq!(
+                hex_upper(&generate_k(*algorithm, &q, &x, &h1).unwrap()),
+                *k,
+                "{} {}",
+                algorithm,
+                msg
+            );
+        }
+    }
+
+    // A.2.5
+    #[test]
+    fn test_rfc6979_p256() {
+        check(
+            "FFFFFFFF00000000FFFFFFFFFFFFFFFFBCE6FAADA7179E84F3B9CAC2FC632551",
+            "C9AFA9D845BA75166B5C215767B1D6934E50C3DB36E89B127B8A622B120F6721",
+            &[
+                (
+                    Algorithm::Sha224,
+                    "sample",
+                    "103F90EE9DC52E5E7FB5132B7033C63066D194321491862059967C715985D473",
+                ),
+                (
+                    Algorithm::Sha256,
+                    "sample",
+                    "A6E3C57DD01ABE90086538398355DD4C3B17AA873382B0F24D6129493D8AAD60",
+                ),
+                (
+                    Algorithm::Sha384,
+                    "sample",
+                    "09F634B188CEFD98E7EC88B1AA9852D734D0BC272F7D2A47DECC6EBEB375AAD4",
+                ),
+                (
+                    Algorithm::Sha512,
+                    "sample",
+                    "5FA81C63109BADB88C1F367B47DA606DA28CAD69AA22C4FE6AD7DF73A7173AA5",
+                ),
+                (
+                    Algorithm::Sha224,
+                    "test",
+                    "669F4426F2688B8BE0DB3A6BD1989BDAEFFF84B649EEB84F3DD26080F667FAA7",
+                ),
+                (
+                    Algorithm::Sha256,
+                    "test",
+                    "D16B6AE827F17175E040871A1C7EC3500192C4C92677336EC2537ACAEE0008E0",
+                ),
+                (
+                    Algorithm::Sha384,
+                    "test",
+                    "16AEFFA357260B04B1DD199693960740066C1A8F3E8EDD79070AA914D361B3B8",
+                ),
+                (
+                    Algorithm::Sha512,
+                    "test",
+                    "6915D11632ACA3C40D5D51C08DAF9C555933819548784480E93499000D9F0B7F",
+                ),
+            ],
+        );
+    }
+
+    // A.2.7, where qlen isn't a multiple of 8 and the hashes are shorter
+    // than q
+    #[test]
+    fn test_rfc6979_p521() {
+        check(
+            "01FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF\
+             FA51868783BF2F966B7FCC0148F709A5D03BB5C9B8899C47AEBB6FB71E91386409",
+            "00FAD06DAA62BA3B25D2FB40133DA757205DE67F5BB0018FEE8C86E1B68C7E75\
+             CAA896EB32F1F47C70855836A6D16FCC1466F6D8FBEC67DB89EC0C08B0E996B83538",
+            &[
+                (
+                    Algorithm::Sha224,
+                    "sample",
+                    "0121415EC2CD7726330A61F7F3FA5DE14BE9436019C4DB8CB4041F3B54CF31BE\
+                     0493EE3F427FB906393D895A19C9523F3A1D54BB8702BD4AA9C99DAB2597B92113F3",
+                ),
+                (
+                    Algorithm::Sha256,
+                    "sample",
+                    "00EDF38AFCAAECAB4383358B34D67C9F2216C8382AAEA44A3DAD5FDC9C325757\
+                     61793FEF24EB0FC276DFC4F6E3EC476752F043CF01415387470BCBD8678ED2C7E1A0",
+                ),
+                (
+                    Algorithm::Sha384,
+                    "sample",
+                    "01546A108BC23A15D6F21872F7DED661FA8431DDBD922D0DCDB77CC878C8553F\
+                     FAD064C95A920A750AC9137E527390D2D92F153E66196966EA554D9ADFCB109C4211",
+                ),
+                (
+                    Algorithm::Sha512,
+                    "sample",
+                    "01DAE2EA071F8110DC26882D4D5EAE0621A3256FC8847FB9022E2B7D28E6F101\
+                     98B1574FDD03A9053C08A1854A168AA5A57470EC97DD5CE090124EF52A2F7ECBFFD3",
+                ),
+                (
+                    Algorithm::Sha224,
+                    "test",
+                    "0040D09FCF3C8A5F62CF4FB223CBBB2B9937F6B0577C27020A99602C25A01136\
+                     987E452988781484EDBBCF1C47E554E7FC901BC3085E5206D9F619CFF07E73D6F706",
+                ),
+                (
+                    Algorithm::Sha256,
+                    "test",
+                    "001DE74955EFAABC4C4F17F8E84D881D1310B5392D7700275F82F145C61E8438\
+                     41AF09035BF7A6210F5A431A6A9E81C9323354A9E69135D44EBD2FCAA7731B909258",
+                ),
+                (
+                    Algorithm::Sha384,
+                    "test",
+                    "01F1FC4A349A7DA9A9E116BFDD055DC08E78252FF8E23AC276AC88B1770AE0B5\
+                     DCEB1ED14A4916B769A523CE1E90BA22846AF11DF8B300C38818F713DADD85DE0C88",
+                ),
+                (
+                    Algorithm::Sha512,
+                    "test",
+                    "016200813020EC986863BEDFC1B121F605C1215645018AEA1A7B215A564DE9EB\
+                     1B38A67AA1128B80CE391C4FB71187654AAA3431027BFC7F395766CA988C964DC56D",
+                ),
+            ],
+        );
+    }
+
+    // the worked example of A.1, a 163 bit q and a hash longer than it
+    #[test]
+    fn test_rfc6979_a1() {
+        check(
+            "04000000000000000000020108A2E0CC0D99F8A5EF",
+            "009A4D6792295A7F730FC3F2B49CBC0F62E862272F",
+            &[(
+                Algorithm::Sha256,
+                "sample",
+                "023AF4074C90A02B3FE61D286D5C87F425E6BDD81B",
+            )],
+        );
+    }
+
+    #[test]
+    fn test_rfc6979_retry() {
+        // with q = 2 the only nonce is 1, and half the candidates are 0
+        let mut generator = NonceGenerator::new(Algorithm::Sha256, &[2], &[1], &[0xab]).unwrap();
+        for _ in 0..8 {
+            assert_eq!(generator.next_k(), vec![1]);
+        }
+
+        // later nonces differ from the first
+        let q =
+            decode_hex("FFFFFFFF00000000FFFFFFFFFFFFFFFFBCE6FAADA7179E84F3B9CAC2FC632551").unwrap();
+        let h1 = Algorithm::Sha256.hash(b"sample");
+        let nonces: Vec<Vec<u8>> = NonceGenerator::new(Algorithm::Sha256, &q, &[7], &h1)
+            .unwrap()
+            .take(3)
+            .collect();
+        assert_eq!(
+            nonces[0],
+            generate_k(Algorithm::Sha256, &q, &[7], &h1).unwrap()
+        );
+        assert_ne!(nonces[0], nonces[1]);
+        assert_ne!(nonces[1], nonces[2]);
+    }
+
+    #[test]
+    fn test_rfc6979_errors() {
+        let h1 = Algorithm::Sha256.hash(b"sample");
+        for q in [&[][..], &[0, 0], &[0, 1]].iter() {
+            assert_eq!(
+                generate_k(Algorithm::Sha256, q, &[1], &h1),
+                Err(Rfc6979Error::InvalidOrder)
+            );
+        }
+        for x in [
+            &[][..],
+            &[0],
+            &[0x01, 0x00],
+            &[0x01, 0x01],
+            &[0x01, 0x00, 0x00],
+        ]
+        .iter()
+        {
+            assert_eq!(
+                generate_k(Algorithm::Sha256, &[0x01, 0x00], x, &h1),
+                Err(Rfc6979Error::InvalidKey)
+            );
+        }
+        assert!(generate_k(Algorithm::Sha256, &[0x01, 0x00], &[0x00, 0xff], &h1).is_ok());
+    }
+}
@@ -0,0 +1,843 @@
+/*
+
+SCRAM, the Salted Challenge Response Authentication Mechanism of RFC
+5802, as SCRAM-SHA-256 (RFC 7677) and SCRAM-SHA-512:
+
+    C: n,,n=user,r=<client nonce>
+    S: r=<client nonce><server nonce>,s=<salt>,i=<iterations>
+    C: c=<gs2 header>,r=<nonce>,p=<ClientProof>
+    S: v=<ServerSignature>
+
+    SaltedPassword  = PBKDF2(HMAC, SASLprep(password), salt, i)
+    ClientKey       = HMAC(SaltedPassword, "Client Key")
+    StoredKey       = H(ClientKey)
+    ClientSignature = HMAC(StoredKey, AuthMessage)
+    ClientProof     = ClientKey ^ ClientSignature
+    ServerSignature = HMAC(HMAC(SaltedPassword, "Server Key"), AuthMessage)
+
+where AuthMessage is the first two messages without the client's GS2
+header, and the last one without its proof, joined with commas.
+
+Both sides are state machines which consume themselves at each step, so
+a message can only be handled in the state which expects it. The nonces
+are passed in rather than generated here, since the crate has no source
+of randomness, and have to be fresh random printable characters.
+
+The server only stores StoredKey and ServerKey, which can't be used to
+log in as the user without also having seen an exchange.
+
+*/
+
+use crate::algorithm::Algorithm;
+use crate::encoding::{base64, decode_base64};
+use crate::hmac::hmac;
+use crate::pbkdf2::pbkdf2;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum ScramError {
+    // a username or password which SASLprep rejects
+    InvalidUsername,
+    InvalidPassword,
+    // a nonce with characters other than printable ASCII, or a comma
+    InvalidNonce,
+    // a message which doesn't follow the grammar of RFC 5802 section 7
+    InvalidMessage,
+    // a mandatory extension, m=, which nothing here understands
+    ExtensionsNotSupported,
+    // the server's nonce doesn't extend the client's, or the final
+    // message has a different one
+    NonceMismatch,
+    ChannelBindingMismatch,
+    // the client says it supports channel binding but thinks the server
+    // doesn't, when the server does, which is a downgrade
+    ServerSupportsChannelBinding,
+    ChannelBindingNotSupported,
+    InvalidProof,
+    // the server's signature doesn't match, so it doesn't know the
+    // password
+    InvalidServerSignature,
+    // the e= value of a server-final-message
+    Server(String),
+}
+
+impl ScramError {
+    // the server-final-message reporting this error to the client
+    pub fn server_final_message(&self) -> String {
+        let value = match self {
+            ScramError::InvalidUsername => "invalid-username-encoding",
+            ScramError::InvalidMessage | ScramError::InvalidNonce | ScramError::NonceMismatch => {
+                "invalid-encoding"
+            }
+            ScramError::ExtensionsNotSupported => "extensions-not-supported",
+            ScramError::ChannelBindingMismatch => "channel-bindings-dont-match",
+            ScramError::ServerSupportsChannelBinding => "server-does-support-channel-binding",
+            ScramError::ChannelBindingNotSupported => "channel-binding-not-supported",
+            ScramError::InvalidProof => "invalid-proof",
+            _ => "other-error",
+        };
+        format!("e={}", value)
+    }
+}
+
+impl fmt::Display for ScramError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScramError::InvalidUsername => f.write_str("username prohibited by SASLprep"),
+            ScramError::InvalidPassword => f.write_str("password prohibited by SASLprep"),
+            ScramError::InvalidNonce => f.write_str("invalid nonce"),
+            ScramError::InvalidMessage => f.write_str("invalid SCRAM message"),
+            ScramError::ExtensionsNotSupported => f.write_str("mandatory extensions not supported"),
+            ScramError::NonceMismatch => f.write_str("nonce mismatch"),
+            ScramError::ChannelBindingMismatch => f.write_str("channel bindings don't match"),
+            ScramError::ServerSupportsChannelBinding => {
+                f.write_str("the server does support channel binding")
+            }
+            ScramError::ChannelBindingNotSupported => f.write_str("channel binding not supported"),
+            ScramError::InvalidProof => f.write_str("invalid client proof"),
+            ScramError::InvalidServerSignature => f.write_str("invalid server signature"),
+            ScramError::Server(value) => write!(f, "server error: {}", value),
+        }
+    }
+}
+
+impl Error for ScramError {}
+
+// "SCRAM-SHA-256" and the like, with "-PLUS" for channel binding. Only
+// SHA-256 and SHA-512 have registered mechanism names.
+pub fn mechanism_name(algorithm: Algorithm, plus: bool) -> Option<&'static str> {
+    match (algorithm, plus) {
+        (Algorithm::Sha256, false) => Some("SCRAM-SHA-256"),
+        (Algorithm::Sha256, true) => Some("SCRAM-SHA-256-PLUS"),
+        (Algorithm::Sha512, false) => Some("SCRAM-SHA-512"),
+        (Algorithm::Sha512, true) => Some("SCRAM-SHA-512-PLUS"),
+        _ => None,
+    }
+}
+
+// The channel binding type, such as tls-server-end-point or tls-exporter,
+// and its data for the current connection
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelBindingData {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+// The client's GS2 channel binding flag
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChannelBinding {
+    // "n", the client doesn't support channel binding
+    None,
+    // "y", the client does, but thinks the server doesn't
+    SupportedNotUsed,
+    // "p=", the client binds to the channel
+    Used(ChannelBindingData),
+}
+
+// SASLprep, then "=" and "," escaped as "=3D" and "=2C"
+fn sasl_name(name: &str) -> Result<String, ScramError> {
+    let name = stringprep::saslprep(name).map_err(|_| ScramError::InvalidUsername)?;
+    Ok(name.replace('=', "=3D").replace(',', "=2C"))
+}
+
+fn decode_sasl_name(name: &str) -> Result<String, ScramError> {
+    let mut out = String::with_capacity(name.len());
+    let mut rest = name;
+    while let Some(i) = rest.find('=') {
+        out.push_str(&rest[..i]);
+        if rest[i..].starts_with("=3D") {
+            out.push('=');
+        } else if rest[i..].starts_with("=2C") {
+            out.push(',');
+        } else {
+            return Err(ScramError::InvalidUsername);
+        }
+        rest = &rest[i + 3..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn check_nonce(nonce: &str) -> Result<(), ScramError> {
+    if nonce.is_empty() || !nonce.bytes().all(|c| c.is_ascii_graphic() && c != b',') {
+        return Err(ScramError::InvalidNonce);
+    }
+    Ok(())
+}
+
+// The attributes of a message, each a letter, '=' and a value. Values can
+// hold '=' but not ','.
+fn attributes(msg: &str) -> Result<Vec<(u8, &str)>, ScramError> {
+    msg.split(',')
+        .map(|attribute| {
+            let bytes = attribute.as_bytes();
+            if bytes.len() < 2 || !bytes[0].is_ascii_alphabetic() || bytes[1] != b'=' {
+                return Err(ScramError::InvalidMessage);
+            }
+            Ok((bytes[0], &attribute[2..]))
+        })
+        .collect()
+}
+
+fn salted_password(
+    algorithm: Algorithm,
+    password: &str,
+    salt: &[u8],
+    iterations: u32,
+) -> Result<Vec<u8>, ScramError> {
+    let password = stringprep::saslprep(password).map_err(|_| ScramError::InvalidPassword)?;
+    pbkdf2(
+        algorithm,
+        password.as_bytes(),
+        salt,
+        iterations,
+        algorithm.output_size(),
+    )
+    .map_err(|_| ScramError::InvalidMessage)
+}
+
+// What a server stores for each user, derived from the password once
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoredCredentials {
+    pub algorithm: Algorithm,
+    pub salt: Vec<u8>,
+    pub iterations: u32,
+    pub stored_key: Vec<u8>,
+    pub server_key: Vec<u8>,
+}
+
+impl StoredCredentials {
+    // the salt should be fresh random bytes, and RFC 7677 asks for at
+    // least 4096 iterations
+    pub fn new(
+        algorithm: Algorithm,
+        password: &str,
+        salt: &[u8],
+        iterations: u32,
+    ) -> Result<StoredCredentials, ScramError> {
+        let salted_password = salted_password(algorithm, password, salt, iterations)?;
+        let client_key = hmac(algorithm, &salted_password, b"Client Key");
+        Ok(StoredCredentials {
+            algorithm,
+            salt: salt.to_vec(),
+            iterations,
+            stored_key: algorithm.hash(&client_key),
+            server_key: hmac(algorithm, &salted_password, b"Server Key"),
+        })
+    }
+}
+
+// The GS2 header, "n,,", "y,," or "p=<name>,," with an a= authzid
+// between the commas if there is one
+fn gs2_header(channel_binding: &ChannelBinding, authzid: Option<&str>) -> String {
+    let flag = match channel_binding {
+        ChannelBinding::None => "n".to_string(),
+        ChannelBinding::SupportedNotUsed => "y".to_string(),
+        ChannelBinding::Used(binding) => format!("p={}", binding.name),
+    };
+    match authzid {
+        Some(authzid) => format!("{},a={},", flag, authzid),
+        None => format!("{},,", flag),
+    }
+}
+
+// the c= attribute, the GS2 header followed by the channel binding data
+fn channel_binding_attribute(gs2_header: &str, channel_binding: &ChannelBinding) -> String {
+    let mut cbind_input = gs2_header.as_bytes().to_vec();
+    if let ChannelBinding::Used(binding) = channel_binding {
+        cbind_input.extend_from_slice(&binding.data);
+    }
+    base64(&cbind_input)
+}
+
+pub struct ScramClient {
+    algorithm: Algorithm,
+    password: String,
+    nonce: String,
+    channel_binding: ChannelBinding,
+    gs2_header: String,
+    client_first_bare: String,
+}
+
+impl ScramClient {
+    pub fn new(
+        algorithm: Algorithm,
+        username: &str,
+        password: &str,
+        nonce: &str,
+        channel_binding: ChannelBinding,
+    ) -> Result<ScramClient, ScramError> {
+        ScramClient::with_authzid(algorithm, username, password, None, nonce, channel_binding)
+    }
+
+    // logging in as username to act as authzid
+    pub fn with_authzid(
+        algorithm: Algorithm,
+        username: &str,
+        password: &str,
+        authzid: Option<&str>,
+        nonce: &str,
+        channel_binding: ChannelBinding,
+    ) -> Result<ScramClient, ScramError> {
+        check_nonce(nonce)?;
+        if let ChannelBinding::Used(binding) = &channel_binding {
+            if binding.name.is_empty()
+                || !binding
+                    .name
+                    .bytes()
+                    .all(|c| c.is_ascii_alphanumeric() || c == b'.' || c == b'-')
+            {
+                return Err(ScramError::InvalidMessage);
+            }
+        }
+        let authzid = authzid.map(sasl_name).transpose()?;
+        let gs2_header = gs2_header(&channel_binding, authzid.as_deref());
+        let client_first_bare = format!("n={},r={}", sasl_name(username)?, nonce);
+
+        Ok(ScramClient {
+            algorithm,
+            password: password.to_string(),
+            nonce: nonce.to_string(),
+            channel_binding,
+            gs2_header,
+            client_first_bare,
+        })
+    }
+
+    pub fn client_first_message(&self) -> String {
+        format!("{}{}", self.gs2_header, self.client_first_bare)
+    }
+
+    // the client-final-message answering the server-first-message
+    pub fn handle_server_first(
+        self,
+        server_first: &str,
+    ) -> Result<(ScramClientFinal, String), ScramError> {
+        let attributes = attributes(server_first)?;
+        if attributes.first().map(|(name, _)| *name) == Some(b'm') {
+            return Err(ScramError::ExtensionsNotSupported);
+        }
+        let (nonce, salt, iterations) = match attributes[..] {
+            [(b'r', nonce), (b's', salt), (b'i', iterations), ..] => (nonce, salt, iterations),
+            _ => return Err(ScramError::InvalidMessage),
+        };
+        check_nonce(nonce)?;
+        if !nonce.starts_with(&self.nonce) || nonce.len() == self.nonce.len() {
+            return Err(ScramError::NonceMismatch);
+        }
+        let salt = decode_base64(salt).map_err(|_| ScramError::InvalidMessage)?;
+        let iterations = parse_iterations(iterations)?;
+
+        let salted_password = salted_password(self.algorithm, &self.password, &salt, iterations)?;
+        let client_final_without_proof = format!(
+            "c={},r={}",
+            channel_binding_attribute(&self.gs2_header, &self.channel_binding),
+            nonce
+        );
+        let auth_message = format!(
+            "{},{},{}",
+            self.client_first_bare, server_first, client_final_without_proof
+        );
+
+        let client_key = hmac(self.algorithm, &salted_password, b"Client Key");
+        let stored_key = self.algorithm.hash(&client_key);
+        let client_signature = hmac(self.algorithm, &stored_key, auth_message.as_bytes());
+        let proof: Vec<u8> = client_key
+            .iter()
+            .zip(client_signature.iter())
+            .map(|(key, signature)| key ^ signature)
+            .collect();
+        let server_key = hmac(self.algorithm, &salted_password, b"Server Key");
+        let server_signature = hmac(self.algorithm, &server_key, auth_message.as_bytes());
+
+        Ok((
+            ScramClientFinal { server_signature },
+            format!("{},p={}", client_final_without_proof, base64(&proof)),
+        ))
+    }
+}
+
+fn parse_iterations(iterations: &str) -> Result<u32, ScramError> {
+    if !iterations.bytes().all(|c| c.is_ascii_digit()) {
+        return Err(ScramError::InvalidMessage);
+    }
+    match iterations.parse() {
+        Ok(iterations) if iterations > 0 => Ok(iterations),
+        _ => Err(ScramError::InvalidMessage),
+    }
+}
+
+// A client waiting for the server-final-message
+pub struct ScramClientFinal {
+    server_signature: Vec<u8>,
+}
+
+impl ScramClientFinal {
+    // Ok once the server has proved it knows the password too
+    pub fn handle_server_final(self, server_final: &str) -> Result<(), ScramError> {
+        match attributes(server_final)?.first() {
+            Some((b'e', value)) => Err(ScramError::Server(value.to_string())),
+            Some((b'v', signature)) => {
+                let signature = decode_base64(signature).map_err(|_| ScramError::InvalidMessage)?;
+                if crate::util::ct_eq(&signature, &self.server_signature) {
+                    Ok(())
+                } else {
+                    Err(ScramError::InvalidServerSignature)
+                }
+            }
+            _ => Err(ScramError::InvalidMessage),
+        }
+    }
+}
+
+pub struct ScramServer {
+    channel_binding: Option<ChannelBindingData>,
+}
+
+impl ScramServer {
+    // with the channel binding of the connection if the server offers the
+    // -PLUS mechanism
+    pub fn new(channel_binding: Option<ChannelBindingData>) -> ScramServer {
+        ScramServer { channel_binding }
+    }
+
+    // Parse the client-first-message, after which the caller looks up the
+    // credentials of the user it names
+    pub fn handle_client_first(self, client_first: &str) -> Result<ScramServerFirst, ScramError> {
+        // the GS2 header is everything up to the second comma
+        let mut fields = client_first.splitn(3, ',');
+        let (flag, authzid, bare) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(flag), Some(authzid), Some(bare)) => (flag, authzid, bare),
+            _ => return Err(ScramError::InvalidMessage),
+        };
+        let gs2_header = client_first[..client_first.len() - bare.len()].to_string();
+
+        let channel_binding = match flag {
+            "n" => ChannelBinding::None,
+            "y" if self.channel_binding.is_some() => {
+                return Err(ScramError::ServerSupportsChannelBinding)
+            }
+            "y" => ChannelBinding::SupportedNotUsed,
+            _ => match (flag.strip_prefix("p="), &self.channel_binding) {
+                (Some(name), Some(binding)) if name == binding.name => {
+                    ChannelBinding::Used(binding.clone())
+                }
+                (Some(_), _) => return Err(ScramError::ChannelBindingNotSupported),
+                (None, _) => return Err(ScramError::InvalidMessage),
+            },
+        };
+        let authzid = match authzid {
+            "" => None,
+            _ => match authzid.strip_prefix("a=") {
+                Some(authzid) => Some(decode_sasl_name(authzid)?),
+                None => return Err(ScramError::InvalidMessage),
+            },
+        };
+
+        let attributes = attributes(bare)?;
+        if attributes.first().map(|(name, _)| *name) == Some(b'm') {
+            return Err(ScramError::ExtensionsNotSupported);
+        }
+        let (username, nonce) = match attributes[..] {
+            [(b'n', username), (b'r', nonce), ..] => (decode_sasl_name(username)?, nonce),
+            _ => return Err(ScramError::InvalidMessage),
+        };
+        check_nonce(nonce)?;
+
+        Ok(ScramServerFirst {
+            username,
+            authzid,
+            client_nonce: nonce.to_string(),
+            channel_binding,
+            gs2_header,
+            client_first_bare: bare.to_string(),
+        })
+    }
+}
+
+// A server which has the client-first-message and needs the user's
+// credentials to answer it
+pub struct ScramServerFirst {
+    username: String,
+    authzid: Option<String>,
+    client_nonce: String,
+    channel_binding: ChannelBinding,
+    gs2_header: String,
+    client_first_bare: String,
+}
+
+impl ScramServerFirst {
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    pub fn authzid(&self) -> Option<&str> {
+        self.authzid.as_deref()
+    }
+
+    // The server-first-message. The server's nonce is appended to the
+    // client's. For an unknown user the exchange should carry on with
+    // made up credentials, and fail at the proof, so as not to give away
+    // which users exist.
+    pub fn server_first(
+        self,
+        credentials: &StoredCredentials,
+        nonce: &str,
+    ) -> Result<(ScramServerFinal, String), ScramError> {
+        check_nonce(nonce)?;
+        let nonce = format!("{}{}", self.client_nonce, nonce);
+        let server_first = format!(
+            "r={},s={},i={}",
+            nonce,
+            base64(&credentials.salt),
+            credentials.iterations
+        );
+
+        Ok((
+            ScramServerFinal {
+                credentials: credentials.clone(),
+                nonce,
+                channel_binding: self.channel_binding,
+                gs2_header: self.gs2_header,
+                auth_message_start: format!("{},{}", self.client_first_bare, server_first),
+            },
+            server_first,
+        ))
+    }
+}
+
+// A server waiting for the client-final-message
+pub struct ScramServerFinal {
+    credentials: StoredCredentials,
+    nonce: String,
+    channel_binding: ChannelBinding,
+    gs2_header: String,
+    auth_message_start: String,
+}
+
+impl ScramServerFinal {
+    // The server-final-message once the client has proved it knows the
+    // password. On an error, the message to send is
+    // ScramError::server_final_message.
+    pub fn handle_client_final(self, client_final: &str) -> Result<String, ScramError> {
+        let proof_start = client_final
+            .rfind(",p=")
+            .ok_or(ScramError::InvalidMessage)?;
+        let without_proof = &client_final[..proof_start];
+        let attributes = attributes(without_proof)?;
+        let (channel_binding, nonce) = match attributes[..] {
+            [(b'c', channel_binding), (b'r', nonce), ..] => (channel_binding, nonce),
+            _ => return Err(ScramError::InvalidMessage),
+        };
+
+        let expected = channel_binding_attribute(&self.gs2_header, &self.channel_binding);
+        if channel_binding != expected {
+            return Err(ScramError::ChannelBindingMismatch);
+        }
+        if nonce != self.nonce {
+            return Err(ScramError::NonceMismatch);
+        }
+        let proof = decode_base64(&client_final[proof_start + 3..])
+            .map_err(|_| ScramError::InvalidMessage)?;
+
+        let algorithm = self.credentials.algorithm;
+        let auth_message = format!("{},{}", self.auth_message_start, without_proof);
+        let client_signature = hmac(
+            algorithm,
+            &self.credentials.stored_key,
+            auth_message.as_bytes(),
+        );
+        // ClientKey = ClientProof ^ ClientSignature, whose hash has to be
+        // the StoredKey
+        if proof.len() != client_signature.len() {
+            return Err(ScramError::InvalidProof);
+        }
+        let client_key: Vec<u8> = proof
+            .iter()
+            .zip(client_signature.iter())
+            .map(|(proof, signature)| proof ^ signature)
+            .collect();
+        if !crate::util::ct_eq(&algorithm.hash(&client_key), &self.credentials.stored_key) {
+            return Err(ScramError::InvalidProof);
+        }
+
+        let server_signature = hmac(
+            algorithm,
+            &self.credentials.server_key,
+            auth_message.as_bytes(),
+        );
+        Ok(format!("v={}", base64(&server_signature)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIENT_NONCE: &str = "rOprNGfwEbeRWgbNEkqO";
+    const SERVER_NONCE: &str = "%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0";
+    const SALT: &str = "W22ZaJ0SNY7soEsUEjb6gQ==";
+
+    // the example exchange of RFC 7677 section 3, and the same with
+    // SHA-512 checked against a Python implementation
+    const EXCHANGES: [(Algorithm, &str, &str); 2] = [
+        (
+            Algorithm::Sha256,
+            "dHzbZapWIk4jUhN+Ute9ytag9zjfMHgsqmmiz7AndVQ=",
+            "6rriTRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4=",
+        ),
+        (
+            Algorithm::Sha512,
+            "gMGXRcevScNtxZ6/8lQYpGtnsNAc3mGcmNomv+xnoOMw+3R2xNJdMNnzMlTN8PPC6wdp6dybEmDYXYTxwnYPJQ==",
+            "ZQnYEgWQMFmmsM8aQMF0nDDCy/AgCzkwk8CmMZYcMg0vSVlKDanekLtifDSeVGT4+5ZxXnJq199RVG2rR7N7Zw==",
+        ),
+    ];
+
+    fn credentials(algorithm: Algorithm) -> StoredCredentials {
+        StoredCredentials::new(algorithm, "pencil", &decode_base64(SALT).unwrap(), 4096).unwrap()
+    }
+
+    #[test]
+    fn test_scram_rfc7677() {
+        for (algorithm, proof, signature) in EXCHANGES.iter() {
+            let client = ScramClient::new(
+                *algorithm,
+                "user",
+                "pencil",
+                CLIENT_NONCE,
+                ChannelBinding::None,
+            )
+            .unwrap();
+            let client_first = client.client_first_message();
+            assert_eq!(client_first, "n,,n=user,r=rOprNGfwEbeRWgbNEkqO");
+
+            let server = ScramServer::new(None)
+                .handle_client_first(&client_first)
+                .unwrap();
+            assert_eq!(server.username(), "user");
+            assert_eq!(server.authzid(), None);
+            let (server, server_first) = server
+                .server_first(&credentials(*algorithm), SERVER_NONCE)
+                .unwrap();
+            assert_eq!(
+                server_first,
+                "r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,\
+                 s=W22ZaJ0SNY7soEsUEjb6gQ==,i=4096"
+            );
+
+            let (client, client_final) = client.handle_server_first(&server_first).unwrap();
+            assert_eq!(
+                client_final,
+                format!(
+                    "c=biws,r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,p={}",
+                    proof
+                )
+            );
+
+            let server_final = server.handle_client_final(&client_final).unwrap();
+            assert_eq!(server_final, format!("v={}", signature));
+            assert_eq!(client.handle_server_final(&server_final), Ok(()));
+        }
+    }
+
+    fn exchange(
+        client: ScramClient,
+        server: ScramServer,
+        password: &str,
+    ) -> Result<(ScramClientFinal, String), ScramError> {
+        let server = server.handle_client_first(&client.client_first_message())?;
+        let credentials = StoredCredentials::new(Algorithm::Sha256, password, b"salt", 4096)?;
+        let (server, server_first) = server.server_first(&credentials, "servernonce")?;
+        let (client, client_final) = client.handle_server_first(&server_first)?;
+        Ok((client, server.handle_client_final(&client_final)?))
+    }
+
+    #[test]
+    fn test_scram_failures() {
+        let client = |password| {
+            ScramClient::new(
+                Algorithm::Sha256,
+                "user",
+                password,
+                "nonce",
+                ChannelBinding::None,
+            )
+            .unwrap()
+        };
+        assert!(exchange(client("pencil"), ScramServer::new(None), "pencil").is_ok());
+        assert_eq!(
+            exchange(client("pen"), ScramServer::new(None), "pencil").err(),
+            Some(ScramError::InvalidProof)
+        );
+        assert_eq!(
+            ScramError::InvalidProof.server_final_message(),
+            "e=invalid-proof"
+        );
+
+        // a server which doesn't know the password
+        let (client_final, _) =
+            exchange(client("pencil"), ScramServer::new(None), "pencil").unwrap();
+        assert_eq!(
+            client_final.handle_server_final("v=6rriTRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4="),
+            Err(ScramError::InvalidServerSignature)
+        );
+        let (client_final, _) =
+            exchange(client("pencil"), ScramServer::new(None), "pencil").unwrap();
+        assert_eq!(
+            client_final.handle_server_final("e=unknown-user"),
+            Err(ScramError::Server("unknown-user".to_string()))
+        );
+
+        // a server nonce which doesn't extend the client's
+        assert_eq!(
+            client("pencil")
+                .handle_server_first("r=other,s=c2FsdA==,i=4096")
+                .err(),
+            Some(ScramError::NonceMismatch)
+        );
+        for server_first in [
+            "r=noncex,s=c2FsdA==",
+            "r=noncex,s=c2FsdA==,i=0",
+            "r=noncex,s=c2F*dA==,i=4096",
+            "s=c2FsdA==,r=noncex,i=4096",
+        ]
+        .iter()
+        {
+            assert_eq!(
+                client("pencil").handle_server_first(server_first).err(),
+                Some(ScramError::InvalidMessage)
+            );
+        }
+        assert_eq!(
+            client("pencil")
+                .handle_server_first("m=ext,r=noncex,s=c2FsdA==,i=4096")
+                .err(),
+            Some(ScramError::ExtensionsNotSupported)
+        );
+
+        for client_first in [
+            "n,,r=nonce",
+            "x,,n=user,r=nonce",
+            "n,,n=us=er,r=nonce",
+            "n,n=user,r=nonce",
+            "n,,n=user,r=no nce",
+        ]
+        .iter()
+        {
+            assert!(ScramServer::new(None)
+                .handle_client_first(client_first)
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn test_scram_channel_binding() {
+        let tls = ChannelBindingData {
+            name: "tls-server-end-point".to_string(),
+            data: vec![0x01, 0x02, 0x03],
+        };
+        let client = |binding| {
+            ScramClient::new(Algorithm::Sha256, "user", "pencil", "nonce", binding).unwrap()
+        };
+
+        let (client_final, server_final) = exchange(
+            client(ChannelBinding::Used(tls.clone())),
+            ScramServer::new(Some(tls.clone())),
+            "pencil",
+        )
+        .unwrap();
+        assert_eq!(client_final.handle_server_final(&server_final), Ok(()));
+
+        // bound to a different channel, as with a man in the middle
+        let other = ChannelBindingData {
+            data: vec![0x04],
+            ..tls.clone()
+        };
+        assert_eq!(
+            exchange(
+                client(ChannelBinding::Used(tls.clone())),
+                ScramServer::new(Some(other)),
+                "pencil"
+            )
+            .err(),
+            Some(ScramError::ChannelBindingMismatch)
+        );
+        assert_eq!(
+            exchange(
+                client(ChannelBinding::Used(tls.clone())),
+                ScramServer::new(None),
+                "pencil"
+            )
+            .err(),
+            Some(ScramError::ChannelBindingNotSupported)
+        );
+
+        // "y" to a server which supports channel binding is a downgrade
+        assert_eq!(
+            exchange(
+                client(ChannelBinding::SupportedNotUsed),
+                ScramServer::new(Some(tls.clone())),
+                "pencil"
+            )
+            .err(),
+            Some(ScramError::ServerSupportsChannelBinding)
+        );
+        assert!(exchange(
+            client(ChannelBinding::SupportedNotUsed),
+            ScramServer::new(None),
+            "pencil"
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_scram_saslprep() {
+        // a soft hyphen is mapped to nothing, and "=" and "," escaped
+        let client = ScramClient::with_authzid(
+            Algorithm::Sha256,
+            "us\u{ad}er,=",
+            "pencil",
+            Some("admin"),
+            "nonce",
+            ChannelBinding::None,
+        )
+        .unwrap();
+        let client_first = client.client_first_message();
+        assert_eq!(client_first, "n,a=admin,n=user=2C=3D,r=nonce");
+        let server = ScramServer::new(None)
+            .handle_client_first(&client_first)
+            .unwrap();
+        assert_eq!(server.username(), "user,=");
+        assert_eq!(server.authzid(), Some("admin"));
+
+        // passwords too, with a non-ASCII space mapped to a space
+        assert_eq!(
+            StoredCredentials::new(Algorithm::Sha256, "pen\u{a0}cil", b"salt", 1),
+            StoredCredentials::new(Algorithm::Sha256, "pen cil", b"salt", 1)
+        );
+        assert_eq!(
+            StoredCredentials::new(Algorithm::Sha256, "pen\u{7}cil", b"salt", 1),
+            Err(ScramError::InvalidPassword)
+        );
+        assert!(ScramClient::new(
+            Algorithm::Sha256,
+            "user\u{0}",
+            "pencil",
+            "nonce",
+            ChannelBinding::None
+        )
+        .is_err());
+        assert_eq!(
+            ScramClient::new(
+                Algorithm::Sha256,
+                "user",
+                "pencil",
+                "a,b",
+                ChannelBinding::None
+            )
+            .err(),
+            Some(ScramError::InvalidNonce)
+        );
+    }
+}
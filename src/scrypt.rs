@@ -0,0 +1,490 @@
+/*
+
+The scrypt password-based key derivation function, see RFC 7914:
+
+    B = PBKDF2-HMAC-SHA256(P, S, 1, p * 128 * r)
+    B_i = ROMix(r, B_i, N) for each of the p blocks
+    DK = PBKDF2-HMAC-SHA256(P, B, 1, dkLen)
+
+ROMix fills a table of N blocks of 128 * r bytes by iterating BlockMix,
+a Salsa20/8 based mixing function, then reads the table back in a data
+dependent order, which is what makes scrypt expensive in memory as well
+as in time.
+
+The cost is given as log2 N. Since N, r and p may come from a stored
+hash, the memory ROMix would allocate is checked against a limit before
+anything is done, 1 GiB by default.
+
+Hashes are stored in the PHC string format,
+
+    $scrypt$ln=17,r=8,p=1$<salt>$<hash>
+
+with the salt and hash in unpadded standard base64.
+
+*/
+
+use crate::algorithm::Algorithm;
+use crate::encoding;
+use crate::pbkdf2::pbkdf2;
+use crate::util::rotl32;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+pub const DEFAULT_MAX_MEMORY: usize = 1 << 30;
+
+// the length of the hashes written by PasswordHash::new
+pub const HASH_LEN: usize = 32;
+
+#[derive(Debug, PartialEq)]
+pub enum ScryptError {
+    // N not a power of two greater than 1 or not below 2^(16 * r), r or p
+    // zero, or r * p not below 2^30
+    InvalidParams,
+    MemoryLimit { maximum: usize },
+    // more than (2^32 - 1) * 32 bytes
+    OutputTooLong,
+    // a PHC string which isn't $scrypt$ln=..,r=..,p=..$salt$hash
+    InvalidFormat,
+}
+
+impl fmt::Display for ScryptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScryptError::InvalidParams => f.write_str("invalid scrypt parameters"),
+            ScryptError::MemoryLimit { maximum } => write!(
+                f,
+                "the parameters need more than the limit of {} bytes of memory",
+                maximum
+            ),
+            ScryptError::OutputTooLong => f.write_str("derived key too long"),
+            ScryptError::InvalidFormat => f.write_str("invalid scrypt PHC string"),
+        }
+    }
+}
+
+impl Error for ScryptError {}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Params {
+    log_n: u8,
+    r: u32,
+    p: u32,
+    max_memory: usize,
+}
+
+impl Params {
+    pub fn new(log_n: u8, r: u32, p: u32) -> Result<Params, ScryptError> {
+        if log_n == 0
+            || log_n >= 64
+            || r == 0
+            || p == 0
+            || (log_n as u64) >= 16 * r as u64
+            || r as u64 * p as u64 >= 1 << 30
+        {
+            return Err(ScryptError::InvalidParams);
+        }
+        Ok(Params {
+            log_n,
+            r,
+            p,
+            max_memory: DEFAULT_MAX_MEMORY,
+        })
+    }
+
+    pub fn log_n(&self) -> u8 {
+        self.log_n
+    }
+
+    pub fn r(&self) -> u32 {
+        self.r
+    }
+
+    pub fn p(&self) -> u32 {
+        self.p
+    }
+
+    // the bytes of ROMix's table and the p blocks, or None if that
+    // doesn't fit in a usize
+    pub fn memory(&self) -> Option<usize> {
+        let block = 128usize.checked_mul(self.r as usize)?;
+        let blocks = 1usize
+            .checked_shl(self.log_n as u32)?
+            .checked_add(self.p as usize)?;
+        block.checked_mul(blocks)
+    }
+
+    pub fn max_memory(&self) -> usize {
+        self.max_memory
+    }
+
+    pub fn set_max_memory(&mut self, max_memory: usize) {
+        self.max_memory = max_memory;
+    }
+}
+
+// log2 N = 17, r = 8 and p = 1, which takes 128 MiB
+impl Default for Params {
+    fn default() -> Params {
+        Params::new(17, 8, 1).unwrap()
+    }
+}
+
+pub fn scrypt(
+    password: &[u8],
+    salt: &[u8],
+    params: &Params,
+    dk_len: usize,
+) -> Result<Vec<u8>, ScryptError> {
+    match params.memory() {
+        Some(memory) if memory <= params.max_memory => {}
+        _ => {
+            return Err(ScryptError::MemoryLimit {
+                maximum: params.max_memory,
+            })
+        }
+    }
+    if dk_len as u64 > u32::MAX as u64 * 32 {
+        return Err(ScryptError::OutputTooLong);
+    }
+
+    let r = params.r as usize;
+    let block_len = 128 * r;
+    let mut b = pbkdf2(
+        Algorithm::Sha256,
+        password,
+        salt,
+        1,
+        params.p as usize * block_len,
+    )
+    .unwrap();
+
+    let mut x = vec![0u32; 32 * r];
+    let mut v = vec![0u32; (32 * r) << params.log_n];
+    for chunk in b.chunks_mut(block_len) {
+        for (word, bytes) in x.iter_mut().zip(chunk.chunks(4)) {
+            *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        ro_mix(&mut x, &mut v, params.log_n);
+        for (word, bytes) in x.iter().zip(chunk.chunks_mut(4)) {
+            bytes.copy_from_slice(&word.to_le_bytes());
+        }
+    }
+
+    let dk = pbkdf2(Algorithm::Sha256, password, &b, 1, dk_len).unwrap();
+
+    #[cfg(feature = "zeroize")]
+    {
+        use zeroize::Zeroize;
+        b.zeroize();
+        x.zeroize();
+        v.zeroize();
+    }
+
+    Ok(dk)
+}
+
+// Salsa20/8, four double rounds of the Salsa20 core
+fn salsa20_8(b: &mut [u32]) {
+    fn quarter_round(x: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+        x[b] ^= rotl32(7, x[a].wrapping_add(x[d]));
+        x[c] ^= rotl32(9, x[b].wrapping_add(x[a]));
+        x[d] ^= rotl32(13, x[c].wrapping_add(x[b]));
+        x[a] ^= rotl32(18, x[d].wrapping_add(x[c]));
+    }
+
+    let mut x = [0u32; 16];
+    x.copy_from_slice(b);
+    for _ in 0..4 {
+        // columns, then rows
+        quarter_round(&mut x, 0, 4, 8, 12);
+        quarter_round(&mut x, 5, 9, 13, 1);
+        quarter_round(&mut x, 10, 14, 2, 6);
+        quarter_round(&mut x, 15, 3, 7, 11);
+        quarter_round(&mut x, 0, 1, 2, 3);
+        quarter_round(&mut x, 5, 6, 7, 4);
+        quarter_round(&mut x, 10, 11, 8, 9);
+        quarter_round(&mut x, 15, 12, 13, 14);
+    }
+    for (b, x) in b.iter_mut().zip(x.iter()) {
+        *b = b.wrapping_add(*x);
+    }
+}
+
+// BlockMix of the 2 * r 64 byte blocks of b into y, with the even blocks
+// going to the first half of y and the odd ones to the second
+fn block_mix(b: &[u32], y: &mut [u32]) {
+    let r = b.len() / 32;
+    let mut x = [0u32; 16];
+    x.copy_from_slice(&b[b.len() - 16..]);
+    for (i, block) in b.chunks(16).enumerate() {
+        for (x, b) in x.iter_mut().zip(block.iter()) {
+            *x ^= b;
+        }
+        salsa20_8(&mut x);
+        let out = (i / 2 + (i % 2) * r) * 16;
+        y[out..out + 16].copy_from_slice(&x);
+    }
+}
+
+// ROMix of x in place, with v as the table of N blocks
+fn ro_mix(x: &mut [u32], v: &mut [u32], log_n: u8) {
+    let len = x.len();
+    let n = 1u64 << log_n;
+    let mut y = vec![0u32; len];
+
+    for block in v.chunks_mut(len) {
+        block.copy_from_slice(x);
+        block_mix(block, x);
+    }
+    for _ in 0..n {
+        // Integerify, the first 64 bits of the last 64 byte block
+        let j = (x[len - 16] as u64 | (x[len - 15] as u64) << 32) & (n - 1);
+        let j = j as usize * len;
+        for (x, v) in x.iter_mut().zip(v[j..j + len].iter()) {
+            *x ^= v;
+        }
+        block_mix(x, &mut y);
+        x.copy_from_slice(&y);
+    }
+}
+
+// A password hash in the PHC string format, parsed with FromStr and
+// written with Display
+#[derive(Clone, Debug, PartialEq)]
+pub struct PasswordHash {
+    pub params: Params,
+    pub salt: Vec<u8>,
+    pub hash: Vec<u8>,
+}
+
+impl PasswordHash {
+    // HASH_LEN bytes of scrypt of the password. The salt should be fresh
+    // random bytes, 16 of them is plenty.
+    pub fn new(password: &[u8], salt: &[u8], params: Params) -> Result<PasswordHash, ScryptError> {
+        let hash = scrypt(password, salt, &params, HASH_LEN)?;
+        Ok(PasswordHash {
+            params,
+            salt: salt.to_vec(),
+            hash,
+        })
+    }
+
+    // whether the password matches, comparing in constant time. The
+    // parameters are still checked against their memory limit, so a
+    // stored hash can't make this allocate more than that.
+    pub fn verify(&self, password: &[u8]) -> Result<bool, ScryptError> {
+        let hash = scrypt(password, &self.salt, &self.params, self.hash.len())?;
+        Ok(crate::util::ct_eq(&hash, &self.hash))
+    }
+}
+
+impl fmt::Display for PasswordHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "$scrypt$ln={},r={},p={}${}${}",
+            self.params.log_n,
+            self.params.r,
+            self.params.p,
+            encoding::base64_unpadded(&self.salt),
+            encoding::base64_unpadded(&self.hash)
+        )
+    }
+}
+
+impl FromStr for PasswordHash {
+    type Err = ScryptError;
+
+    fn from_str(s: &str) -> Result<PasswordHash, ScryptError> {
+        let fields: Vec<&str> = s.split('$').collect();
+        if fields.len() != 5 || !fields[0].is_empty() || fields[1] != "scrypt" {
+            return Err(ScryptError::InvalidFormat);
+        }
+
+        // exactly ln, r and p in that order, as decimal numbers without
+        // leading zeros
+        let mut values = [0u32; 3];
+        let params: Vec<&str> = fields[2].split(',').collect();
+        if params.len() != 3 {
+            return Err(ScryptError::InvalidFormat);
+        }
+        for ((param, name), value) in params
+            .iter()
+            .zip(["ln", "r", "p"].iter())
+            .zip(values.iter_mut())
+        {
+            let digits = param
+                .strip_prefix(name)
+                .and_then(|param| param.strip_prefix('='))
+                .ok_or(ScryptError::InvalidFormat)?;
+            if digits.is_empty()
+                || (digits.len() > 1 && digits.starts_with('0'))
+                || !digits.bytes().all(|c| c.is_ascii_digit())
+            {
+                return Err(ScryptError::InvalidFormat);
+            }
+            *value = digits.parse().map_err(|_| ScryptError::InvalidFormat)?;
+        }
+        if values[0] > u8::MAX as u32 {
+            return Err(ScryptError::InvalidParams);
+        }
+        let params = Params::new(values[0] as u8, values[1], values[2])?;
+
+        let salt = encoding::decode_base64(fields[3]).map_err(|_| ScryptError::InvalidFormat)?;
+        let hash = encoding::decode_base64(fields[4]).map_err(|_| ScryptError::InvalidFormat)?;
+        if fields[3].ends_with('=') || fields[4].ends_with('=') || hash.is_empty() {
+            return Err(ScryptError::InvalidFormat);
+        }
+
+        Ok(PasswordHash { params, salt, hash })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::hex;
+
+    // RFC 7914 section 12, as (P, S, log2 N, r, p, DK)
+    const VECTORS: [(&str, &str, u8, u32, u32, &str); 4] = [
+        (
+            "",
+            "",
+            4,
+            1,
+            1,
+            "77d6576238657b203b19ca42c18a0497f16b4844e3074ae8dfdffa3fede21442\
+             fcd0069ded0948f8326a753a0fc81f17e8d3e0fb2e0d3628cf35e20c38d18906",
+        ),
+        (
+            "password",
+            "NaCl",
+            10,
+            8,
+            16,
+            "fdbabe1c9d3472007856e7190d01e9fe7c6ad7cbc8237830e77376634b373162\
+             2eaf30d92e22a3886ff109279d9830dac727afb94a83ee6d8360cbdfa2cc0640",
+        ),
+        (
+            "pleaseletmein",
+            "SodiumChloride",
+            14,
+            8,
+            1,
+            "7023bdcb3afd7348461c06cd81fd38ebfda8fbba904f8e3ea9b543f6545da1f2\
+             d5432955613f0fcf62d49705242a9af9e61e85dc0d651e40dfcf017b45575887",
+        ),
+        (
+            "pleaseletmein",
+            "SodiumChloride",
+            20,
+            8,
+            1,
+            "2101cb9b6a511aaeaddbbe09cf70f881ec568d574a2ffd4dabe5ee9820adaa47\
+             8e56fd8f4ba5d09ffa1c6d927c40f4c337304049e8a952fbcbf45c6fa77a41a4",
+        ),
+    ];
+
+    fn check(vectors: &[(&str, &str, u8, u32, u32, &str)]) {
+        for (password, salt, log_n, r, p, dk) in vectors.iter() {
+            let mut params = Params::new(*log_n, *r, *p).unwrap();
+            params.set_max_memory(usize::MAX);
+            let derived = scrypt(password.as_bytes(), salt.as_bytes(), &params, 64).unwrap();
+            assert_eq!(hex(&derived), *dk);
+        }
+    }
+
+    #[test]
+    fn test_scrypt_rfc7914() {
+        check(&VECTORS[..3]);
+    }
+
+    // the last vector takes 1 GiB and a while in a debug build, run with
+    // cargo test --release -- --ignored
+    #[test]
+    #[ignore]
+    fn test_scrypt_rfc7914_1gib() {
+        check(&VECTORS[3..]);
+    }
+
+    #[test]
+    fn test_scrypt_params() {
+        assert!(Params::new(1, 1, 1).is_ok());
+        assert!(Params::new(15, 1, 1).is_ok());
+        for (log_n, r, p) in [
+            (0, 1, 1),
+            (16, 1, 1),
+            (64, 8, 1),
+            (4, 0, 1),
+            (4, 1, 0),
+            (4, 1 << 15, 1 << 15),
+        ]
+        .iter()
+        {
+            assert_eq!(Params::new(*log_n, *r, *p), Err(ScryptError::InvalidParams));
+        }
+
+        // 128 * 8 * (2^17 + 1)
+        let mut params = Params::default();
+        assert_eq!(params.memory(), Some(128 * 8 * ((1 << 17) + 1)));
+        params.set_max_memory(1 << 20);
+        assert_eq!(
+            scrypt(b"password", b"salt", &params, 32),
+            Err(ScryptError::MemoryLimit { maximum: 1 << 20 })
+        );
+    }
+
+    #[test]
+    fn test_scrypt_phc() {
+        let params = Params::new(10, 8, 1).unwrap();
+        let hash = PasswordHash::new(b"password", b"saltsaltsaltsalt", params).unwrap();
+        let phc = hash.to_string();
+        assert!(phc.starts_with("$scrypt$ln=10,r=8,p=1$c2FsdHNhbHRzYWx0c2FsdA$"));
+        assert_eq!(phc.parse::<PasswordHash>(), Ok(hash.clone()));
+        assert_eq!(hash.verify(b"password"), Ok(true));
+        assert_eq!(hash.verify(b"passwore"), Ok(false));
+
+        // the RFC's first vector as a PHC string
+        let phc: PasswordHash =
+            "$scrypt$ln=4,r=1,p=1$$d9ZXYjhleyA7GcpCwYoEl/FrSETjB0ro39/6P+3iFEL8\
+                                 0Aad7QlI+DJqdToPyB8X6NPg+y4NNijPNeIMONGJBg"
+                .parse()
+                .unwrap();
+        assert_eq!(phc.verify(b""), Ok(true));
+
+        // a stored hash asking for more memory than allowed
+        let phc: PasswordHash = "$scrypt$ln=30,r=8,p=1$c2FsdA$AAAA".parse().unwrap();
+        assert_eq!(
+            phc.verify(b""),
+            Err(ScryptError::MemoryLimit {
+                maximum: DEFAULT_MAX_MEMORY
+            })
+        );
+
+        for s in [
+            "",
+            "$scrypt$ln=4,r=1,p=1$c2FsdA",
+            "$argon2id$ln=4,r=1,p=1$c2FsdA$AAAA",
+            "$scrypt$r=1,ln=4,p=1$c2FsdA$AAAA",
+            "$scrypt$ln=4,r=1$c2FsdA$AAAA",
+            "$scrypt$ln=04,r=1,p=1$c2FsdA$AAAA",
+            "$scrypt$ln=4,r=1,p=1$c2FsdA==$AAAA",
+            "$scrypt$ln=4,r=1,p=1$c2FsdA$",
+            "$scrypt$ln=4,r=1,p=1$c2F*dA$AAAA",
+        ]
+        .iter()
+        {
+            assert_eq!(
+                s.parse::<PasswordHash>(),
+                Err(ScryptError::InvalidFormat),
+                "{}",
+                s
+            );
+        }
+        assert_eq!(
+            "$scrypt$ln=0,r=1,p=1$c2FsdA$AAAA".parse::<PasswordHash>(),
+            Err(ScryptError::InvalidParams)
+        );
+    }
+}
@@ -0,0 +1,80 @@
+/*
+
+SHA-224, which is SHA-256 with its own initial hash value (see 5.3.2) and
+the digest truncated to 224 bits / the first 7 of the 8 32-bit words.
+See https://nvlpubs.nist.gov/nistpubs/FIPS/NIST.FIPS.180-4.pdf
+and crate::truncated.
+
+*/
+
+truncated!(
+    Sha224,
+    Sha256,
+    sha256,
+    u32,
+    Sha224Digest,
+    Sha256Digest,
+    [
+        0xc1059ed8, 0x367cd507, 0x3070dd17, 0xf70e5939, 0xffc00b31, 0x68581511, 0x64f98fa7,
+        0xbefa4fa4
+    ]
+);
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_sha224_hash() {
+        let message_hashes = [
+            (
+                "abc",
+                "23097d223405d8228642a477bda255b32aadbce4bda0b3f7e36c9da7",
+            ),
+            (
+                "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+                "75388b16512776cc5dba5da1fd890150b0c6455cb4f58b1952522525",
+            ),
+        ];
+
+        for (msg, hash) in message_hashes.iter() {
+            assert_eq!(super::hash(msg.as_bytes()), Some(hash.parse().unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_sha224_streaming() {
+        let msg: Vec<u8> = (0..300u32).map(|i| (i * 7 % 251) as u8).collect();
+
+        for len in 1..msg.len() {
+            let mut hasher = super::Sha224::new();
+            for chunk in msg[..len].chunks(100) {
+                hasher.update(chunk);
+            }
+            assert_eq!(Some(hasher.finalize()), super::hash(&msg[..len]));
+        }
+
+        assert_eq!(
+            super::Sha224::new().finalize().to_hex(),
+            "d14a028c2a3a2bc9476102bb288234c415a2b01f828ea62ac5b3e42f"
+        );
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_sha224_serde() {
+        let msg: Vec<u8> = (0..100u8).collect();
+        let mut hasher = super::Sha224::new();
+        hasher.update(&msg[..70]);
+        let json = serde_json::to_string(&hasher).unwrap();
+
+        let mut resumed: super::Sha224 = serde_json::from_str(&json).unwrap();
+        resumed.update(&msg[70..]);
+        assert_eq!(Some(resumed.finalize()), super::hash(&msg));
+
+        // the states of SHA-224 and SHA-256 have the same layout, but one
+        // must not be resumed as the other
+        assert!(serde_json::from_str::<crate::sha256::Sha256>(&json).is_err());
+        let mut sha256 = crate::sha256::Sha256::new();
+        sha256.update(&msg[..70]);
+        let json = serde_json::to_string(&sha256).unwrap();
+        assert!(serde_json::from_str::<super::Sha224>(&json).is_err());
+    }
+}
@@ -1,8 +1,11 @@
+use crate::digest::Sha256Digest;
+use crate::observer::{NoObserver, Observer};
 use crate::util::*;
 
 /*
 
-This is the SHA-256 implementation.
+This is the SHA-256 implementation, which SHA-224 is built on, see
+crate::truncated.
 See https://nvlpubs.nist.gov/nistpubs/FIPS/NIST.FIPS.180-4.pdf
 for implementation details.
 
@@ -30,7 +33,6 @@ The algorithm consists of two main stages:
 // msg should be a multiple of 512 bits
 // pad with 1 then 0s up to msg.len % 512 - 64 - 1
 fn pad_message(msg: &[u8]) -> Vec<u8> {
-    let num_blocks = (msg.len() * 8 + 64 + 1) / 512;
     let min_msg_bits = msg.len() * 8 % 512 + 1;
     let mut num_zero_bits = 0;
 
@@ -40,11 +42,7 @@ fn pad_message(msg: &[u8]) -> Vec<u8> {
             num_zero_bits = 448 - (msg.len() * 8 % 512 + 1);
         }
         Ordering::Greater => {
-            if num_blocks > 1 {
-                num_zero_bits = 512 - ((msg.len() * 8 + 1 + 64) % 512) + 512 * num_blocks;
-            } else {
-                num_zero_bits = 512 - ((msg.len() * 8 + 1 + 64) % 512);
-            }
+            num_zero_bits = 512 - ((msg.len() * 8 + 1 + 64) % 512);
         }
         Ordering::Equal => {}
     }
@@ -66,7 +64,14 @@ fn pad_message(msg: &[u8]) -> Vec<u8> {
         // TODO: not sure how to handle this
     }
     buffer.put_u64(length_64);
-    buffer.to_vec()
+    let padded_message = buffer.to_vec();
+
+    // the copy in the BytesMut has served its purpose, don't leave the
+    // message behind in freed memory
+    #[cfg(feature = "zeroize")]
+    zeroize::Zeroize::zeroize(&mut buffer[..]);
+
+    padded_message
 }
 
 fn ch(x: u32, y: u32, z: u32) -> u32 {
@@ -92,13 +97,107 @@ fn b_sigma0_256(word: u32) -> u32 {
     rotr32(2, word) ^ rotr32(13, word) ^ rotr32(22, word)
 }
 
-pub fn hash(msg: &[u8]) -> Option<[u32; 8]> {
+// the hash computation for a single 512 bit block, see 6.2.2 / 6.4.2.
+// msg_schedule is the caller's space for W, which with the zeroize
+// feature is wiped before returning along with the working variables.
+fn compress<O: Observer<u32>>(
+    hashes: &mut [u32; 8],
+    block: &[u8],
+    msg_schedule: &mut [u32; 64],
+    observer: &mut O,
+) {
+    use byteorder::{BigEndian, ByteOrder};
+
+    observer.block(block, hashes);
+
+    // build message schedule
+    for (t, word) in block.chunks(4).take(16).enumerate() {
+        msg_schedule[t] = BigEndian::read_u32(word);
+    }
+    for t in 16..64 {
+        msg_schedule[t] = s_sigma1_256(msg_schedule[t - 2])
+            .wrapping_add(msg_schedule[t - 7])
+            .wrapping_add(s_sigma0_256(msg_schedule[t - 15]))
+            .wrapping_add(msg_schedule[t - 16]);
+    }
+
+    observer.message_schedule(msg_schedule);
+
+    let mut a = hashes[0];
+    let mut b = hashes[1];
+    let mut c = hashes[2];
+    let mut d = hashes[3];
+    let mut e = hashes[4];
+    let mut f = hashes[5];
+    let mut g = hashes[6];
+    let mut h = hashes[7];
+
+    for t in 0..64 {
+        let t1 = h
+            .wrapping_add(b_sigma1_256(e))
+            .wrapping_add(ch(e, f, g))
+            .wrapping_add(SHA_256[t])
+            .wrapping_add(msg_schedule[t]);
+        let t2 = b_sigma0_256(a).wrapping_add(maj(a, b, c));
+
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+        observer.round(t, &[a, b, c, d, e, f, g, h]);
+    }
+
+    hashes[0] = hashes[0].wrapping_add(a);
+    hashes[1] = hashes[1].wrapping_add(b);
+    hashes[2] = hashes[2].wrapping_add(c);
+    hashes[3] = hashes[3].wrapping_add(d);
+    hashes[4] = hashes[4].wrapping_add(e);
+    hashes[5] = hashes[5].wrapping_add(f);
+    hashes[6] = hashes[6].wrapping_add(g);
+    hashes[7] = hashes[7].wrapping_add(h);
+
+    observer.block_done(hashes);
+
+    #[cfg(feature = "zeroize")]
+    {
+        use zeroize::Zeroize;
+        msg_schedule.zeroize();
+        a.zeroize();
+        b.zeroize();
+        c.zeroize();
+        d.zeroize();
+        e.zeroize();
+        f.zeroize();
+        g.zeroize();
+        h.zeroize();
+    }
+}
+
+pub fn hash(msg: &[u8]) -> Option<Sha256Digest> {
+    hash_observed(msg, &mut NoObserver)
+}
+
+// hash, calling back into the observer during the hash computation, see
+// crate::observer
+pub fn hash_observed<O: Observer<u32>>(msg: &[u8], observer: &mut O) -> Option<Sha256Digest> {
+    hash_from(SHA_256_INIT, msg, observer)
+}
+
+// the hash computation starting from the given initial hash value, which
+// together with truncating the result is all that distinguishes SHA-224
+pub(crate) fn hash_from<O: Observer<u32>>(
+    init: [u32; 8],
+    msg: &[u8],
+    observer: &mut O,
+) -> Option<Sha256Digest> {
     if msg.is_empty() {
         None
     } else {
         let padded_message = pad_message(msg);
-        // println!("Padded message: {:#x?}", padded_message);
-        // println!("Length of padded message: {} bytes", padded_message.len());
 
         // we only take n * 512 bit messages
         assert_eq!((padded_message.len() * 8) % 512, 0);
@@ -106,85 +205,279 @@ pub fn hash(msg: &[u8]) -> Option<[u32; 8]> {
         // parse into 512 bit blocks (64 bytes), using 32 bit words (4 bytes)
         // see 6.4.1 and 6.4.2 on p24 of
         // https://nvlpubs.nist.gov/nistpubs/FIPS/NIST.FIPS.180-4.pdf
-        use byteorder::{BigEndian, ByteOrder};
+        let mut hashes = init;
+        let mut msg_schedule = [0u32; 64];
+        for block in padded_message.chunks(64) {
+            compress(&mut hashes, block, &mut msg_schedule, observer);
+        }
 
-        let mut hashes = SHA_256_INIT;
-        //println!("Initial hashes: {:#x?}", hashes);
-        for (_i, block) in padded_message.chunks(64).enumerate() {
-            let mut t = 0;
-            let mut msg_schedule: [u32; 64] = [0u32; 64];
+        let digest = Sha256Digest::from_words(hashes);
 
-            // build message schedule
-            for word in block.chunks(4) {
-                if t < 16 {
-                    msg_schedule[t] = BigEndian::read_u32(word);
-                }
-                t += 1;
-            }
-            for t in 16..64 {
-                msg_schedule[t] = s_sigma1_256(msg_schedule[t - 2])
-                    .wrapping_add(msg_schedule[t - 7])
-                    .wrapping_add(s_sigma0_256(msg_schedule[t - 15]))
-                    .wrapping_add(msg_schedule[t - 16]);
-            }
+        #[cfg(feature = "zeroize")]
+        {
+            use zeroize::Zeroize;
+            let mut padded_message = padded_message;
+            padded_message.zeroize();
+            hashes.zeroize();
+        }
 
-            /*
-            println!("Message schedule for block: {}", i);
-            for m in msg_schedule.iter() {
-                print!("{:#x?} ", m);
-            }
-            println!("");
-            */
-
-            let mut a = hashes[0];
-            let mut b = hashes[1];
-            let mut c = hashes[2];
-            let mut d = hashes[3];
-            let mut e = hashes[4];
-            let mut f = hashes[5];
-            let mut g = hashes[6];
-            let mut h = hashes[7];
-
-            for t in 0..64 {
-                //print!("t={}: ", t);
-                let t1 = h
-                    .wrapping_add(b_sigma1_256(e))
-                    .wrapping_add(ch(e, f, g))
-                    .wrapping_add(SHA_256[t])
-                    .wrapping_add(msg_schedule[t]);
-                let t2 = b_sigma0_256(a).wrapping_add(maj(a, b, c));
-
-                h = g;
-                g = f;
-                f = e;
-                e = d.wrapping_add(t1);
-                d = c;
-                c = b;
-                b = a;
-                a = t1.wrapping_add(t2);
-                /*
-                print!(
-                    "A: {:#x?} B: {:#x?} C: {:#x?} D: {:#x?} E: {:#x?} F: {:#x?} G: {:#x?} H: {:#x?}",
-                    a, b, c, d, e, f, g, h
-                );
-                println!("");
-                */
+        Some(digest)
+    }
+}
+
+// Streaming SHA-256, for messages which arrive in pieces or are too large
+// to hold in memory. Whole 512 bit blocks are compressed as soon as they
+// have been buffered, and the padding is only applied to the final partial
+// block when the hash is finalized.
+#[derive(Clone)]
+pub struct Sha256 {
+    hashes: [u32; 8],
+    block: [u8; 64],
+    block_len: usize,
+    // length of the message so far, in bytes
+    msg_len: u64,
+}
+
+impl Sha256 {
+    pub fn new() -> Sha256 {
+        Sha256::with_init(SHA_256_INIT)
+    }
+
+    pub(crate) fn with_init(init: [u32; 8]) -> Sha256 {
+        Sha256 {
+            hashes: init,
+            block: [0u8; 64],
+            block_len: 0,
+            msg_len: 0,
+        }
+    }
+
+    pub fn update(&mut self, mut msg: &[u8]) {
+        self.msg_len = self.msg_len.wrapping_add(msg.len() as u64);
+
+        while !msg.is_empty() {
+            let n = std::cmp::min(64 - self.block_len, msg.len());
+            self.block[self.block_len..self.block_len + n].copy_from_slice(&msg[..n]);
+            self.block_len += n;
+            msg = &msg[n..];
+
+            if self.block_len == 64 {
+                compress(&mut self.hashes, &self.block, &mut [0; 64], &mut NoObserver);
+                self.block_len = 0;
             }
+        }
+    }
+
+    pub fn finalize(mut self) -> Sha256Digest {
+        self.finish()
+    }
+
+    // finalize, leaving the hasher wiped with the zeroize feature, the
+    // block having held the end of the message and the padding
+    fn finish(&mut self) -> Sha256Digest {
+        use byteorder::{BigEndian, ByteOrder};
+
+        // a single 1 bit, then 0s up to the 64 bit message length,
+        // spilling into an extra block if there is no room left in this one
+        let msg_bits = self.msg_len.wrapping_mul(8);
+        self.block[self.block_len] = 0x80;
+        for byte in self.block[self.block_len + 1..].iter_mut() {
+            *byte = 0;
+        }
+        if self.block_len >= 56 {
+            compress(&mut self.hashes, &self.block, &mut [0; 64], &mut NoObserver);
+            self.block = [0u8; 64];
+        }
+        BigEndian::write_u64(&mut self.block[56..], msg_bits);
+        compress(&mut self.hashes, &self.block, &mut [0; 64], &mut NoObserver);
+
+        let digest = Sha256Digest::from_words(self.hashes);
+        #[cfg(feature = "zeroize")]
+        zeroize::Zeroize::zeroize(self);
+        digest
+    }
 
-            hashes[0] = hashes[0].wrapping_add(a);
-            hashes[1] = hashes[1].wrapping_add(b);
-            hashes[2] = hashes[2].wrapping_add(c);
-            hashes[3] = hashes[3].wrapping_add(d);
-            hashes[4] = hashes[4].wrapping_add(e);
-            hashes[5] = hashes[5].wrapping_add(f);
-            hashes[6] = hashes[6].wrapping_add(g);
-            hashes[7] = hashes[7].wrapping_add(h);
+    // with the zeroize feature, the old state is wiped when it is dropped
+    // by the assignment
+    pub fn reset(&mut self) {
+        *self = Sha256::new();
+    }
+}
+
+// wipe the chaining state, buffered message bytes and length, for when
+// the message is a secret such as a key
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for Sha256 {
+    fn zeroize(&mut self) {
+        self.hashes.zeroize();
+        self.block.zeroize();
+        self.block_len.zeroize();
+        self.msg_len.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Sha256 {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(self);
+    }
+}
+
+// The serialized form of a streaming hasher, so that hashing a long
+// message can be checkpointed and picked up again later. Only the bytes
+// actually buffered are kept, and the version is bumped whenever the
+// layout changes. The initial hash value tells apart the algorithms
+// built on this one, see crate::truncated, so that a state can only be
+// resumed by the algorithm which saved it.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Sha256State {
+    version: u32,
+    init: [u32; 8],
+    hashes: [u32; 8],
+    msg_len: u64,
+    block: Vec<u8>,
+}
+
+// the buffered bytes are part of the message
+#[cfg(all(feature = "serde", feature = "zeroize"))]
+impl Drop for Sha256State {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.block);
+        zeroize::Zeroize::zeroize(&mut self.hashes);
+    }
+}
+
+#[cfg(feature = "serde")]
+const STATE_VERSION: u32 = 2;
+
+#[cfg(feature = "serde")]
+impl Sha256 {
+    pub(crate) fn serialize_with_init<S: serde::Serializer>(
+        &self,
+        init: [u32; 8],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(
+            &Sha256State {
+                version: STATE_VERSION,
+                init,
+                hashes: self.hashes,
+                msg_len: self.msg_len,
+                block: self.block[..self.block_len].to_vec(),
+            },
+            serializer,
+        )
+    }
+
+    pub(crate) fn deserialize_with_init<'de, D: serde::Deserializer<'de>>(
+        init: [u32; 8],
+        deserializer: D,
+    ) -> Result<Sha256, D::Error> {
+        use serde::de::Error;
+
+        let state = <Sha256State as serde::Deserialize>::deserialize(deserializer)?;
+        if state.version != STATE_VERSION {
+            return Err(D::Error::custom(format!(
+                "unsupported sha256 state version {}",
+                state.version
+            )));
+        }
+        if state.init != init {
+            return Err(D::Error::custom(
+                "the sha256 state is of a different algorithm",
+            ));
         }
+        // the buffered bytes are always the tail of the message, and never
+        // a whole block
+        if state.block.len() >= 64 || state.msg_len % 64 != state.block.len() as u64 {
+            return Err(D::Error::custom("inconsistent sha256 state"));
+        }
+
+        let mut hasher = Sha256::with_init(init);
+        hasher.hashes = state.hashes;
+        hasher.msg_len = state.msg_len;
+        hasher.block[..state.block.len()].copy_from_slice(&state.block);
+        hasher.block_len = state.block.len();
+        Ok(hasher)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Sha256 {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.serialize_with_init(SHA_256_INIT, serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Sha256 {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Sha256, D::Error> {
+        Sha256::deserialize_with_init(SHA_256_INIT, deserializer)
+    }
+}
 
-        Some(hashes)
+impl Default for Sha256 {
+    fn default() -> Sha256 {
+        Sha256::new()
     }
 }
 
+impl crate::StreamHasher for Sha256 {
+    type Output = Sha256Digest;
+
+    fn update(&mut self, msg: &[u8]) {
+        Sha256::update(self, msg)
+    }
+
+    fn finalize(self) -> Sha256Digest {
+        Sha256::finalize(self)
+    }
+}
+
+// hash a file, memory-mapping it where possible, see crate::file
+pub fn hash_file<P: AsRef<std::path::Path>>(
+    path: P,
+) -> Result<Sha256Digest, crate::file::FileError> {
+    crate::file::hash_file(path.as_ref(), Sha256::new())
+}
+
+// hash each of the messages on the rayon thread pool, the hashes are
+// returned in the same order as the messages
+#[cfg(feature = "rayon")]
+pub fn hash_batch<T: AsRef<[u8]> + Sync>(msgs: &[T]) -> Vec<Option<Sha256Digest>> {
+    use rayon::prelude::*;
+    msgs.par_iter().map(|msg| hash(msg.as_ref())).collect()
+}
+
+// hash each of the files on the rayon thread pool, the results are
+// returned in the same order as the paths
+#[cfg(feature = "rayon")]
+pub fn hash_files<P: AsRef<std::path::Path> + Sync>(
+    paths: &[P],
+) -> Vec<Result<Sha256Digest, crate::file::FileError>> {
+    use rayon::prelude::*;
+    paths.par_iter().map(hash_file).collect()
+}
+
+// the initial hash value consists of the following eight 64-bit words (i.e. 512 bits)
+const SHA_256_INIT: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+// SHA-384, SHA-512, SHA-512/224 and SHA-512/256 use the same sequence of
+// eighty constant 64-bit words
+const SHA_256: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -219,30 +512,208 @@ mod tests {
 
         // TODO: why do I need to call super::hash here?
         for (msg, hash) in message_hashes.iter() {
-            let test_hashes = super::hash(&msg.as_bytes());
+            let test_hashes = super::hash(msg.as_bytes());
 
             for (i, test_hash) in test_hashes.iter().enumerate() {
                 println!("Test {}", i);
-                assert_eq!(hash[i], test_hash[i]);
+                assert_eq!(hash[i], test_hash.words()[i]);
             }
         }
     }
-}
 
-// the initial hash value consists of the following eight 64-bit words (i.e. 512 bits)
-const SHA_256_INIT: [u32; 8] = [
-    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
-];
+    #[test]
+    fn test_sha256_padding_spills_into_extra_block() {
+        // messages which leave too little room in their last block for the
+        // 1 bit and the length, and so need one more block of padding
+        let message_hashes: [(usize, [u32; 8]); 2] = [
+            (
+                120,
+                [
+                    0x2f3d3354, 0x32c70b58, 0x0af0e8e1, 0xb3674a7c, 0x020d683a, 0xa5f73aaa,
+                    0xedfdc55a, 0xf904c21c,
+                ],
+            ),
+            (
+                127,
+                [
+                    0xc57e9278, 0xaf78fa3c, 0xab38667b, 0xef4ce29d, 0x783787a2, 0xf731d4e1,
+                    0x2200270f, 0x0c32320a,
+                ],
+            ),
+        ];
+
+        for (len, hash) in message_hashes.iter() {
+            let msg = vec![b'a'; *len];
+            assert_eq!(super::hash(&msg), Some((*hash).into()));
+        }
+    }
 
-// SHA-384, SHA-512, SHA-512/224 and SHA-512/256 use the same sequence of
-// eighty constant 64-bit words
-const SHA_256: [u32; 64] = [
-    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
-    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
-    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
-    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
-    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
-    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
-    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
-    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
-];
+    #[test]
+    fn test_sha256_streaming() {
+        let msg: Vec<u8> = (0..1000u32).map(|i| (i * 7 % 251) as u8).collect();
+
+        for len in 1..msg.len() {
+            for chunk_size in [1, 3, 64, 100, 129].iter() {
+                let mut hasher = super::Sha256::new();
+                for chunk in msg[..len].chunks(*chunk_size) {
+                    hasher.update(chunk);
+                }
+                assert_eq!(Some(hasher.finalize()), super::hash(&msg[..len]));
+            }
+        }
+    }
+
+    #[test]
+    fn test_sha256_streaming_empty() {
+        // unlike hash, the streaming interface accepts an empty message
+        assert_eq!(
+            super::Sha256::new().finalize().words(),
+            [
+                0xe3b0c442, 0x98fc1c14, 0x9afbf4c8, 0x996fb924, 0x27ae41e4, 0x649b934c, 0xa495991b,
+                0x7852b855
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sha256_streaming_reset() {
+        let mut hasher = super::Sha256::new();
+        hasher.update(b"abc");
+        let abc = hasher.clone().finalize();
+
+        hasher.update(b"def");
+        hasher.reset();
+        hasher.update(b"abc");
+        assert_eq!(hasher.finalize(), abc);
+    }
+
+    #[test]
+    fn test_sha256_hash_file() {
+        let dir = std::env::temp_dir().join(format!("sha256_hash_file_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        for len in [0, 1, 1000, 300_000].iter() {
+            let msg: Vec<u8> = (0..*len as u32).map(|i| (i % 241) as u8).collect();
+            let path = dir.join(format!("file_{}", len));
+            std::fs::write(&path, &msg).unwrap();
+
+            let mut hasher = super::Sha256::new();
+            hasher.update(&msg);
+            assert_eq!(super::hash_file(&path).unwrap(), hasher.finalize());
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_sha256_hash_batch() {
+        let msgs: Vec<Vec<u8>> = (1..100u8).map(|i| vec![i; i as usize * 3]).collect();
+        let hashes = super::hash_batch(&msgs);
+
+        assert_eq!(hashes.len(), msgs.len());
+        for (msg, hash) in msgs.iter().zip(hashes.iter()) {
+            assert_eq!(*hash, super::hash(msg));
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_sha256_hash_files() {
+        let dir = std::env::temp_dir().join(format!("sha256_hash_files_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut paths = Vec::new();
+        for i in 1..20u8 {
+            let path = dir.join(format!("file_{}", i));
+            std::fs::write(&path, vec![i; i as usize * 50]).unwrap();
+            paths.push(path);
+        }
+        paths.push(dir.join("missing"));
+
+        let hashes = super::hash_files(&paths);
+        assert_eq!(hashes.len(), paths.len());
+        for (i, hash) in hashes.iter().take(19).enumerate() {
+            let msg = vec![i as u8 + 1; (i + 1) * 50];
+            assert_eq!(Some(*hash.as_ref().unwrap()), super::hash(&msg));
+        }
+        assert!(hashes[19].is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn test_sha256_zeroize() {
+        use zeroize::Zeroize;
+
+        let secret = [0x5a; 200];
+
+        let mut hasher = super::Sha256::new();
+        hasher.update(&secret);
+        hasher.zeroize();
+        assert_eq!(hasher.hashes, [0; 8]);
+        assert!(hasher.block.iter().all(|b| *b == 0));
+        assert_eq!((hasher.block_len, hasher.msg_len), (0, 0));
+
+        let mut hasher = super::Sha256::new();
+        hasher.update(&secret);
+        hasher.reset();
+        assert_eq!(hasher.hashes, super::SHA_256_INIT);
+        assert!(hasher.block.iter().all(|b| *b == 0));
+        assert_eq!((hasher.block_len, hasher.msg_len), (0, 0));
+
+        // finalizing wipes the block, which had the end of the message and
+        // then the padding in it
+        let mut hasher = super::Sha256::new();
+        hasher.update(&secret);
+        assert!(hasher.block.contains(&0x5a));
+        assert_eq!(Some(hasher.finish()), super::hash(&secret));
+        assert_eq!(hasher.hashes, [0; 8]);
+        assert!(hasher.block.iter().all(|b| *b == 0));
+        assert_eq!((hasher.block_len, hasher.msg_len), (0, 0));
+
+        // and compressing a block wipes W, every word of which it sets
+        let mut hashes = super::SHA_256_INIT;
+        let mut msg_schedule = [1; 64];
+        super::compress(
+            &mut hashes,
+            &secret[..64],
+            &mut msg_schedule,
+            &mut crate::observer::NoObserver,
+        );
+        assert_ne!(hashes, super::SHA_256_INIT);
+        assert_eq!(msg_schedule, [0; 64]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_sha256_serde_resume() {
+        let msg: Vec<u8> = (0..1000u32).map(|i| (i * 13 % 256) as u8).collect();
+
+        for split in [0, 1, 64 - 1, 64, 500].iter() {
+            let mut hasher = super::Sha256::new();
+            hasher.update(&msg[..*split]);
+
+            let json = serde_json::to_string(&hasher).unwrap();
+            let mut resumed: super::Sha256 = serde_json::from_str(&json).unwrap();
+            resumed.update(&msg[*split..]);
+            assert_eq!(Some(resumed.finalize()), super::hash(&msg));
+
+            let bytes = bincode::serialize(&hasher).unwrap();
+            let mut resumed: super::Sha256 = bincode::deserialize(&bytes).unwrap();
+            resumed.update(&msg[*split..]);
+            assert_eq!(Some(resumed.finalize()), super::hash(&msg));
+        }
+
+        let mut hasher = super::Sha256::new();
+        hasher.update(b"abc");
+        let json = serde_json::to_string(&hasher).unwrap();
+        assert!(json.starts_with("{\"version\":2,\"init\":["));
+
+        let bad_version = json.replace("\"version\":2", "\"version\":1");
+        assert!(serde_json::from_str::<super::Sha256>(&bad_version).is_err());
+        let bad_length = json.replace("\"msg_len\":3", "\"msg_len\":4");
+        assert!(serde_json::from_str::<super::Sha256>(&bad_length).is_err());
+    }
+}
@@ -0,0 +1,88 @@
+/*
+
+SHA-384, which is SHA-512 with its own initial hash value (see 5.3.4) and
+the digest truncated to 384 bits / the first 6 of the 8 64-bit words.
+See https://nvlpubs.nist.gov/nistpubs/FIPS/NIST.FIPS.180-4.pdf
+and crate::truncated.
+
+*/
+
+truncated!(
+    Sha384,
+    Sha512,
+    sha512,
+    u64,
+    Sha384Digest,
+    Sha512Digest,
+    [
+        0xcbbb9d5dc1059ed8,
+        0x629a292a367cd507,
+        0x9159015a3070dd17,
+        0x152fecd8f70e5939,
+        0x67332667ffc00b31,
+        0x8eb44a8768581511,
+        0xdb0c2e0d64f98fa7,
+        0x47b5481dbefa4fa4
+    ]
+);
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_sha384_hash() {
+        let message_hashes = [
+            (
+                "abc",
+                "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded1631a8b605a43ff5bed8086072ba1e7cc2358baeca134c825a7",
+            ),
+            (
+                "abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu",
+                "09330c33f71147e83d192fc782cd1b4753111b173b3b05d22fa08086e3b0f712fcc7c71a557e2db966c3e9fa91746039",
+            ),
+        ];
+
+        for (msg, hash) in message_hashes.iter() {
+            assert_eq!(super::hash(msg.as_bytes()), Some(hash.parse().unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_sha384_streaming() {
+        let msg: Vec<u8> = (0..300u32).map(|i| (i * 7 % 251) as u8).collect();
+
+        for len in 1..msg.len() {
+            let mut hasher = super::Sha384::new();
+            for chunk in msg[..len].chunks(100) {
+                hasher.update(chunk);
+            }
+            assert_eq!(Some(hasher.finalize()), super::hash(&msg[..len]));
+        }
+
+        assert_eq!(
+            super::Sha384::new().finalize().to_hex(),
+            "38b060a751ac96384cd9327eb1b1e36a21fdb71114be07434c0cc7bf63f6e1da274edebfe76f65fbd51ad2f14898b95b"
+        );
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_sha384_serde() {
+        let msg: Vec<u8> = (0..200u8).collect();
+        let mut hasher = super::Sha384::new();
+        hasher.update(&msg[..150]);
+        let json = serde_json::to_string(&hasher).unwrap();
+
+        let mut resumed: super::Sha384 = serde_json::from_str(&json).unwrap();
+        resumed.update(&msg[150..]);
+        assert_eq!(Some(resumed.finalize()), super::hash(&msg));
+
+        // the states of everything built on SHA-512 have the same layout,
+        // but are only resumed by the algorithm which saved them
+        assert!(serde_json::from_str::<crate::sha512::Sha512>(&json).is_err());
+        assert!(serde_json::from_str::<crate::sha512_256::Sha512_256>(&json).is_err());
+        let mut sha512 = crate::sha512::Sha512::new();
+        sha512.update(&msg[..150]);
+        let json = serde_json::to_string(&sha512).unwrap();
+        assert!(serde_json::from_str::<super::Sha384>(&json).is_err());
+        assert!(serde_json::from_str::<crate::sha512_224::Sha512_224>(&json).is_err());
+    }
+}
@@ -1,7 +1,12 @@
+use crate::digest::Sha512Digest;
+use crate::observer::{NoObserver, Observer};
+use crate::util::*;
+
 /*
 
-This implementation only works for SHA-512 currently. Other algorithms
-may be added later on. See https://nvlpubs.nist.gov/nistpubs/FIPS/NIST.FIPS.180-4.pdf
+This is the SHA-512 implementation, which SHA-384, SHA-512/224 and
+SHA-512/256 are built on, see crate::truncated.
+See https://nvlpubs.nist.gov/nistpubs/FIPS/NIST.FIPS.180-4.pdf
 for implementation details.
 
 The algorithm consists of two main stages:
@@ -28,7 +33,6 @@ The algorithm consists of two main stages:
 // msg should be a multiple of 1024 bits
 // pad with 1 then 0s up to msg.len % 1024 - 128 - 1
 fn pad_message(msg: &[u8]) -> Vec<u8> {
-    let num_blocks = (msg.len() * 8 + 128 + 1) / 1024;
     let min_msg_bits = msg.len() * 8 % 1024 + 1;
     let mut num_zero_bits = 0;
 
@@ -38,11 +42,7 @@ fn pad_message(msg: &[u8]) -> Vec<u8> {
             num_zero_bits = 896 - (msg.len() * 8 % 1024 + 1);
         }
         Ordering::Greater => {
-            if num_blocks > 1 {
-                num_zero_bits = 1024 - ((msg.len() * 8 + 1 + 128) % 1024) + 1024 * num_blocks;
-            } else {
-                num_zero_bits = 1024 - ((msg.len() * 8 + 1 + 128) % 1024);
-            }
+            num_zero_bits = 1024 - ((msg.len() * 8 + 1 + 128) % 1024);
         }
         Ordering::Equal => {}
     }
@@ -64,19 +64,14 @@ fn pad_message(msg: &[u8]) -> Vec<u8> {
         // TODO: not sure how to handle this
     }
     buffer.put_u128(length_128);
-    buffer.to_vec()
-}
+    let padded_message = buffer.to_vec();
 
-// Functions to be used during the hash computation
-#[allow(dead_code)]
-fn rotl(n: u64, x: u64) -> u64 {
-    (x << n) | (x >> (64 - n))
-}
-fn rotr(n: u64, x: u64) -> u64 {
-    (x >> n) | (x << (64 - n))
-}
-fn shr(n: u64, x: u64) -> u64 {
-    x >> n
+    // the copy in the BytesMut has served its purpose, don't leave the
+    // message behind in freed memory
+    #[cfg(feature = "zeroize")]
+    zeroize::Zeroize::zeroize(&mut buffer[..]);
+
+    padded_message
 }
 
 fn ch(x: u64, y: u64, z: u64) -> u64 {
@@ -87,28 +82,123 @@ fn maj(x: u64, y: u64, z: u64) -> u64 {
 }
 
 fn s_sigma1_512(word: u64) -> u64 {
-    rotr(19, word) ^ rotr(61, word) ^ shr(6, word)
+    rotr64(19, word) ^ rotr64(61, word) ^ shr64(6, word)
 }
 
 fn s_sigma0_512(word: u64) -> u64 {
-    rotr(1, word) ^ rotr(8, word) ^ shr(7, word)
+    rotr64(1, word) ^ rotr64(8, word) ^ shr64(7, word)
 }
 
 fn b_sigma1_512(word: u64) -> u64 {
-    rotr(14, word) ^ rotr(18, word) ^ rotr(41, word)
+    rotr64(14, word) ^ rotr64(18, word) ^ rotr64(41, word)
 }
 
 fn b_sigma0_512(word: u64) -> u64 {
-    rotr(28, word) ^ rotr(34, word) ^ rotr(39, word)
+    rotr64(28, word) ^ rotr64(34, word) ^ rotr64(39, word)
+}
+
+// the hash computation for a single 1024 bit block, see 6.2.2 / 6.4.2.
+// msg_schedule is the caller's space for W, which with the zeroize
+// feature is wiped before returning along with the working variables.
+fn compress<O: Observer<u64>>(
+    hashes: &mut [u64; 8],
+    block: &[u8],
+    msg_schedule: &mut [u64; 80],
+    observer: &mut O,
+) {
+    use byteorder::{BigEndian, ByteOrder};
+
+    observer.block(block, hashes);
+
+    // build message schedule
+    for (t, word) in block.chunks(8).take(16).enumerate() {
+        msg_schedule[t] = BigEndian::read_u64(word);
+    }
+    for t in 16..80 {
+        msg_schedule[t] = s_sigma1_512(msg_schedule[t - 2])
+            .wrapping_add(msg_schedule[t - 7])
+            .wrapping_add(s_sigma0_512(msg_schedule[t - 15]))
+            .wrapping_add(msg_schedule[t - 16]);
+    }
+
+    observer.message_schedule(msg_schedule);
+
+    let mut a = hashes[0];
+    let mut b = hashes[1];
+    let mut c = hashes[2];
+    let mut d = hashes[3];
+    let mut e = hashes[4];
+    let mut f = hashes[5];
+    let mut g = hashes[6];
+    let mut h = hashes[7];
+
+    for t in 0..80 {
+        let t1 = h
+            .wrapping_add(b_sigma1_512(e))
+            .wrapping_add(ch(e, f, g))
+            .wrapping_add(SHA_512[t])
+            .wrapping_add(msg_schedule[t]);
+        let t2 = b_sigma0_512(a).wrapping_add(maj(a, b, c));
+
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+        observer.round(t, &[a, b, c, d, e, f, g, h]);
+    }
+
+    hashes[0] = hashes[0].wrapping_add(a);
+    hashes[1] = hashes[1].wrapping_add(b);
+    hashes[2] = hashes[2].wrapping_add(c);
+    hashes[3] = hashes[3].wrapping_add(d);
+    hashes[4] = hashes[4].wrapping_add(e);
+    hashes[5] = hashes[5].wrapping_add(f);
+    hashes[6] = hashes[6].wrapping_add(g);
+    hashes[7] = hashes[7].wrapping_add(h);
+
+    observer.block_done(hashes);
+
+    #[cfg(feature = "zeroize")]
+    {
+        use zeroize::Zeroize;
+        msg_schedule.zeroize();
+        a.zeroize();
+        b.zeroize();
+        c.zeroize();
+        d.zeroize();
+        e.zeroize();
+        f.zeroize();
+        g.zeroize();
+        h.zeroize();
+    }
+}
+
+pub fn hash(msg: &[u8]) -> Option<Sha512Digest> {
+    hash_observed(msg, &mut NoObserver)
 }
 
-pub fn hash(msg: &[u8]) -> Option<[u64; 8]> {
+// hash, calling back into the observer during the hash computation, see
+// crate::observer
+pub fn hash_observed<O: Observer<u64>>(msg: &[u8], observer: &mut O) -> Option<Sha512Digest> {
+    hash_from(SHA_512_INIT, msg, observer)
+}
+
+// the hash computation starting from the given initial hash value, which
+// together with truncating the result is all that distinguishes SHA-384,
+// SHA-512/224 and SHA-512/256
+pub(crate) fn hash_from<O: Observer<u64>>(
+    init: [u64; 8],
+    msg: &[u8],
+    observer: &mut O,
+) -> Option<Sha512Digest> {
     if msg.is_empty() {
         None
     } else {
         let padded_message = pad_message(msg);
-        //println!("Padded message: {:#x?}", padded_message);
-        //println!("Length of padded message: {} bytes", padded_message.len());
 
         // we only take n * 1024 bit messages
         assert_eq!((padded_message.len() * 8) % 1024, 0);
@@ -116,145 +206,261 @@ pub fn hash(msg: &[u8]) -> Option<[u64; 8]> {
         // parse into 1024 bit blocks (128 bytes), using 64 bit words (8 bytes)
         // see 6.4.1 and 6.4.2 on p24 of
         // https://nvlpubs.nist.gov/nistpubs/FIPS/NIST.FIPS.180-4.pdf
-        use byteorder::{BigEndian, ByteOrder};
+        let mut hashes = init;
+        let mut msg_schedule = [0u64; 80];
+        for block in padded_message.chunks(128) {
+            compress(&mut hashes, block, &mut msg_schedule, observer);
+        }
 
-        let mut hashes: [u64; 8] = SHA_512_INIT;
-        //println!("Initial hashes: {:#x?}", hashes);
-        for (_i, block) in padded_message.chunks(128).enumerate() {
-            let mut t = 0;
-            let mut msg_schedule: [u64; 80] = [0u64; 80];
+        let digest = Sha512Digest::from_words(hashes);
 
-            // build message schedule
-            for word in block.chunks(8) {
-                if t < 16 {
-                    msg_schedule[t] = BigEndian::read_u64(word);
-                }
-                t += 1;
-            }
-            for t in 16..80 {
-                msg_schedule[t] = s_sigma1_512(msg_schedule[t - 2])
-                    .wrapping_add(msg_schedule[t - 7])
-                    .wrapping_add(s_sigma0_512(msg_schedule[t - 15]))
-                    .wrapping_add(msg_schedule[t - 16]);
-            }
+        #[cfg(feature = "zeroize")]
+        {
+            use zeroize::Zeroize;
+            let mut padded_message = padded_message;
+            padded_message.zeroize();
+            hashes.zeroize();
+        }
 
-            /*
-            println!("Message schedule for block: {}", i);
-            for m in msg_schedule.iter() {
-                print!("{:#x?} ", m);
-            }
-            println!("");
-            */
-
-            let mut a = hashes[0];
-            let mut b = hashes[1];
-            let mut c = hashes[2];
-            let mut d = hashes[3];
-            let mut e = hashes[4];
-            let mut f = hashes[5];
-            let mut g = hashes[6];
-            let mut h = hashes[7];
-
-            for t in 0..80 {
-                //print!("t={}: ", t);
-                let t1 = h
-                    .wrapping_add(b_sigma1_512(e))
-                    .wrapping_add(ch(e, f, g))
-                    .wrapping_add(SHA_512[t])
-                    .wrapping_add(msg_schedule[t]);
-                let t2 = b_sigma0_512(a).wrapping_add(maj(a, b, c));
-
-                h = g;
-                g = f;
-                f = e;
-                e = d.wrapping_add(t1);
-                d = c;
-                c = b;
-                b = a;
-                a = t1.wrapping_add(t2);
-                /*
-                print!(
-                    "A: {:#x?} B: {:#x?} C: {:#x?} D: {:#x?} E: {:#x?} F: {:#x?} G: {:#x?} H: {:#x?}",
-                    a, b, c, d, e, f, g, h
-                );
-                println!("");
-                */
+        Some(digest)
+    }
+}
+
+// Streaming SHA-512, for messages which arrive in pieces or are too large
+// to hold in memory. Whole 1024 bit blocks are compressed as soon as they
+// have been buffered, and the padding is only applied to the final partial
+// block when the hash is finalized.
+#[derive(Clone)]
+pub struct Sha512 {
+    hashes: [u64; 8],
+    block: [u8; 128],
+    block_len: usize,
+    // length of the message so far, in bytes
+    msg_len: u128,
+}
+
+impl Sha512 {
+    pub fn new() -> Sha512 {
+        Sha512::with_init(SHA_512_INIT)
+    }
+
+    pub(crate) fn with_init(init: [u64; 8]) -> Sha512 {
+        Sha512 {
+            hashes: init,
+            block: [0u8; 128],
+            block_len: 0,
+            msg_len: 0,
+        }
+    }
+
+    pub fn update(&mut self, mut msg: &[u8]) {
+        self.msg_len = self.msg_len.wrapping_add(msg.len() as u128);
+
+        while !msg.is_empty() {
+            let n = std::cmp::min(128 - self.block_len, msg.len());
+            self.block[self.block_len..self.block_len + n].copy_from_slice(&msg[..n]);
+            self.block_len += n;
+            msg = &msg[n..];
+
+            if self.block_len == 128 {
+                compress(&mut self.hashes, &self.block, &mut [0; 80], &mut NoObserver);
+                self.block_len = 0;
             }
+        }
+    }
 
-            hashes[0] = hashes[0].wrapping_add(a);
-            hashes[1] = hashes[1].wrapping_add(b);
-            hashes[2] = hashes[2].wrapping_add(c);
-            hashes[3] = hashes[3].wrapping_add(d);
-            hashes[4] = hashes[4].wrapping_add(e);
-            hashes[5] = hashes[5].wrapping_add(f);
-            hashes[6] = hashes[6].wrapping_add(g);
-            hashes[7] = hashes[7].wrapping_add(h);
+    pub fn finalize(mut self) -> Sha512Digest {
+        self.finish()
+    }
+
+    // finalize, leaving the hasher wiped with the zeroize feature, the
+    // block having held the end of the message and the padding
+    fn finish(&mut self) -> Sha512Digest {
+        use byteorder::{BigEndian, ByteOrder};
+
+        // a single 1 bit, then 0s up to the 128 bit message length,
+        // spilling into an extra block if there is no room left in this one
+        let msg_bits = self.msg_len.wrapping_mul(8);
+        self.block[self.block_len] = 0x80;
+        for byte in self.block[self.block_len + 1..].iter_mut() {
+            *byte = 0;
         }
+        if self.block_len >= 112 {
+            compress(&mut self.hashes, &self.block, &mut [0; 80], &mut NoObserver);
+            self.block = [0u8; 128];
+        }
+        BigEndian::write_u128(&mut self.block[112..], msg_bits);
+        compress(&mut self.hashes, &self.block, &mut [0; 80], &mut NoObserver);
+
+        let digest = Sha512Digest::from_words(self.hashes);
+        #[cfg(feature = "zeroize")]
+        zeroize::Zeroize::zeroize(self);
+        digest
+    }
 
-        Some(hashes)
+    // with the zeroize feature, the old state is wiped when it is dropped
+    // by the assignment
+    pub fn reset(&mut self) {
+        *self = Sha512::new();
     }
 }
 
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn test_sha512_hash() {
-        use std::collections::HashMap;
-        let mut message_hashes: HashMap<&str, [u64; 8]> = HashMap::new();
-        message_hashes.insert(
-            "",
-            [
-                0xcf83e1357eefb8bd,
-                0xf1542850d66d8007,
-                0xd620e4050b5715dc,
-                0x83f4a921d36ce9ce,
-                0x47d0d13c5d85f2b0,
-                0xff8318d2877eec2f,
-                0x63b931bd47417a81,
-                0xa538327af927da3e,
-            ],
-        );
-        message_hashes.insert(
-            "abc",
-            [
-                0xddaf35a193617aba,
-                0xcc417349ae204131,
-                0x12e6fa4e89a97ea2,
-                0x0a9eeee64b55d39a,
-                0x2192992a274fc1a8,
-                0x36ba3c23a3feebbd,
-                0x454d4423643ce80e,
-                0x2a9ac94fa54ca49f,
-            ],
-        );
-        message_hashes.insert(
-            "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
-            [
-                0x204a8fc6dda82f0a,
-                0x0ced7beb8e08a416,
-                0x57c16ef468b228a8,
-                0x279be331a703c335,
-                0x96fd15c13b1b07f9,
-                0xaa1d3bea57789ca0,
-                0x31ad85c7a71dd703,
-                0x54ec631238ca3445,
-            ],
-        );
-        message_hashes.insert(
-            "abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu",
-            [0x8e959b75dae313da, 0x8cf4f72814fc143f, 0x8f7779c6eb9f7fa1, 0x7299aeadb6889018, 0x501d289e4900f7e4, 0x331b99dec4b5433a, 0xc7d329eeb6dd2654, 0x5e96e55b874be909]
-        );
+// wipe the chaining state, buffered message bytes and length, for when
+// the message is a secret such as a key
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for Sha512 {
+    fn zeroize(&mut self) {
+        self.hashes.zeroize();
+        self.block.zeroize();
+        self.block_len.zeroize();
+        self.msg_len.zeroize();
+    }
+}
 
-        for (msg, hash) in message_hashes.iter() {
-            let test_hashes = super::hash(&msg.as_bytes());
+#[cfg(feature = "zeroize")]
+impl Drop for Sha512 {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(self);
+    }
+}
 
-            for (i, test_hash) in test_hashes.iter().enumerate() {
-                assert_eq!(hash[i], test_hash[i]);
-            }
+// The serialized form of a streaming hasher, so that hashing a long
+// message can be checkpointed and picked up again later. Only the bytes
+// actually buffered are kept, and the version is bumped whenever the
+// layout changes. The initial hash value tells apart the algorithms
+// built on this one, see crate::truncated, so that a state can only be
+// resumed by the algorithm which saved it.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Sha512State {
+    version: u32,
+    init: [u64; 8],
+    hashes: [u64; 8],
+    msg_len: u128,
+    block: Vec<u8>,
+}
+
+// the buffered bytes are part of the message
+#[cfg(all(feature = "serde", feature = "zeroize"))]
+impl Drop for Sha512State {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.block);
+        zeroize::Zeroize::zeroize(&mut self.hashes);
+    }
+}
+
+#[cfg(feature = "serde")]
+const STATE_VERSION: u32 = 2;
+
+#[cfg(feature = "serde")]
+impl Sha512 {
+    pub(crate) fn serialize_with_init<S: serde::Serializer>(
+        &self,
+        init: [u64; 8],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(
+            &Sha512State {
+                version: STATE_VERSION,
+                init,
+                hashes: self.hashes,
+                msg_len: self.msg_len,
+                block: self.block[..self.block_len].to_vec(),
+            },
+            serializer,
+        )
+    }
+
+    pub(crate) fn deserialize_with_init<'de, D: serde::Deserializer<'de>>(
+        init: [u64; 8],
+        deserializer: D,
+    ) -> Result<Sha512, D::Error> {
+        use serde::de::Error;
+
+        let state = <Sha512State as serde::Deserialize>::deserialize(deserializer)?;
+        if state.version != STATE_VERSION {
+            return Err(D::Error::custom(format!(
+                "unsupported sha512 state version {}",
+                state.version
+            )));
+        }
+        if state.init != init {
+            return Err(D::Error::custom(
+                "the sha512 state is of a different algorithm",
+            ));
+        }
+        // the buffered bytes are always the tail of the message, and never
+        // a whole block
+        if state.block.len() >= 128 || state.msg_len % 128 != state.block.len() as u128 {
+            return Err(D::Error::custom("inconsistent sha512 state"));
         }
+
+        let mut hasher = Sha512::with_init(init);
+        hasher.hashes = state.hashes;
+        hasher.msg_len = state.msg_len;
+        hasher.block[..state.block.len()].copy_from_slice(&state.block);
+        hasher.block_len = state.block.len();
+        Ok(hasher)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Sha512 {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.serialize_with_init(SHA_512_INIT, serializer)
     }
 }
 
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Sha512 {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Sha512, D::Error> {
+        Sha512::deserialize_with_init(SHA_512_INIT, deserializer)
+    }
+}
+
+impl Default for Sha512 {
+    fn default() -> Sha512 {
+        Sha512::new()
+    }
+}
+
+impl crate::StreamHasher for Sha512 {
+    type Output = Sha512Digest;
+
+    fn update(&mut self, msg: &[u8]) {
+        Sha512::update(self, msg)
+    }
+
+    fn finalize(self) -> Sha512Digest {
+        Sha512::finalize(self)
+    }
+}
+
+// hash a file, memory-mapping it where possible, see crate::file
+pub fn hash_file<P: AsRef<std::path::Path>>(
+    path: P,
+) -> Result<Sha512Digest, crate::file::FileError> {
+    crate::file::hash_file(path.as_ref(), Sha512::new())
+}
+
+// hash each of the messages on the rayon thread pool, the hashes are
+// returned in the same order as the messages
+#[cfg(feature = "rayon")]
+pub fn hash_batch<T: AsRef<[u8]> + Sync>(msgs: &[T]) -> Vec<Option<Sha512Digest>> {
+    use rayon::prelude::*;
+    msgs.par_iter().map(|msg| hash(msg.as_ref())).collect()
+}
+
+// hash each of the files on the rayon thread pool, the results are
+// returned in the same order as the paths
+#[cfg(feature = "rayon")]
+pub fn hash_files<P: AsRef<std::path::Path> + Sync>(
+    paths: &[P],
+) -> Vec<Result<Sha512Digest, crate::file::FileError>> {
+    use rayon::prelude::*;
+    paths.par_iter().map(hash_file).collect()
+}
+
 // the initial hash value consists of the following eight 64-bit words (i.e. 512 bits)
 const SHA_512_INIT: [u64; 8] = [
     0x6a09_e667_f3bc_c908,
@@ -351,3 +557,277 @@ const SHA_512: [u64; 80] = [
     0x5fcb_6fab_3ad6_faec,
     0x6c44_198c_4a47_5817,
 ];
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_sha512_hash() {
+        use std::collections::HashMap;
+        let mut message_hashes: HashMap<&str, [u64; 8]> = HashMap::new();
+        message_hashes.insert(
+            "",
+            [
+                0xcf83e1357eefb8bd,
+                0xf1542850d66d8007,
+                0xd620e4050b5715dc,
+                0x83f4a921d36ce9ce,
+                0x47d0d13c5d85f2b0,
+                0xff8318d2877eec2f,
+                0x63b931bd47417a81,
+                0xa538327af927da3e,
+            ],
+        );
+        message_hashes.insert(
+            "abc",
+            [
+                0xddaf35a193617aba,
+                0xcc417349ae204131,
+                0x12e6fa4e89a97ea2,
+                0x0a9eeee64b55d39a,
+                0x2192992a274fc1a8,
+                0x36ba3c23a3feebbd,
+                0x454d4423643ce80e,
+                0x2a9ac94fa54ca49f,
+            ],
+        );
+        message_hashes.insert(
+            "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+            [
+                0x204a8fc6dda82f0a,
+                0x0ced7beb8e08a416,
+                0x57c16ef468b228a8,
+                0x279be331a703c335,
+                0x96fd15c13b1b07f9,
+                0xaa1d3bea57789ca0,
+                0x31ad85c7a71dd703,
+                0x54ec631238ca3445,
+            ],
+        );
+        message_hashes.insert(
+            "abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu",
+            [0x8e959b75dae313da, 0x8cf4f72814fc143f, 0x8f7779c6eb9f7fa1, 0x7299aeadb6889018, 0x501d289e4900f7e4, 0x331b99dec4b5433a, 0xc7d329eeb6dd2654, 0x5e96e55b874be909]
+        );
+
+        for (msg, hash) in message_hashes.iter() {
+            let test_hashes = super::hash(msg.as_bytes());
+
+            for (i, test_hash) in test_hashes.iter().enumerate() {
+                assert_eq!(hash[i], test_hash.words()[i]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sha512_padding_spills_into_extra_block() {
+        // messages which leave too little room in their last block for the
+        // 1 bit and the length, and so need one more block of padding
+        let message_hashes: [(usize, [u64; 8]); 2] = [
+            (
+                240,
+                [
+                    0x4c296d90c61052a6,
+                    0x2ffb1dd196f1b7b0,
+                    0x9373b1f93e71836b,
+                    0xaebf89690546b759,
+                    0x5684dbe9467a8e48,
+                    0x4fa0d1094272b434,
+                    0x4a7c24f5fee8daed,
+                    0xeb0bf549c985ab5f,
+                ],
+            ),
+            (
+                250,
+                [
+                    0xa58f26ac81e8bf6d,
+                    0x1ea7b63209182cf6,
+                    0x1353bd93dc609c87,
+                    0x5b1fd15bf973060d,
+                    0x038ebb83d9ba0d7a,
+                    0x9340b69c182472e0,
+                    0xab213223f02e54e8,
+                    0xac36ec31a2c998bd,
+                ],
+            ),
+        ];
+
+        for (len, hash) in message_hashes.iter() {
+            let msg = vec![b'a'; *len];
+            assert_eq!(super::hash(&msg), Some((*hash).into()));
+        }
+    }
+
+    #[test]
+    fn test_sha512_streaming() {
+        let msg: Vec<u8> = (0..1000u32).map(|i| (i * 7 % 251) as u8).collect();
+
+        for len in 1..msg.len() {
+            for chunk_size in [1, 3, 64, 100, 129].iter() {
+                let mut hasher = super::Sha512::new();
+                for chunk in msg[..len].chunks(*chunk_size) {
+                    hasher.update(chunk);
+                }
+                assert_eq!(Some(hasher.finalize()), super::hash(&msg[..len]));
+            }
+        }
+    }
+
+    #[test]
+    fn test_sha512_streaming_empty() {
+        // unlike hash, the streaming interface accepts an empty message
+        assert_eq!(
+            super::Sha512::new().finalize().words(),
+            [
+                0xcf83e1357eefb8bd,
+                0xf1542850d66d8007,
+                0xd620e4050b5715dc,
+                0x83f4a921d36ce9ce,
+                0x47d0d13c5d85f2b0,
+                0xff8318d2877eec2f,
+                0x63b931bd47417a81,
+                0xa538327af927da3e
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sha512_streaming_reset() {
+        let mut hasher = super::Sha512::new();
+        hasher.update(b"abc");
+        let abc = hasher.clone().finalize();
+
+        hasher.update(b"def");
+        hasher.reset();
+        hasher.update(b"abc");
+        assert_eq!(hasher.finalize(), abc);
+    }
+
+    #[test]
+    fn test_sha512_hash_file() {
+        let dir = std::env::temp_dir().join(format!("sha512_hash_file_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        for len in [0, 1, 1000, 300_000].iter() {
+            let msg: Vec<u8> = (0..*len as u32).map(|i| (i % 241) as u8).collect();
+            let path = dir.join(format!("file_{}", len));
+            std::fs::write(&path, &msg).unwrap();
+
+            let mut hasher = super::Sha512::new();
+            hasher.update(&msg);
+            assert_eq!(super::hash_file(&path).unwrap(), hasher.finalize());
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_sha512_hash_batch() {
+        let msgs: Vec<Vec<u8>> = (1..100u8).map(|i| vec![i; i as usize * 3]).collect();
+        let hashes = super::hash_batch(&msgs);
+
+        assert_eq!(hashes.len(), msgs.len());
+        for (msg, hash) in msgs.iter().zip(hashes.iter()) {
+            assert_eq!(*hash, super::hash(msg));
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_sha512_hash_files() {
+        let dir = std::env::temp_dir().join(format!("sha512_hash_files_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut paths = Vec::new();
+        for i in 1..20u8 {
+            let path = dir.join(format!("file_{}", i));
+            std::fs::write(&path, vec![i; i as usize * 50]).unwrap();
+            paths.push(path);
+        }
+        paths.push(dir.join("missing"));
+
+        let hashes = super::hash_files(&paths);
+        assert_eq!(hashes.len(), paths.len());
+        for (i, hash) in hashes.iter().take(19).enumerate() {
+            let msg = vec![i as u8 + 1; (i + 1) * 50];
+            assert_eq!(Some(*hash.as_ref().unwrap()), super::hash(&msg));
+        }
+        assert!(hashes[19].is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn test_sha512_zeroize() {
+        use zeroize::Zeroize;
+
+        let secret = [0x5a; 200];
+
+        let mut hasher = super::Sha512::new();
+        hasher.update(&secret);
+        hasher.zeroize();
+        assert_eq!(hasher.hashes, [0; 8]);
+        assert!(hasher.block.iter().all(|b| *b == 0));
+        assert_eq!((hasher.block_len, hasher.msg_len), (0, 0));
+
+        let mut hasher = super::Sha512::new();
+        hasher.update(&secret);
+        hasher.reset();
+        assert_eq!(hasher.hashes, super::SHA_512_INIT);
+        assert!(hasher.block.iter().all(|b| *b == 0));
+        assert_eq!((hasher.block_len, hasher.msg_len), (0, 0));
+
+        // finalizing wipes the block, which had the end of the message and
+        // then the padding in it
+        let mut hasher = super::Sha512::new();
+        hasher.update(&secret);
+        assert!(hasher.block.contains(&0x5a));
+        assert_eq!(Some(hasher.finish()), super::hash(&secret));
+        assert_eq!(hasher.hashes, [0; 8]);
+        assert!(hasher.block.iter().all(|b| *b == 0));
+        assert_eq!((hasher.block_len, hasher.msg_len), (0, 0));
+
+        // and compressing a block wipes W, every word of which it sets
+        let mut hashes = super::SHA_512_INIT;
+        let mut msg_schedule = [1; 80];
+        super::compress(
+            &mut hashes,
+            &secret[..128],
+            &mut msg_schedule,
+            &mut crate::observer::NoObserver,
+        );
+        assert_ne!(hashes, super::SHA_512_INIT);
+        assert_eq!(msg_schedule, [0; 80]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_sha512_serde_resume() {
+        let msg: Vec<u8> = (0..1000u32).map(|i| (i * 13 % 256) as u8).collect();
+
+        for split in [0, 1, 128 - 1, 128, 500].iter() {
+            let mut hasher = super::Sha512::new();
+            hasher.update(&msg[..*split]);
+
+            let json = serde_json::to_string(&hasher).unwrap();
+            let mut resumed: super::Sha512 = serde_json::from_str(&json).unwrap();
+            resumed.update(&msg[*split..]);
+            assert_eq!(Some(resumed.finalize()), super::hash(&msg));
+
+            let bytes = bincode::serialize(&hasher).unwrap();
+            let mut resumed: super::Sha512 = bincode::deserialize(&bytes).unwrap();
+            resumed.update(&msg[*split..]);
+            assert_eq!(Some(resumed.finalize()), super::hash(&msg));
+        }
+
+        let mut hasher = super::Sha512::new();
+        hasher.update(b"abc");
+        let json = serde_json::to_string(&hasher).unwrap();
+        assert!(json.starts_with("{\"version\":2,\"init\":["));
+
+        let bad_version = json.replace("\"version\":2", "\"version\":1");
+        assert!(serde_json::from_str::<super::Sha512>(&bad_version).is_err());
+        let bad_length = json.replace("\"msg_len\":3", "\"msg_len\":4");
+        assert!(serde_json::from_str::<super::Sha512>(&bad_length).is_err());
+    }
+}
@@ -0,0 +1,66 @@
+/*
+
+SHA-512/224, which is SHA-512 with its own initial hash value (see 5.3.6.1) and
+the digest truncated to 224 bits / the first 3.5 of the 8 64-bit words.
+See https://nvlpubs.nist.gov/nistpubs/FIPS/NIST.FIPS.180-4.pdf
+and crate::truncated.
+
+*/
+
+truncated!(
+    Sha512_224,
+    Sha512,
+    sha512,
+    u64,
+    Sha512_224Digest,
+    Sha512Digest,
+    [
+        0x8c3d37c819544da2,
+        0x73e1996689dcd4d6,
+        0x1dfab7ae32ff9c82,
+        0x679dd514582f9fcf,
+        0x0f6d2b697bd44da8,
+        0x77e36f7304c48942,
+        0x3f9d85a86a1d36c8,
+        0x1112e6ad91d692a1
+    ]
+);
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_sha512_224_hash() {
+        let message_hashes = [
+            (
+                "abc",
+                "4634270f707b6a54daae7530460842e20e37ed265ceee9a43e8924aa",
+            ),
+            (
+                "abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu",
+                "23fec5bb94d60b23308192640b0c453335d664734fe40e7268674af9",
+            ),
+        ];
+
+        for (msg, hash) in message_hashes.iter() {
+            assert_eq!(super::hash(msg.as_bytes()), Some(hash.parse().unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_sha512_224_streaming() {
+        let msg: Vec<u8> = (0..300u32).map(|i| (i * 7 % 251) as u8).collect();
+
+        for len in 1..msg.len() {
+            let mut hasher = super::Sha512_224::new();
+            for chunk in msg[..len].chunks(100) {
+                hasher.update(chunk);
+            }
+            assert_eq!(Some(hasher.finalize()), super::hash(&msg[..len]));
+        }
+
+        assert_eq!(
+            super::Sha512_224::new().finalize().to_hex(),
+            "6ed0dd02806fa89e25de060c19d3ac86cabb87d6a0ddd05c333b84f4"
+        );
+    }
+}
@@ -0,0 +1,66 @@
+/*
+
+SHA-512/256, which is SHA-512 with its own initial hash value (see 5.3.6.2) and
+the digest truncated to 256 bits / the first 4 of the 8 64-bit words.
+See https://nvlpubs.nist.gov/nistpubs/FIPS/NIST.FIPS.180-4.pdf
+and crate::truncated.
+
+*/
+
+truncated!(
+    Sha512_256,
+    Sha512,
+    sha512,
+    u64,
+    Sha512_256Digest,
+    Sha512Digest,
+    [
+        0x22312194fc2bf72c,
+        0x9f555fa3c84c64c2,
+        0x2393b86b6f53b151,
+        0x963877195940eabd,
+        0x96283ee2a88effe3,
+        0xbe5e1e2553863992,
+        0x2b0199fc2c85b8aa,
+        0x0eb72ddc81c52ca2
+    ]
+);
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_sha512_256_hash() {
+        let message_hashes = [
+            (
+                "abc",
+                "53048e2681941ef99b2e29b76b4c7dabe4c2d0c634fc6d46e0e2f13107e7af23",
+            ),
+            (
+                "abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu",
+                "3928e184fb8690f840da3988121d31be65cb9d3ef83ee6146feac861e19b563a",
+            ),
+        ];
+
+        for (msg, hash) in message_hashes.iter() {
+            assert_eq!(super::hash(msg.as_bytes()), Some(hash.parse().unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_sha512_256_streaming() {
+        let msg: Vec<u8> = (0..300u32).map(|i| (i * 7 % 251) as u8).collect();
+
+        for len in 1..msg.len() {
+            let mut hasher = super::Sha512_256::new();
+            for chunk in msg[..len].chunks(100) {
+                hasher.update(chunk);
+            }
+            assert_eq!(Some(hasher.finalize()), super::hash(&msg[..len]));
+        }
+
+        assert_eq!(
+            super::Sha512_256::new().finalize().to_hex(),
+            "c672b8d1ef56ed28ab87c3622c5114069bdd3ad7b8f9737498d0c01ecef0967a"
+        );
+    }
+}
@@ -0,0 +1,397 @@
+/*
+
+SHA-256-crypt and SHA-512-crypt, the $5$ and $6$ password hashes of
+/etc/shadow, see Ulrich Drepper's "Unix crypt using SHA-256 and SHA-512"
+https://www.akkadia.org/drepper/SHA-crypt.txt
+
+A hash looks like
+
+    $5$rounds=10000$saltstringsaltst$3xv.VbSHBb41AL9AvLeujZkZRBAwqFMz2.opqey6IcA
+
+where the rounds= field is optional and only written out when it was
+asked for, the default being 5000 rounds. Rounds outside [1000,
+999999999] are clamped to the nearest bound rather than rejected, and
+salts longer than 16 characters are cut short, both as the specification
+does.
+
+The digest is written in crypt's own base64, with the alphabet starting
+at '.' and '/', least significant bits first, after the digest bytes
+have been shuffled into a fixed order.
+
+*/
+
+use crate::sha256::Sha256;
+use crate::sha512::Sha512;
+use crate::StreamHasher;
+use std::error::Error;
+use std::fmt;
+
+pub const DEFAULT_ROUNDS: u32 = 5000;
+pub const MIN_ROUNDS: u32 = 1000;
+pub const MAX_ROUNDS: u32 = 999_999_999;
+
+// the longest salt in characters, longer ones are truncated
+pub const MAX_SALT_LEN: usize = 16;
+
+const CRYPT_BASE64: &[u8; 64] = b"./0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+// the order the digest bytes are encoded in, three at a time
+const SHA256_ORDER: [usize; 32] = [
+    0, 10, 20, 21, 1, 11, 12, 22, 2, 3, 13, 23, 24, 4, 14, 15, 25, 5, 6, 16, 26, 27, 7, 17, 18, 28,
+    8, 9, 19, 29, 31, 30,
+];
+const SHA512_ORDER: [usize; 64] = [
+    0, 21, 42, 22, 43, 1, 44, 2, 23, 3, 24, 45, 25, 46, 4, 47, 5, 26, 6, 27, 48, 28, 49, 7, 50, 8,
+    29, 9, 30, 51, 31, 52, 10, 53, 11, 32, 12, 33, 54, 34, 55, 13, 56, 14, 35, 15, 36, 57, 37, 58,
+    16, 59, 17, 38, 18, 39, 60, 40, 61, 19, 62, 20, 41, 63,
+];
+
+#[derive(Debug, PartialEq)]
+pub enum ShaCryptError {
+    // neither $5$ nor $6$
+    UnknownPrefix,
+    // a rounds= field which isn't a decimal number followed by '$'
+    InvalidRounds,
+    // a salt with characters which can't be stored in the hash: '$', ':',
+    // whitespace, control or non-ASCII characters
+    InvalidSalt,
+}
+
+impl fmt::Display for ShaCryptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShaCryptError::UnknownPrefix => f.write_str("not a $5$ or $6$ hash"),
+            ShaCryptError::InvalidRounds => f.write_str("invalid rounds= field"),
+            ShaCryptError::InvalidSalt => f.write_str("invalid character in the salt"),
+        }
+    }
+}
+
+impl Error for ShaCryptError {}
+
+// SHA-256-crypt of the password, with rounds= written out if rounds is
+// given
+pub fn sha256_crypt(
+    password: &[u8],
+    salt: &str,
+    rounds: Option<u32>,
+) -> Result<String, ShaCryptError> {
+    let salt = check_salt(salt)?;
+    let rounds = rounds.map(|rounds| rounds.clamp(MIN_ROUNDS, MAX_ROUNDS));
+    let digest = sha_crypt::<Sha256>(password, salt.as_bytes(), rounds);
+    Ok(encode("$5$", salt, rounds, &digest, &SHA256_ORDER))
+}
+
+pub fn sha512_crypt(
+    password: &[u8],
+    salt: &str,
+    rounds: Option<u32>,
+) -> Result<String, ShaCryptError> {
+    let salt = check_salt(salt)?;
+    let rounds = rounds.map(|rounds| rounds.clamp(MIN_ROUNDS, MAX_ROUNDS));
+    let digest = sha_crypt::<Sha512>(password, salt.as_bytes(), rounds);
+    Ok(encode("$6$", salt, rounds, &digest, &SHA512_ORDER))
+}
+
+// Hash the password with the settings of an existing hash, or of just its
+// prefix, rounds= and salt, like crypt(3). The salt ends at the next '$'
+// so a complete hash can be passed in.
+pub fn crypt(password: &[u8], setting: &str) -> Result<String, ShaCryptError> {
+    let (sha512, rest) = if let Some(rest) = setting.strip_prefix("$5$") {
+        (false, rest)
+    } else if let Some(rest) = setting.strip_prefix("$6$") {
+        (true, rest)
+    } else {
+        return Err(ShaCryptError::UnknownPrefix);
+    };
+
+    let (rounds, rest) = match rest.strip_prefix("rounds=") {
+        Some(rest) => {
+            let end = rest.find('$').ok_or(ShaCryptError::InvalidRounds)?;
+            (Some(parse_rounds(&rest[..end])?), &rest[end + 1..])
+        }
+        None => (None, rest),
+    };
+    let salt = rest.split('$').next().unwrap_or("");
+
+    if sha512 {
+        sha512_crypt(password, salt, rounds)
+    } else {
+        sha256_crypt(password, salt, rounds)
+    }
+}
+
+// whether the password matches a $5$ or $6$ hash, comparing in constant
+// time
+pub fn verify(password: &[u8], hash: &str) -> bool {
+    match crypt(password, hash) {
+        Ok(computed) => crate::util::ct_eq(computed.as_bytes(), hash.as_bytes()),
+        Err(_) => false,
+    }
+}
+
+// decimal digits only, anything too large is clamped later along with
+// everything else above MAX_ROUNDS
+fn parse_rounds(digits: &str) -> Result<u32, ShaCryptError> {
+    if digits.is_empty() || !digits.bytes().all(|c| c.is_ascii_digit()) {
+        return Err(ShaCryptError::InvalidRounds);
+    }
+    Ok(digits.parse().unwrap_or(u32::MAX))
+}
+
+fn check_salt(salt: &str) -> Result<&str, ShaCryptError> {
+    if !salt
+        .bytes()
+        .all(|c| c.is_ascii_graphic() && c != b'$' && c != b':')
+    {
+        return Err(ShaCryptError::InvalidSalt);
+    }
+    Ok(&salt[..salt.len().min(MAX_SALT_LEN)])
+}
+
+fn encode(prefix: &str, salt: &str, rounds: Option<u32>, digest: &[u8], order: &[usize]) -> String {
+    let mut out = String::from(prefix);
+    if let Some(rounds) = rounds {
+        out.push_str(&format!("rounds={}$", rounds));
+    }
+    out.push_str(salt);
+    out.push('$');
+
+    // each group of up to three bytes goes out as one more character than
+    // it has bytes, least significant six bits first
+    for group in order.chunks(3) {
+        let mut w = group.iter().fold(0u32, |w, i| (w << 8) | digest[*i] as u32);
+        for _ in 0..=group.len() {
+            out.push(CRYPT_BASE64[(w & 0x3f) as usize] as char);
+            w >>= 6;
+        }
+    }
+    out
+}
+
+// bytes repeated over and over to len bytes
+fn repeat_to(bytes: &[u8], len: usize) -> Vec<u8> {
+    bytes.iter().cycle().take(len).copied().collect()
+}
+
+// the digest of steps 1 to 21 of the specification, for either hash
+fn sha_crypt<H>(password: &[u8], salt: &[u8], rounds: Option<u32>) -> Vec<u8>
+where
+    H: StreamHasher + Default,
+    H::Output: AsRef<[u8]>,
+{
+    let rounds = rounds.unwrap_or(DEFAULT_ROUNDS);
+
+    // the alternate digest B
+    let mut hasher = H::default();
+    hasher.update(password);
+    hasher.update(salt);
+    hasher.update(password);
+    let b = hasher.finalize().as_ref().to_vec();
+
+    // A, with B repeated over the length of the password, then B or the
+    // password for each bit of the length
+    let mut hasher = H::default();
+    hasher.update(password);
+    hasher.update(salt);
+    hasher.update(&repeat_to(&b, password.len()));
+    let mut len = password.len();
+    while len > 0 {
+        if len & 1 == 1 {
+            hasher.update(&b);
+        } else {
+            hasher.update(password);
+        }
+        len >>= 1;
+    }
+    let mut a = hasher.finalize().as_ref().to_vec();
+
+    // the byte sequences P and S, from the password repeated once per
+    // byte of itself and the salt repeated 16 + A[0] times
+    let mut hasher = H::default();
+    for _ in 0..password.len() {
+        hasher.update(password);
+    }
+    #[cfg_attr(not(feature = "zeroize"), allow(unused_mut))]
+    let mut p = repeat_to(hasher.finalize().as_ref(), password.len());
+
+    let mut hasher = H::default();
+    for _ in 0..16 + a[0] as usize {
+        hasher.update(salt);
+    }
+    let s = repeat_to(hasher.finalize().as_ref(), salt.len());
+
+    for i in 0..rounds {
+        let mut hasher = H::default();
+        if i & 1 == 1 {
+            hasher.update(&p);
+        } else {
+            hasher.update(&a);
+        }
+        if i % 3 != 0 {
+            hasher.update(&s);
+        }
+        if i % 7 != 0 {
+            hasher.update(&p);
+        }
+        if i & 1 == 1 {
+            hasher.update(&a);
+        } else {
+            hasher.update(&p);
+        }
+        a.copy_from_slice(hasher.finalize().as_ref());
+    }
+
+    #[cfg(feature = "zeroize")]
+    {
+        use zeroize::Zeroize;
+        p.zeroize();
+    }
+
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the test vectors at the end of the specification, as (setting,
+    // password, $5$ hash, $6$ hash), with the two which take many rounds
+    // last
+    const VECTORS: [(&str, &str, &str, &str); 7] = [
+        (
+            "saltstring",
+            "Hello world!",
+            "$5$saltstring$5B8vYYiY.CVt1RlTTf8KbXBH3hsxY/GNooZaBBGWEc5",
+            "$6$saltstring$svn8UoSVapNtMuq1ukKS4tPQd8iKwSMHWjl/O817G3uBnIFNjnQJuesI68u4OTLiBFdcbY\
+             EdFCoEOfaS35inz1",
+        ),
+        (
+            "rounds=10000$saltstringsaltstring",
+            "Hello world!",
+            "$5$rounds=10000$saltstringsaltst$3xv.VbSHBb41AL9AvLeujZkZRBAwqFMz2.opqey6IcA",
+            "$6$rounds=10000$saltstringsaltst$OW1/O6BYHV6BcXZu8QVeXbDWra3Oeqh0sbHbbMCVNSnCM/UrjmM0\
+             Dp8vOuZeHBy/YTBmSK6H9qs/y3RnOaw5v.",
+        ),
+        (
+            "rounds=5000$toolongsaltstring",
+            "This is just a test",
+            "$5$rounds=5000$toolongsaltstrin$Un/5jzAHMgOGZ5.mWJpuVolil07guHPvOW8mGRcvxa5",
+            "$6$rounds=5000$toolongsaltstrin$lQ8jolhgVRVhY4b5pZKaysCLi0QBxGoNeKQzQ3glMhwllF7oGDZx\
+             Uhx1yxdYcz/e1JSbq3y6JMxxl8audkUEm0",
+        ),
+        (
+            "rounds=1400$anotherlongsaltstring",
+            "a very much longer text to encrypt.  This one even stretches over morethan one line.",
+            "$5$rounds=1400$anotherlongsalts$Rx.j8H.h8HjEDGomFU8bDkXm3XIUnzyxf12oP84Bnq1",
+            "$6$rounds=1400$anotherlongsalts$POfYwTEok97VWcjxIiSOjiykti.o/pQs.wPvMxQ6Fm7I6IoYN3Cm\
+             Ls66x9t0oSwbtEW7o7UmJEiDwGqd8p4ur1",
+        ),
+        (
+            "rounds=10$roundstoolow",
+            "the minimum number is still observed",
+            "$5$rounds=1000$roundstoolow$yfvwcWrQ8l/K0DAWyuPMDNHpIVlTQebY9l/gL972bIC",
+            "$6$rounds=1000$roundstoolow$kUMsbe306n21p9R.FRkW3IGn.S9NPN0x50YhH1xhLsPuWGsUSklZt58j\
+             aTfF4ZEQpyUNGc0dqbpBYYBaHHrsX.",
+        ),
+        (
+            "rounds=77777$short",
+            "we have a short salt string but not a short password",
+            "$5$rounds=77777$short$JiO1O3ZpDAxGJeaDIuqCoEFysAe1mZNJRs3pw0KQRd/",
+            "$6$rounds=77777$short$WuQyW2YR.hBNpjjRhpYD/ifIw05xdfeEyQoMxIXbkvr0gge1a1x3yRULJ5CCaU\
+             eOxFmtlcGZelFl5CxtgfiAc0",
+        ),
+        (
+            "rounds=123456$asaltof16chars..",
+            "a short string",
+            "$5$rounds=123456$asaltof16chars..$gP3VQ/6X7UUEW3HkBn2w1/Ptq2jxPyzV/cZKmF/wJvD",
+            "$6$rounds=123456$asaltof16chars..$BtCwjqMJGx5hrJhZywWvt0RLE8uZ4oPwcelCjmw2kSYu.Ec6yc\
+             ULevoBK25fs2xXgMNrCzIMVcgEJAstJeonj1",
+        ),
+    ];
+
+    fn check(vectors: &[(&str, &str, &str, &str)]) {
+        for (setting, password, sha256, sha512) in vectors.iter() {
+            let password = password.as_bytes();
+            assert_eq!(
+                crypt(password, &format!("$5${}", setting)).unwrap(),
+                *sha256
+            );
+            assert_eq!(
+                crypt(password, &format!("$6${}", setting)).unwrap(),
+                *sha512
+            );
+
+            assert!(verify(password, sha256));
+            assert!(verify(password, sha512));
+            assert!(!verify(b"Hello world?", sha256));
+            assert!(!verify(b"Hello world?", sha512));
+        }
+    }
+
+    #[test]
+    fn test_sha_crypt_vectors() {
+        check(&VECTORS[..5]);
+    }
+
+    // 77777 and 123456 rounds take a while in a debug build, run with
+    // cargo test --release -- --ignored
+    #[test]
+    #[ignore]
+    fn test_sha_crypt_vectors_many_rounds() {
+        check(&VECTORS[5..]);
+    }
+
+    #[test]
+    fn test_sha_crypt_settings() {
+        assert_eq!(
+            sha256_crypt(b"Hello world!", "saltstring", None).unwrap(),
+            VECTORS[0].2
+        );
+        assert_eq!(
+            sha512_crypt(b"Hello world!", "saltstringsaltstring", Some(10000)).unwrap(),
+            VECTORS[1].3
+        );
+        // rounds=5000 is written out when asked for, and the rest of a
+        // hash after the salt is ignored
+        assert_eq!(
+            crypt(b"Hello world!", "$5$rounds=5000$saltstring$").unwrap(),
+            "$5$rounds=5000$saltstring$5B8vYYiY.CVt1RlTTf8KbXBH3hsxY/GNooZaBBGWEc5"
+        );
+        assert_eq!(
+            crypt(b"Hello world!", "$5$saltstring$garbage").unwrap(),
+            VECTORS[0].2
+        );
+
+        // an empty password and salt, checked against glibc
+        assert_eq!(
+            crypt(b"", "$6$").unwrap(),
+            "$6$$/chiBau24cE26QQVW3IfIe68Xu5.JQ4E8Ie7lcRLwqxO5cxGuBhqF2HmTL.zWJ9zjChg3yJYFXeGBQ2y3\
+             Ba1d1"
+        );
+    }
+
+    #[test]
+    fn test_sha_crypt_errors() {
+        assert_eq!(crypt(b"", "$1$salt"), Err(ShaCryptError::UnknownPrefix));
+        assert_eq!(crypt(b"", "saltstring"), Err(ShaCryptError::UnknownPrefix));
+        for setting in [
+            "$5$rounds=$salt",
+            "$5$rounds=12a$salt",
+            "$6$rounds=-1$salt",
+            "$6$rounds=1000",
+        ]
+        .iter()
+        {
+            assert_eq!(crypt(b"", setting), Err(ShaCryptError::InvalidRounds));
+        }
+        for salt in ["a$b", "a:b", "a b", "sälz", "\n"].iter() {
+            assert_eq!(
+                sha256_crypt(b"", salt, None),
+                Err(ShaCryptError::InvalidSalt)
+            );
+        }
+        assert!(!verify(b"", "$5$rounds=$salt"));
+        assert!(!verify(b"", ""));
+    }
+}
@@ -0,0 +1,434 @@
+/*
+
+Subresource Integrity (W3C SRI) metadata, as in the integrity attribute
+of script and link elements and the integrity field of npm lockfiles:
+
+    integrity="sha384-H8BRh8j48O9oYatfu5AZzq6A9RINhZO5H16dQZngK7T62em8MUt1FLm52t+eX6xO"
+
+Metadata is a whitespace separated list of hashes, each an algorithm
+(sha256, sha384 or sha512), a dash, the base64 digest and optionally a
+'?' and options, which are kept but have no meaning yet. As the spec
+requires, entries with other algorithms, such as the sha1 of old npm
+lockfiles, are skipped rather than rejected, and content is checked
+only against the hashes of the strongest algorithm listed, matching if
+any of them does.
+
+*/
+
+use crate::algorithm::Algorithm;
+use crate::encoding;
+use crate::file::FileError;
+use std::convert::Infallible;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+// strongest first
+const SUPPORTED: [(Algorithm, &str); 3] = [
+    (Algorithm::Sha512, "sha512"),
+    (Algorithm::Sha384, "sha384"),
+    (Algorithm::Sha256, "sha256"),
+];
+
+#[derive(Debug)]
+pub enum SriError {
+    // not algorithm-base64, or a digest of the wrong length
+    Malformed,
+    UnsupportedAlgorithm(String),
+    Mismatch(Algorithm),
+    File(FileError),
+}
+
+impl fmt::Display for SriError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SriError::Malformed => f.write_str("malformed integrity hash"),
+            SriError::UnsupportedAlgorithm(alg) => {
+                write!(f, "unsupported integrity algorithm {}", alg)
+            }
+            SriError::Mismatch(alg) => write!(f, "the {} integrity hash doesn't match", alg.name()),
+            SriError::File(err) => err.fmt(f),
+        }
+    }
+}
+
+impl Error for SriError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SriError::File(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<FileError> for SriError {
+    fn from(err: FileError) -> SriError {
+        SriError::File(err)
+    }
+}
+
+fn token(algorithm: Algorithm) -> Result<&'static str, SriError> {
+    SUPPORTED
+        .iter()
+        .find(|(alg, _)| *alg == algorithm)
+        .map(|(_, token)| *token)
+        .ok_or_else(|| SriError::UnsupportedAlgorithm(algorithm.name().to_string()))
+}
+
+// tokens are case-insensitive
+fn from_token(token: &str) -> Option<Algorithm> {
+    SUPPORTED
+        .iter()
+        .find(|(_, t)| t.eq_ignore_ascii_case(token))
+        .map(|(alg, _)| *alg)
+}
+
+// Either base64 alphabet, with or without padding, as some tools write the
+// URL-safe one
+fn decode_digest(value: &str) -> Option<Vec<u8>> {
+    encoding::decode_base64(value)
+        .or_else(|_| encoding::decode_base64_url(value))
+        .ok()
+}
+
+// One hash of the metadata, such as sha384-H8BR...
+#[derive(Clone, Debug, PartialEq)]
+pub struct IntegrityHash {
+    algorithm: Algorithm,
+    digest: Vec<u8>,
+    options: Option<String>,
+}
+
+impl IntegrityHash {
+    pub fn new(algorithm: Algorithm, data: &[u8]) -> Result<IntegrityHash, SriError> {
+        token(algorithm)?;
+        Ok(IntegrityHash {
+            algorithm,
+            digest: algorithm.hash(data),
+            options: None,
+        })
+    }
+
+    pub fn from_file<P: AsRef<Path>>(
+        algorithm: Algorithm,
+        path: P,
+    ) -> Result<IntegrityHash, SriError> {
+        token(algorithm)?;
+        Ok(IntegrityHash {
+            algorithm,
+            digest: algorithm.hash_file(path)?,
+            options: None,
+        })
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    pub fn digest(&self) -> &[u8] {
+        &self.digest
+    }
+
+    pub fn options(&self) -> Option<&str> {
+        self.options.as_deref()
+    }
+
+    // what follows the '?', without it
+    pub fn set_options(&mut self, options: Option<&str>) {
+        self.options = options.map(str::to_string);
+    }
+
+    // compared in constant time
+    pub fn matches(&self, data: &[u8]) -> bool {
+        crate::util::ct_eq(&self.algorithm.hash(data), &self.digest)
+    }
+}
+
+impl fmt::Display for IntegrityHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // only supported algorithms get in
+        let token = token(self.algorithm).unwrap();
+        write!(f, "{}-{}", token, encoding::base64(&self.digest))?;
+        if let Some(options) = &self.options {
+            write!(f, "?{}", options)?;
+        }
+        Ok(())
+    }
+}
+
+// Unlike Integrity this is strict, so a single hash which can't be used is
+// an error
+impl FromStr for IntegrityHash {
+    type Err = SriError;
+
+    fn from_str(s: &str) -> Result<IntegrityHash, SriError> {
+        let mut parts = s.splitn(2, '?');
+        let expression = parts.next().unwrap();
+        let options = parts.next();
+        let mut parts = expression.splitn(2, '-');
+        let algorithm = parts.next().unwrap();
+        let value = parts.next().ok_or(SriError::Malformed)?;
+
+        let algorithm = from_token(algorithm)
+            .ok_or_else(|| SriError::UnsupportedAlgorithm(algorithm.to_string()))?;
+        let digest = decode_digest(value).ok_or(SriError::Malformed)?;
+        if digest.len() != algorithm.output_size() {
+            return Err(SriError::Malformed);
+        }
+        Ok(IntegrityHash {
+            algorithm,
+            digest,
+            options: options.map(str::to_string),
+        })
+    }
+}
+
+// Integrity metadata, a list of hashes
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Integrity {
+    hashes: Vec<IntegrityHash>,
+}
+
+impl Integrity {
+    pub fn new() -> Integrity {
+        Integrity::default()
+    }
+
+    // hashes of the data for each of the algorithms, in order
+    pub fn from_data(algorithms: &[Algorithm], data: &[u8]) -> Result<Integrity, SriError> {
+        let hashes = algorithms
+            .iter()
+            .map(|alg| IntegrityHash::new(*alg, data))
+            .collect::<Result<_, _>>()?;
+        Ok(Integrity { hashes })
+    }
+
+    pub fn from_file<P: AsRef<Path>>(
+        algorithms: &[Algorithm],
+        path: P,
+    ) -> Result<Integrity, SriError> {
+        let hashes = algorithms
+            .iter()
+            .map(|alg| IntegrityHash::from_file(*alg, path.as_ref()))
+            .collect::<Result<_, _>>()?;
+        Ok(Integrity { hashes })
+    }
+
+    pub fn push(&mut self, hash: IntegrityHash) {
+        self.hashes.push(hash);
+    }
+
+    pub fn hashes(&self) -> &[IntegrityHash] {
+        &self.hashes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    // the hashes which content is checked against
+    pub fn strongest(&self) -> Vec<&IntegrityHash> {
+        SUPPORTED
+            .iter()
+            .map(|(alg, _)| {
+                self.hashes
+                    .iter()
+                    .filter(|hash| hash.algorithm == *alg)
+                    .collect::<Vec<_>>()
+            })
+            .find(|hashes| !hashes.is_empty())
+            .unwrap_or_default()
+    }
+
+    // Checks the data against the hashes of the strongest algorithm,
+    // returning the algorithm. As in the spec, metadata with nothing usable
+    // in it lets anything through, which is None here, so callers which
+    // insist on a hash have to check for that.
+    pub fn verify(&self, data: &[u8]) -> Result<Option<Algorithm>, SriError> {
+        self.check(|alg| Ok(alg.hash(data)))
+    }
+
+    pub fn verify_file<P: AsRef<Path>>(&self, path: P) -> Result<Option<Algorithm>, SriError> {
+        self.check(|alg| Ok(alg.hash_file(path.as_ref())?))
+    }
+
+    fn check<F>(&self, hash: F) -> Result<Option<Algorithm>, SriError>
+    where
+        F: FnOnce(Algorithm) -> Result<Vec<u8>, SriError>,
+    {
+        let hashes = self.strongest();
+        let alg = match hashes.first() {
+            Some(first) => first.algorithm,
+            None => return Ok(None),
+        };
+        let actual = hash(alg)?;
+        // every hash is compared, so the time taken doesn't say which
+        // matched
+        let matched = hashes.iter().fold(false, |matched, hash| {
+            matched | crate::util::ct_eq(&actual, &hash.digest)
+        });
+        if !matched {
+            return Err(SriError::Mismatch(alg));
+        }
+        Ok(Some(alg))
+    }
+}
+
+impl fmt::Display for Integrity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, hash) in self.hashes.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{}", hash)?;
+        }
+        Ok(())
+    }
+}
+
+// The spec's parse metadata, which never fails: entries for other
+// algorithms are skipped. One for a supported algorithm whose digest is
+// malformed is kept with an empty digest, so that it still counts when
+// choosing the strongest algorithm but can never match.
+impl FromStr for Integrity {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Integrity, Infallible> {
+        let hashes = s
+            .split_ascii_whitespace()
+            .filter_map(|item| match item.parse::<IntegrityHash>() {
+                Ok(hash) => Some(hash),
+                Err(SriError::Malformed) => {
+                    let algorithm = item.split(['-', '?']).next().and_then(from_token)?;
+                    Some(IntegrityHash {
+                        algorithm,
+                        digest: Vec::new(),
+                        options: None,
+                    })
+                }
+                Err(_) => None,
+            })
+            .collect();
+        Ok(Integrity { hashes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCRIPT: &[u8] = b"alert('Hello, world.');";
+    const SHA384: &str = "sha384-H8BRh8j48O9oYatfu5AZzq6A9RINhZO5H16dQZngK7T62em8MUt1FLm52t+eX6xO";
+    const SHA512: &str = "sha512-Q2bFTOhEALkN8hOms2FKTDLy7eugP2zFZ1T8LCvX42Fp3WoNr3bjZSAHeOsHrbV1Fu9/A0EzCinRE7Af1ofPrw==";
+
+    // the example in the spec, and the other algorithms from Python's
+    // hashlib
+    #[test]
+    fn test_generate() {
+        let hash = IntegrityHash::new(Algorithm::Sha384, SCRIPT).unwrap();
+        assert_eq!(hash.to_string(), SHA384);
+        assert_eq!(SHA384.parse::<IntegrityHash>().unwrap(), hash);
+
+        let integrity =
+            Integrity::from_data(&[Algorithm::Sha256, Algorithm::Sha512], SCRIPT).unwrap();
+        assert_eq!(
+            integrity.to_string(),
+            format!(
+                "sha256-qznLcsROx4GACP2dm0UCKCzCG+HiZ1guq6ZZDob/Tng= {}",
+                SHA512
+            )
+        );
+        assert!(matches!(
+            IntegrityHash::new(Algorithm::Sha224, SCRIPT),
+            Err(SriError::UnsupportedAlgorithm(_))
+        ));
+
+        let path = std::env::temp_dir().join(format!("sri_{}", std::process::id()));
+        std::fs::write(&path, SCRIPT).unwrap();
+        let from_file = Integrity::from_file(&[Algorithm::Sha256, Algorithm::Sha512], &path);
+        let verified = integrity.verify_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(from_file.unwrap(), integrity);
+        assert_eq!(verified.unwrap(), Some(Algorithm::Sha512));
+        assert!(matches!(
+            integrity.verify_file(&path),
+            Err(SriError::File(FileError::NotFound(_)))
+        ));
+    }
+
+    #[test]
+    fn test_parse() {
+        let metadata = format!(
+            "  sha1-Ry3o4T9ECVczn3oqKIOoAcvbjW0=\t{}?foo md5-xyz SHA512-{}?a?b \n",
+            SHA384,
+            &SHA512[7..]
+        );
+        let integrity = metadata.parse::<Integrity>().unwrap();
+        assert_eq!(integrity.hashes().len(), 2);
+        assert_eq!(integrity.hashes()[0].options(), Some("foo"));
+        assert_eq!(integrity.hashes()[1].options(), Some("a?b"));
+        assert_eq!(
+            integrity.to_string(),
+            format!("{}?foo {}?a?b", SHA384, SHA512)
+        );
+        assert_eq!(integrity.strongest(), vec![&integrity.hashes()[1]]);
+
+        // the URL-safe alphabet and no padding
+        let hash = "sha256-qznLcsROx4GACP2dm0UCKCzCG-HiZ1guq6ZZDob_Tng"
+            .parse::<IntegrityHash>()
+            .unwrap();
+        assert!(hash.matches(SCRIPT));
+
+        assert_eq!("".parse::<Integrity>().unwrap(), Integrity::new());
+        for hash in [
+            "sha256",
+            "sha256-!!",
+            "sha256-AAAA",
+            "sha1-Ry3o4T9ECVczn3oqKIOoAcvbjW0=",
+        ]
+        .iter()
+        {
+            assert!(hash.parse::<IntegrityHash>().is_err(), "{}", hash);
+        }
+    }
+
+    #[test]
+    fn test_verify() {
+        let integrity = SHA384.parse::<Integrity>().unwrap();
+        assert_eq!(integrity.verify(SCRIPT).unwrap(), Some(Algorithm::Sha384));
+        assert!(matches!(
+            integrity.verify(b"alert('Hello, world!');"),
+            Err(SriError::Mismatch(Algorithm::Sha384))
+        ));
+
+        // only the sha512 hashes are checked, and any one of them can match
+        let other = IntegrityHash::new(Algorithm::Sha512, b"other").unwrap();
+        let metadata = format!("{} {} {}", SHA384, other, SHA512);
+        let integrity = metadata.parse::<Integrity>().unwrap();
+        assert_eq!(integrity.verify(SCRIPT).unwrap(), Some(Algorithm::Sha512));
+        assert_eq!(integrity.verify(b"other").unwrap(), Some(Algorithm::Sha512));
+        let metadata = format!(
+            "sha256-qznLcsROx4GACP2dm0UCKCzCG+HiZ1guq6ZZDob/Tng= {}",
+            other
+        );
+        assert!(matches!(
+            metadata.parse::<Integrity>().unwrap().verify(SCRIPT),
+            Err(SriError::Mismatch(Algorithm::Sha512))
+        ));
+
+        // a broken sha512 hash still means sha512 is what's checked
+        let metadata = "sha512 sha256-qznLcsROx4GACP2dm0UCKCzCG+HiZ1guq6ZZDob/Tng=";
+        assert!(matches!(
+            metadata.parse::<Integrity>().unwrap().verify(SCRIPT),
+            Err(SriError::Mismatch(Algorithm::Sha512))
+        ));
+
+        // nothing usable, so nothing to check
+        let integrity = "sha1-Ry3o4T9ECVczn3oqKIOoAcvbjW0="
+            .parse::<Integrity>()
+            .unwrap();
+        assert!(integrity.is_empty());
+        assert_eq!(integrity.verify(SCRIPT).unwrap(), None);
+    }
+}
@@ -0,0 +1,641 @@
+/*
+
+Step by step traces of the hash computation, for following a message
+through the algorithm by hand. The trace is recorded with an observer (see
+crate::observer) and can be rendered as plain text, JSON, or a standalone
+HTML page which highlights the bits of the working variables that changed
+in each round.
+
+Words are widened to u64 so that one trace type covers both SHA-256 and
+SHA-512, word_bits says how many of those bits are significant. The
+truncated variants are traced as SHA-256 or SHA-512 from their own
+initial hash value, and digest_bits says how much of the final hash value
+is kept.
+
+*/
+
+use crate::algorithm::Algorithm;
+use crate::encoding;
+use crate::observer::Observer;
+use crate::{sha224, sha256, sha384, sha512, sha512_224, sha512_256};
+use std::fmt::Write;
+
+pub struct Trace {
+    pub algorithm: &'static str,
+    pub word_bits: usize,
+    // the leftmost bits of the final hash value which make the digest
+    pub digest_bits: usize,
+    pub message: Vec<u8>,
+    // the padded message, i.e. every block concatenated
+    pub padded: Vec<u8>,
+    pub blocks: Vec<BlockTrace>,
+    // the final hash value, before any truncation
+    pub hash: [u64; 8],
+}
+
+pub struct BlockTrace {
+    // the intermediate hash value going into the block
+    pub initial: [u64; 8],
+    pub schedule: Vec<u64>,
+    // the working variables A-H after each round
+    pub rounds: Vec<[u64; 8]>,
+    // the intermediate hash value after the final additions
+    pub hashes: [u64; 8],
+}
+
+#[derive(Default)]
+struct Recorder {
+    padded: Vec<u8>,
+    blocks: Vec<BlockTrace>,
+}
+
+impl Recorder {
+    fn current(&mut self) -> &mut BlockTrace {
+        self.blocks.last_mut().unwrap()
+    }
+
+    fn into_trace(self, algorithm: Algorithm, word_bits: usize, message: &[u8]) -> Trace {
+        Trace {
+            algorithm: algorithm.name(),
+            word_bits,
+            digest_bits: algorithm.output_size() * 8,
+            message: message.to_vec(),
+            hash: self.blocks.last().unwrap().hashes,
+            padded: self.padded,
+            blocks: self.blocks,
+        }
+    }
+}
+
+fn widen<W: Copy + Into<u64>>(words: &[W; 8]) -> [u64; 8] {
+    let mut wide = [0u64; 8];
+    for (w, word) in wide.iter_mut().zip(words.iter()) {
+        *w = (*word).into();
+    }
+    wide
+}
+
+impl<W: Copy + Into<u64>> Observer<W> for Recorder {
+    fn block(&mut self, block: &[u8], hashes: &[W; 8]) {
+        self.padded.extend_from_slice(block);
+        self.blocks.push(BlockTrace {
+            initial: widen(hashes),
+            schedule: Vec::new(),
+            rounds: Vec::new(),
+            hashes: [0; 8],
+        });
+    }
+
+    fn message_schedule(&mut self, schedule: &[W]) {
+        self.current().schedule = schedule.iter().map(|w| (*w).into()).collect();
+    }
+
+    fn round(&mut self, _t: usize, vars: &[W; 8]) {
+        let vars = widen(vars);
+        self.current().rounds.push(vars);
+    }
+
+    fn block_done(&mut self, hashes: &[W; 8]) {
+        self.current().hashes = widen(hashes);
+    }
+}
+
+// The trace of any of the algorithms, None for an empty message
+pub fn trace(algorithm: Algorithm, msg: &[u8]) -> Option<Trace> {
+    match algorithm {
+        Algorithm::Sha224 => trace_sha224(msg),
+        Algorithm::Sha256 => trace_sha256(msg),
+        Algorithm::Sha384 => trace_sha384(msg),
+        Algorithm::Sha512 => trace_sha512(msg),
+        Algorithm::Sha512_224 => trace_sha512_224(msg),
+        Algorithm::Sha512_256 => trace_sha512_256(msg),
+    }
+}
+
+pub fn trace_sha224(msg: &[u8]) -> Option<Trace> {
+    let mut recorder = Recorder::default();
+    sha224::hash_observed(msg, &mut recorder)?;
+    Some(recorder.into_trace(Algorithm::Sha224, 32, msg))
+}
+
+pub fn trace_sha256(msg: &[u8]) -> Option<Trace> {
+    let mut recorder = Recorder::default();
+    sha256::hash_observed(msg, &mut recorder)?;
+    Some(recorder.into_trace(Algorithm::Sha256, 32, msg))
+}
+
+pub fn trace_sha384(msg: &[u8]) -> Option<Trace> {
+    let mut recorder = Recorder::default();
+    sha384::hash_observed(msg, &mut recorder)?;
+    Some(recorder.into_trace(Algorithm::Sha384, 64, msg))
+}
+
+pub fn trace_sha512(msg: &[u8]) -> Option<Trace> {
+    let mut recorder = Recorder::default();
+    sha512::hash_observed(msg, &mut recorder)?;
+    Some(recorder.into_trace(Algorithm::Sha512, 64, msg))
+}
+
+pub fn trace_sha512_224(msg: &[u8]) -> Option<Trace> {
+    let mut recorder = Recorder::default();
+    sha512_224::hash_observed(msg, &mut recorder)?;
+    Some(recorder.into_trace(Algorithm::Sha512_224, 64, msg))
+}
+
+pub fn trace_sha512_256(msg: &[u8]) -> Option<Trace> {
+    let mut recorder = Recorder::default();
+    sha512_256::hash_observed(msg, &mut recorder)?;
+    Some(recorder.into_trace(Algorithm::Sha512_256, 64, msg))
+}
+
+const REGISTERS: [char; 8] = ['A', 'B', 'C', 'D', 'E', 'F', 'G', 'H'];
+
+impl Trace {
+    fn word_bytes(&self) -> usize {
+        self.word_bits / 8
+    }
+
+    fn block_bytes(&self) -> usize {
+        self.word_bytes() * 16
+    }
+
+    fn hex(&self, word: u64) -> String {
+        format!("{:0width$x}", word, width = self.word_bits / 4)
+    }
+
+    fn hex_hash(&self) -> String {
+        self.hash.iter().map(|w| self.hex(*w)).collect()
+    }
+
+    // the leftmost digest_bits of the final hash value
+    fn hex_digest(&self) -> String {
+        let mut hex = self.hex_hash();
+        hex.truncate(self.digest_bits / 4);
+        hex
+    }
+
+    fn is_truncated(&self) -> bool {
+        self.digest_bits < self.word_bits * 8
+    }
+
+    // the working variables going into round t, i.e. the values which the
+    // round changes
+    fn previous_vars(&self, block: &BlockTrace, t: usize) -> [u64; 8] {
+        if t == 0 {
+            block.initial
+        } else {
+            block.rounds[t - 1]
+        }
+    }
+
+    // the intermediate hash value going into the block, the working
+    // variables after the last round, and their sum
+    fn final_additions<'a>(
+        &self,
+        block: &'a BlockTrace,
+    ) -> impl Iterator<Item = (u64, u64, u64)> + 'a {
+        let last = block.rounds.last().unwrap();
+        (0..8).map(move |j| (block.initial[j], last[j], block.hashes[j]))
+    }
+
+    // which part of the padded message a bit belongs to: the message itself,
+    // the single 1 bit, the 0 padding or the message length
+    fn padding_part(&self, bit: usize) -> &'static str {
+        let msg_bits = self.message.len() * 8;
+        let length_bits = self.word_bits * 2;
+        if bit < msg_bits {
+            "message"
+        } else if bit == msg_bits {
+            "one"
+        } else if bit < self.padded.len() * 8 - length_bits {
+            "zero"
+        } else {
+            "length"
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        let msg_bits = self.message.len() * 8;
+        let num_blocks = self.blocks.len();
+
+        writeln!(out, "{} trace", self.algorithm).unwrap();
+        writeln!(out).unwrap();
+        writeln!(out, "1. Preprocessing").unwrap();
+        writeln!(out).unwrap();
+        writeln!(
+            out,
+            "Message ({} bits): {}",
+            msg_bits,
+            hex_bytes(&self.message)
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "Padding: a single 1 bit, {} 0 bits and the message length as a {} bit integer",
+            self.padded.len() * 8 - msg_bits - 1 - self.word_bits * 2,
+            self.word_bits * 2
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "Padded message ({} bits, {} block{}):",
+            self.padded.len() * 8,
+            num_blocks,
+            if num_blocks == 1 { "" } else { "s" }
+        )
+        .unwrap();
+        for line in self.padded.chunks(8) {
+            let bits: Vec<String> = line.iter().map(|b| format!("{:08b}", b)).collect();
+            writeln!(out, "  {}", bits.join(" ")).unwrap();
+        }
+
+        for (i, block) in self.blocks.iter().enumerate() {
+            let bytes = &self.padded[i * self.block_bytes()..(i + 1) * self.block_bytes()];
+            writeln!(out).unwrap();
+            writeln!(out, "Block {} parsed into 16 words:", i + 1).unwrap();
+            for (j, word) in bytes.chunks(self.word_bytes()).enumerate() {
+                writeln!(out, "  M{}[{:>2}] = {}", i + 1, j, hex_bytes(word)).unwrap();
+            }
+
+            writeln!(out).unwrap();
+            writeln!(out, "2. Hash computation, block {}", i + 1).unwrap();
+            writeln!(out).unwrap();
+            writeln!(out, "Message schedule:").unwrap();
+            for (t, w) in block.schedule.iter().enumerate() {
+                writeln!(out, "  W[{:>2}] = {}", t, self.hex(*w)).unwrap();
+            }
+
+            writeln!(out).unwrap();
+            writeln!(out, "Working variables:").unwrap();
+            writeln!(out, "  init: {}", self.registers(&block.initial)).unwrap();
+            for (t, vars) in block.rounds.iter().enumerate() {
+                writeln!(out, "  t={:>2}: {}", t, self.registers(vars)).unwrap();
+            }
+
+            writeln!(out).unwrap();
+            writeln!(out, "Final additions:").unwrap();
+            for (j, (initial, last, hash)) in self.final_additions(block).enumerate() {
+                writeln!(
+                    out,
+                    "  H{}[{}] = {} + {} = {}",
+                    i + 1,
+                    j,
+                    self.hex(initial),
+                    self.hex(last),
+                    self.hex(hash)
+                )
+                .unwrap();
+            }
+        }
+
+        writeln!(out).unwrap();
+        if self.is_truncated() {
+            writeln!(out, "Final hash value: {}", self.hex_hash()).unwrap();
+            writeln!(out, "Truncated to the leftmost {} bits", self.digest_bits).unwrap();
+        }
+        writeln!(out, "Hash: {}", self.hex_digest()).unwrap();
+        out
+    }
+
+    fn registers(&self, vars: &[u64; 8]) -> String {
+        let regs: Vec<String> = REGISTERS
+            .iter()
+            .zip(vars.iter())
+            .map(|(r, v)| format!("{}={}", r, self.hex(*v)))
+            .collect();
+        regs.join(" ")
+    }
+
+    pub fn to_json(&self) -> String {
+        let words = |ws: &[u64]| -> String {
+            let ws: Vec<String> = ws.iter().map(|w| format!("\"{}\"", self.hex(*w))).collect();
+            format!("[{}]", ws.join(","))
+        };
+
+        let mut out = String::new();
+        write!(
+            out,
+            "{{\"algorithm\":\"{}\",\"word_bits\":{},\"digest_bits\":{},\"message\":\"{}\",\"message_bits\":{},\"padded\":\"{}\",\"blocks\":[",
+            self.algorithm,
+            self.word_bits,
+            self.digest_bits,
+            encoding::hex(&self.message),
+            self.message.len() * 8,
+            encoding::hex(&self.padded)
+        )
+        .unwrap();
+        for (i, block) in self.blocks.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let bytes = &self.padded[i * self.block_bytes()..(i + 1) * self.block_bytes()];
+            let parsed: Vec<String> = bytes
+                .chunks(self.word_bytes())
+                .map(|w| format!("\"{}\"", encoding::hex(w)))
+                .collect();
+            write!(
+                out,
+                "{{\"words\":[{}],\"schedule\":{},\"initial\":{},\"rounds\":[",
+                parsed.join(","),
+                words(&block.schedule),
+                words(&block.initial)
+            )
+            .unwrap();
+            for (t, vars) in block.rounds.iter().enumerate() {
+                if t > 0 {
+                    out.push(',');
+                }
+                let previous = self.previous_vars(block, t);
+                let changed: Vec<u64> = (0..8).map(|j| vars[j] ^ previous[j]).collect();
+                write!(
+                    out,
+                    "{{\"t\":{},\"vars\":{},\"changed\":{}}}",
+                    t,
+                    words(vars),
+                    words(&changed)
+                )
+                .unwrap();
+            }
+            write!(out, "],\"hashes\":{}}}", words(&block.hashes)).unwrap();
+        }
+        write!(out, "],\"hash\":\"{}\"}}", self.hex_digest()).unwrap();
+        out
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut out = String::new();
+        out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        writeln!(out, "<title>{} trace</title>", self.algorithm).unwrap();
+        out.push_str(
+            "<style>\n\
+             body { font-family: sans-serif; margin: 2em; }\n\
+             table { border-collapse: collapse; margin-bottom: 1em; }\n\
+             td, th { padding: 0.1em 0.6em; text-align: left; }\n\
+             .bits, td.word { font-family: monospace; }\n\
+             .message { color: #1f4e9c; }\n\
+             .one { color: #c0392b; font-weight: bold; }\n\
+             .zero { color: #999; }\n\
+             .length { color: #1e8449; }\n\
+             .changed { background: #f9e79f; }\n\
+             </style>\n</head>\n<body>\n",
+        );
+        writeln!(out, "<h1>{} trace</h1>", self.algorithm).unwrap();
+
+        out.push_str("<h2>1. Preprocessing</h2>\n");
+        writeln!(
+            out,
+            "<p>Message ({} bits): <span class=\"bits\">{}</span></p>",
+            self.message.len() * 8,
+            hex_bytes(&self.message)
+        )
+        .unwrap();
+        out.push_str(
+            "<p>Padded message: <span class=\"message\">message</span>, \
+             <span class=\"one\">1 bit</span>, <span class=\"zero\">0 bits</span>, \
+             <span class=\"length\">length</span></p>\n<div class=\"bits\">\n",
+        );
+        for (i, byte) in self.padded.iter().enumerate() {
+            for j in 0..8 {
+                let bit = i * 8 + j;
+                write!(
+                    out,
+                    "<span class=\"{}\">{}</span>",
+                    self.padding_part(bit),
+                    (byte >> (7 - j)) & 1
+                )
+                .unwrap();
+            }
+            out.push_str(if i % 8 == 7 { "<br>\n" } else { " " });
+        }
+        out.push_str("</div>\n");
+
+        for (i, block) in self.blocks.iter().enumerate() {
+            let bytes = &self.padded[i * self.block_bytes()..(i + 1) * self.block_bytes()];
+            writeln!(out, "<h2>2. Hash computation, block {}</h2>", i + 1).unwrap();
+
+            out.push_str("<h3>Message schedule</h3>\n<table>\n");
+            for (t, w) in block.schedule.iter().enumerate() {
+                let source = if t < 16 {
+                    format!(
+                        "M{}[{}] = {}",
+                        i + 1,
+                        t,
+                        hex_bytes(&bytes[t * self.word_bytes()..(t + 1) * self.word_bytes()])
+                    )
+                } else {
+                    format!(
+                        "&sigma;1(W[{}]) + W[{}] + &sigma;0(W[{}]) + W[{}]",
+                        t - 2,
+                        t - 7,
+                        t - 15,
+                        t - 16
+                    )
+                };
+                writeln!(
+                    out,
+                    "<tr><th>W[{}]</th><td class=\"word\">{}</td><td>{}</td></tr>",
+                    t,
+                    self.hex(*w),
+                    source
+                )
+                .unwrap();
+            }
+            out.push_str("</table>\n");
+
+            out.push_str("<h3>Working variables</h3>\n<table>\n<tr><th>t</th>");
+            for r in REGISTERS.iter() {
+                write!(out, "<th>{}</th>", r).unwrap();
+            }
+            out.push_str("</tr>\n<tr><th>init</th>");
+            for v in block.initial.iter() {
+                write!(out, "<td class=\"word\">{}</td>", self.hex(*v)).unwrap();
+            }
+            out.push_str("</tr>\n");
+            for (t, vars) in block.rounds.iter().enumerate() {
+                let previous = self.previous_vars(block, t);
+                write!(out, "<tr><th>{}</th>", t).unwrap();
+                for j in 0..8 {
+                    write!(
+                        out,
+                        "<td class=\"word\" title=\"{}\">{}</td>",
+                        self.hex(vars[j]),
+                        self.highlight_changes(vars[j], previous[j])
+                    )
+                    .unwrap();
+                }
+                out.push_str("</tr>\n");
+            }
+            out.push_str("</table>\n");
+
+            out.push_str("<h3>Final additions</h3>\n<table>\n");
+            for (j, (initial, last, hash)) in self.final_additions(block).enumerate() {
+                writeln!(
+                    out,
+                    "<tr><th>H{}[{}]</th><td class=\"word\">{} + {} = {}</td></tr>",
+                    i + 1,
+                    j,
+                    self.hex(initial),
+                    self.hex(last),
+                    self.hex(hash)
+                )
+                .unwrap();
+            }
+            out.push_str("</table>\n");
+        }
+
+        out.push_str("<h2>Hash</h2>\n");
+        if self.is_truncated() {
+            writeln!(
+                out,
+                "<p>Final hash value: <span class=\"bits\">{}</span>, truncated to the leftmost {} bits</p>",
+                self.hex_hash(),
+                self.digest_bits
+            )
+            .unwrap();
+        }
+        writeln!(
+            out,
+            "<p class=\"bits\">{}</p>\n</body>\n</html>",
+            self.hex_digest()
+        )
+        .unwrap();
+        out
+    }
+
+    // the word in binary, with runs of bits which differ from the previous
+    // value wrapped in a highlighted span
+    fn highlight_changes(&self, word: u64, previous: u64) -> String {
+        let changed = word ^ previous;
+        let mut out = String::new();
+        let mut in_run = false;
+        for i in (0..self.word_bits).rev() {
+            let is_changed = (changed >> i) & 1 == 1;
+            if is_changed && !in_run {
+                out.push_str("<span class=\"changed\">");
+            } else if !is_changed && in_run {
+                out.push_str("</span>");
+            }
+            in_run = is_changed;
+            out.push(if (word >> i) & 1 == 1 { '1' } else { '0' });
+        }
+        if in_run {
+            out.push_str("</span>");
+        }
+        out
+    }
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+    let bytes: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    bytes.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding;
+
+    #[test]
+    fn test_trace() {
+        let trace = super::trace_sha256(b"abc").unwrap();
+        assert_eq!(trace.padded.len(), 64);
+        assert_eq!(trace.blocks.len(), 1);
+        assert_eq!(trace.blocks[0].rounds.len(), 64);
+        assert_eq!(trace.blocks[0].hashes, trace.hash);
+
+        let text = trace.to_text();
+        assert!(text.contains("Message (24 bits): 61 62 63"));
+        assert!(text.contains("  W[ 0] = 61626380"));
+        assert!(text.contains(
+            "  t= 0: A=5d6aebcd B=6a09e667 C=bb67ae85 D=3c6ef372 E=fa2a4622 F=510e527f G=9b05688c H=1f83d9ab"
+        ));
+        assert!(text.contains("  H1[0] = 6a09e667 + 506e3058 = ba7816bf"));
+        assert!(
+            text.contains("Hash: ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+
+        let json = trace.to_json();
+        assert!(
+            json.starts_with("{\"algorithm\":\"SHA-256\",\"word_bits\":32,\"digest_bits\":256,\"message\":\"616263\"")
+        );
+        assert!(json.contains("{\"t\":0,\"vars\":[\"5d6aebcd\",\"6a09e667\""));
+        assert!(json.ends_with(
+            "\"hash\":\"ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad\"}"
+        ));
+
+        let html = trace.to_html();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<span class=\"one\">1</span>"));
+        assert!(html.ends_with("</html>\n"));
+    }
+
+    #[test]
+    fn test_trace_sha512_multiple_blocks() {
+        let trace = super::trace_sha512(&[b'a'; 200]).unwrap();
+        assert_eq!(trace.word_bits, 64);
+        assert_eq!(trace.blocks.len(), 2);
+        assert_eq!(trace.blocks[1].initial, trace.blocks[0].hashes);
+        assert_eq!(Some(trace.hash.into()), crate::sha512::hash(&[b'a'; 200]));
+        assert!(trace
+            .to_text()
+            .contains("Padded message (2048 bits, 2 blocks):"));
+    }
+
+    #[test]
+    fn test_trace_truncated() {
+        let trace = super::trace_sha224(b"abc").unwrap();
+        assert_eq!((trace.word_bits, trace.digest_bits), (32, 224));
+        let text = trace.to_text();
+        assert!(text.starts_with("SHA-224 trace\n"));
+        assert!(text.contains(
+            "  init: A=c1059ed8 B=367cd507 C=3070dd17 D=f70e5939 E=ffc00b31 F=68581511 G=64f98fa7 H=befa4fa4"
+        ));
+        assert!(text.contains("Truncated to the leftmost 224 bits\n"));
+        assert!(text.ends_with("Hash: 23097d223405d8228642a477bda255b32aadbce4bda0b3f7e36c9da7\n"));
+        let json = trace.to_json();
+        assert!(
+            json.starts_with("{\"algorithm\":\"SHA-224\",\"word_bits\":32,\"digest_bits\":224,")
+        );
+        assert!(json
+            .ends_with("\"hash\":\"23097d223405d8228642a477bda255b32aadbce4bda0b3f7e36c9da7\"}"));
+        assert!(trace
+            .to_html()
+            .contains("truncated to the leftmost 224 bits"));
+
+        // half a word is kept from the fourth
+        let trace = super::trace(crate::algorithm::Algorithm::Sha512_224, b"abc").unwrap();
+        assert_eq!(trace.algorithm, "SHA-512/224");
+        assert!(trace
+            .to_json()
+            .ends_with("\"hash\":\"4634270f707b6a54daae7530460842e20e37ed265ceee9a43e8924aa\"}"));
+
+        for (algorithm, trace) in [
+            (
+                crate::algorithm::Algorithm::Sha384,
+                super::trace_sha384(b"abc"),
+            ),
+            (
+                crate::algorithm::Algorithm::Sha512_256,
+                super::trace_sha512_256(b"abc"),
+            ),
+        ]
+        .iter()
+        {
+            let trace = trace.as_ref().unwrap();
+            assert_eq!(
+                trace.to_text().lines().last().unwrap(),
+                format!("Hash: {}", encoding::hex(&algorithm.hash(b"abc")))
+            );
+        }
+        assert!(super::trace_sha224(b"").is_none());
+    }
+
+    #[test]
+    fn test_highlight_changes() {
+        let trace = super::trace_sha256(b"abc").unwrap();
+        assert_eq!(
+            trace.highlight_changes(0b1011, 0b0110),
+            "0000000000000000000000000000<span class=\"changed\">10</span>1<span class=\"changed\">1</span>"
+        );
+    }
+}
@@ -0,0 +1,134 @@
+/*
+
+The truncated SHA-2 variants: SHA-224, SHA-384, SHA-512/224 and
+SHA-512/256. Each one is SHA-256 or SHA-512 started from its own initial
+hash value (see 5.3.2, 5.3.4 and 5.3.6), with the leftmost bits of the
+final hash value kept as the digest, so the modules are thin wrappers
+around crate::sha256 and crate::sha512 generated by the macro below.
+
+*/
+
+macro_rules! truncated {
+    (
+        $hasher:ident,
+        $inner:ident,
+        $module:ident,
+        $word:ty,
+        $digest:ident,
+        $inner_digest:ident,
+        $init:expr
+    ) => {
+        use crate::digest::{$digest, $inner_digest};
+        use crate::observer::{NoObserver, Observer};
+        use crate::$module::{self, $inner};
+
+        const INIT: [$word; 8] = $init;
+
+        fn truncate(digest: $inner_digest) -> $digest {
+            let mut bytes = [0u8; $digest::LEN];
+            bytes.copy_from_slice(&digest.as_bytes()[..$digest::LEN]);
+            $digest::from_bytes(bytes)
+        }
+
+        pub fn hash(msg: &[u8]) -> Option<$digest> {
+            hash_observed(msg, &mut NoObserver)
+        }
+
+        // hash, calling back into the observer during the hash computation,
+        // see crate::observer
+        pub fn hash_observed<O: Observer<$word>>(msg: &[u8], observer: &mut O) -> Option<$digest> {
+            $module::hash_from(INIT, msg, observer).map(truncate)
+        }
+
+        // streaming, with the underlying hasher doing all the work
+        #[derive(Clone)]
+        pub struct $hasher($inner);
+
+        impl $hasher {
+            pub fn new() -> $hasher {
+                $hasher($inner::with_init(INIT))
+            }
+
+            pub fn update(&mut self, msg: &[u8]) {
+                self.0.update(msg)
+            }
+
+            pub fn finalize(self) -> $digest {
+                truncate(self.0.finalize())
+            }
+
+            pub fn reset(&mut self) {
+                *self = $hasher::new();
+            }
+        }
+
+        // wiping and dropping are handled by the underlying hasher
+        #[cfg(feature = "zeroize")]
+        impl zeroize::Zeroize for $hasher {
+            fn zeroize(&mut self) {
+                zeroize::Zeroize::zeroize(&mut self.0);
+            }
+        }
+
+        // the serialized state of the underlying hasher, with this initial
+        // hash value in it so that it can't be resumed as another algorithm
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for $hasher {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                self.0.serialize_with_init(INIT, serializer)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de> serde::Deserialize<'de> for $hasher {
+            fn deserialize<D: serde::Deserializer<'de>>(
+                deserializer: D,
+            ) -> Result<$hasher, D::Error> {
+                $inner::deserialize_with_init(INIT, deserializer).map($hasher)
+            }
+        }
+
+        impl Default for $hasher {
+            fn default() -> $hasher {
+                $hasher::new()
+            }
+        }
+
+        impl crate::StreamHasher for $hasher {
+            type Output = $digest;
+
+            fn update(&mut self, msg: &[u8]) {
+                $hasher::update(self, msg)
+            }
+
+            fn finalize(self) -> $digest {
+                $hasher::finalize(self)
+            }
+        }
+
+        // hash a file, memory-mapping it where possible, see crate::file
+        pub fn hash_file<P: AsRef<std::path::Path>>(
+            path: P,
+        ) -> Result<$digest, crate::file::FileError> {
+            crate::file::hash_file(path.as_ref(), $hasher::new())
+        }
+
+        // hash each of the messages on the rayon thread pool, the hashes are
+        // returned in the same order as the messages
+        #[cfg(feature = "rayon")]
+        pub fn hash_batch<T: AsRef<[u8]> + Sync>(msgs: &[T]) -> Vec<Option<$digest>> {
+            use rayon::prelude::*;
+            msgs.par_iter().map(|msg| hash(msg.as_ref())).collect()
+        }
+
+        // hash each of the files on the rayon thread pool, the results are
+        // returned in the same order as the paths
+        #[cfg(feature = "rayon")]
+        pub fn hash_files<P: AsRef<std::path::Path> + Sync>(
+            paths: &[P],
+        ) -> Vec<Result<$digest, crate::file::FileError>> {
+            use rayon::prelude::*;
+            paths.par_iter().map(hash_file).collect()
+        }
+    };
+}
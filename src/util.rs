@@ -2,6 +2,10 @@
 // modules within the *same* crate - without making them globally public
 //
 // Functions to be used during the hash computation
+//
+// The rotations are spelled out as they are defined in section 3.2 of
+// FIPS 180-4, rather than using the rotate_left/rotate_right intrinsics
+#![allow(clippy::manual_rotate)]
 
 pub fn rotl64(n: u64, x: u64) -> u64 {
     (x << n) | (x >> (64 - n))
@@ -22,3 +26,17 @@ pub fn rotr32(n: u32, x: u32) -> u32 {
 pub fn shr32(n: u32, x: u32) -> u32 {
     x >> n
 }
+
+// compare two byte strings in time which only depends on their lengths,
+// so that comparing against a secret value such as a MAC doesn't leak how
+// many leading bytes matched
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut diff = 0u8;
+    for (x, y) in a.iter().zip(b.iter()) {
+        diff |= x ^ y;
+    }
+    std::hint::black_box(diff) == 0
+}
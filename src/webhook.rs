@@ -0,0 +1,361 @@
+/*
+
+Verifying the HMAC-SHA-256 signatures which webhook senders put in a
+header, each framing what's MACed a little differently:
+
+    GitHub  X-Hub-Signature-256: sha256=hex(HMAC(secret, body))
+    Stripe  Stripe-Signature: t=<time>,v1=hex(HMAC(secret, "<time>." || body))
+    Slack   X-Slack-Signature: v0=hex(HMAC(secret, "v0:<time>:" || body))
+            X-Slack-Request-Timestamp: <time>
+
+The body has to be the exact bytes received, before any parsing. The
+signatures are compared in constant time.
+
+Stripe and Slack sign the time too, in unix seconds, so that a request
+which has been captured can't be replayed later. The signature is
+checked first, so Stale means a genuine request from outside the
+tolerance, either way, of the current time, rather than a forgery.
+
+*/
+
+use crate::algorithm::Algorithm;
+use crate::encoding;
+use crate::hmac::{hmac, Hmac};
+use std::error::Error;
+use std::fmt;
+
+// five minutes, which Stripe and Slack both use
+pub const DEFAULT_TOLERANCE: u64 = 300;
+
+#[derive(Debug, PartialEq)]
+pub enum WebhookError {
+    // a header which isn't in the sender's format, or lacks a signature
+    Malformed,
+    InvalidSignature,
+    Stale,
+}
+
+impl fmt::Display for WebhookError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WebhookError::Malformed => f.write_str("malformed signature header"),
+            WebhookError::InvalidSignature => f.write_str("invalid signature"),
+            WebhookError::Stale => f.write_str("the timestamp is outside the tolerance"),
+        }
+    }
+}
+
+impl Error for WebhookError {}
+
+// the X-Hub-Signature-256 header for the body
+pub fn sign_github(secret: &[u8], body: &[u8]) -> String {
+    format!(
+        "sha256={}",
+        encoding::hex(&hmac(Algorithm::Sha256, secret, body))
+    )
+}
+
+pub fn verify_github(secret: &[u8], header: &str, body: &[u8]) -> Result<(), WebhookError> {
+    let signature = header
+        .trim()
+        .strip_prefix("sha256=")
+        .ok_or(WebhookError::Malformed)?;
+    let signature = encoding::decode_hex(signature).map_err(|_| WebhookError::Malformed)?;
+    if !mac(secret, &[body]).verify(&signature) {
+        return Err(WebhookError::InvalidSignature);
+    }
+    Ok(())
+}
+
+// the Stripe-Signature header for the body sent at the time
+pub fn sign_stripe(secret: &[u8], body: &[u8], timestamp: u64) -> String {
+    let timestamp = timestamp.to_string();
+    let mac = mac(secret, &[timestamp.as_bytes(), b".", body]).finalize();
+    format!("t={},v1={}", timestamp, encoding::hex(&mac))
+}
+
+// Any of the v1 signatures may match, as there's one for each secret
+// while they're being rolled. Other schemes, such as the v0 of test
+// mode, are ignored.
+pub fn verify_stripe(
+    secret: &[u8],
+    header: &str,
+    body: &[u8],
+    now: u64,
+    tolerance: u64,
+) -> Result<(), WebhookError> {
+    let mut timestamp = None;
+    let mut signatures = Vec::new();
+    for item in header.split(',') {
+        let mut parts = item.trim().splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some("t"), Some(t)) if timestamp.is_none() => timestamp = Some(t),
+            (Some("t"), _) => return Err(WebhookError::Malformed),
+            (Some("v1"), Some(signature)) => signatures.push(signature),
+            (Some(_), Some(_)) => {}
+            _ => return Err(WebhookError::Malformed),
+        }
+    }
+    let timestamp = timestamp.ok_or(WebhookError::Malformed)?;
+    let time = parse_timestamp(timestamp)?;
+    if signatures.is_empty() {
+        return Err(WebhookError::Malformed);
+    }
+
+    let primed = mac(secret, &[timestamp.as_bytes(), b".", body]);
+    // every signature is compared, so the time taken doesn't say which
+    // matched
+    let matched = signatures.iter().fold(false, |matched, signature| {
+        let valid = match encoding::decode_hex(signature) {
+            Ok(signature) => primed.clone().verify(&signature),
+            Err(_) => false,
+        };
+        matched | valid
+    });
+    if !matched {
+        return Err(WebhookError::InvalidSignature);
+    }
+    check_time(time, now, tolerance)
+}
+
+// the X-Slack-Signature header for the body sent at the time, which goes
+// in X-Slack-Request-Timestamp
+pub fn sign_slack(secret: &[u8], body: &[u8], timestamp: u64) -> String {
+    let timestamp = timestamp.to_string();
+    let mac = mac(secret, &[b"v0:", timestamp.as_bytes(), b":", body]).finalize();
+    format!("v0={}", encoding::hex(&mac))
+}
+
+pub fn verify_slack(
+    secret: &[u8],
+    signature_header: &str,
+    timestamp_header: &str,
+    body: &[u8],
+    now: u64,
+    tolerance: u64,
+) -> Result<(), WebhookError> {
+    let timestamp = timestamp_header.trim();
+    let time = parse_timestamp(timestamp)?;
+    let signature = signature_header
+        .trim()
+        .strip_prefix("v0=")
+        .ok_or(WebhookError::Malformed)?;
+    let signature = encoding::decode_hex(signature).map_err(|_| WebhookError::Malformed)?;
+    if !mac(secret, &[b"v0:", timestamp.as_bytes(), b":", body]).verify(&signature) {
+        return Err(WebhookError::InvalidSignature);
+    }
+    check_time(time, now, tolerance)
+}
+
+fn mac(secret: &[u8], parts: &[&[u8]]) -> Hmac {
+    let mut mac = Hmac::new(Algorithm::Sha256, secret);
+    for part in parts.iter() {
+        mac.update(part);
+    }
+    mac
+}
+
+// decimal digits only, as what's signed is the text of the timestamp
+fn parse_timestamp(timestamp: &str) -> Result<u64, WebhookError> {
+    if timestamp.is_empty() || !timestamp.bytes().all(|c| c.is_ascii_digit()) {
+        return Err(WebhookError::Malformed);
+    }
+    timestamp.parse().map_err(|_| WebhookError::Malformed)
+}
+
+fn check_time(time: u64, now: u64, tolerance: u64) -> Result<(), WebhookError> {
+    if time.max(now) - time.min(now) > tolerance {
+        return Err(WebhookError::Stale);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the example in GitHub's documentation
+    #[test]
+    fn test_github() {
+        let secret = b"It's a Secret to Everybody";
+        let header = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+        assert_eq!(sign_github(secret, b"Hello, World!"), header);
+        assert_eq!(verify_github(secret, header, b"Hello, World!"), Ok(()));
+        assert_eq!(
+            verify_github(
+                secret,
+                &header.to_uppercase().replace("SHA256", "sha256"),
+                b"Hello, World!"
+            ),
+            Ok(())
+        );
+
+        assert_eq!(
+            verify_github(secret, header, b"Hello, World?"),
+            Err(WebhookError::InvalidSignature)
+        );
+        assert_eq!(
+            verify_github(secret, &header[..header.len() - 2], b"Hello, World!"),
+            Err(WebhookError::InvalidSignature)
+        );
+        for header in [
+            "757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17",
+            "sha1=757107ea0eb2509fc211221cce984b8a37570b6d",
+            "sha256=xyz",
+        ]
+        .iter()
+        {
+            assert_eq!(
+                verify_github(secret, header, b"Hello, World!"),
+                Err(WebhookError::Malformed)
+            );
+        }
+    }
+
+    // checked against Python's hmac module, as Stripe doesn't publish one
+    #[test]
+    fn test_stripe() {
+        let secret = b"whsec_test_secret";
+        let body = br#"{"id":"evt_test","object":"event"}"#;
+        let signature = "691252e266ce41cb94d709c84e9580d4172b117a510bbc81723f657d2cd5d215";
+        let header = format!("t=1492774577,v1={}", signature);
+        assert_eq!(sign_stripe(secret, body, 1492774577), header);
+        assert_eq!(
+            verify_stripe(secret, &header, body, 1492774577, 300),
+            Ok(())
+        );
+
+        // a rolled secret and a test mode signature alongside
+        let header = format!(
+            "t=1492774577,v1={},v1=00, v0=1234,v1={}",
+            "ab".repeat(32),
+            signature
+        );
+        assert_eq!(
+            verify_stripe(secret, &header, body, 1492774600, 300),
+            Ok(())
+        );
+
+        assert_eq!(
+            verify_stripe(secret, &header, body, 1492774577 + 301, 300),
+            Err(WebhookError::Stale)
+        );
+        assert_eq!(
+            verify_stripe(secret, &header, body, 1492774577 - 301, 300),
+            Err(WebhookError::Stale)
+        );
+        assert_eq!(
+            verify_stripe(b"whsec_other", &header, body, 1492774577, 300),
+            Err(WebhookError::InvalidSignature)
+        );
+        // the time is signed, so changing it breaks the signature
+        assert_eq!(
+            verify_stripe(
+                secret,
+                &format!("t=1492774578,v1={}", signature),
+                body,
+                1492774578,
+                300
+            ),
+            Err(WebhookError::InvalidSignature)
+        );
+
+        for header in [
+            "",
+            "v1=691252e266ce41cb94d709c84e9580d4172b117a510bbc81723f657d2cd5d215",
+            "t=1492774577",
+            "t=1492774577,v0=1234",
+            "t=-1,v1=00",
+            "t=1,t=2,v1=00",
+            "t=1492774577,v1",
+        ]
+        .iter()
+        {
+            assert_eq!(
+                verify_stripe(secret, header, body, 1492774577, 300),
+                Err(WebhookError::Malformed)
+            );
+        }
+    }
+
+    // the example in Slack's documentation
+    #[test]
+    fn test_slack() {
+        let secret = b"8f742231b10e8888abcd99yyyzzz85a5";
+        let body = b"token=xyzz0WbapA4vBCDEFasx0q6G&team_id=T1DC2JH3J&team_domain=testteamnow\
+                     &channel_id=G8PSS9T3V&channel_name=foobar&user_id=U2CERLKJA\
+                     &user_name=roadrunner&command=%2Fwebhook-collect&text=\
+                     &response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2FT1DC2JH3J\
+                     %2F397700885554%2F96rGlfmibIGlgcZRskXaIFfN\
+                     &trigger_id=398738663015.47445629121.803a0bc887a14d10d2c447fce8b6703c";
+        let header = "v0=a2114d57b48eac39b9ad189dd8316235a7b4a8d21a10bd27519666489c69b503";
+        assert_eq!(sign_slack(secret, body, 1531420618), header);
+        assert_eq!(
+            verify_slack(
+                secret,
+                header,
+                "1531420618",
+                body,
+                1531420618,
+                DEFAULT_TOLERANCE
+            ),
+            Ok(())
+        );
+
+        assert_eq!(
+            verify_slack(
+                secret,
+                header,
+                "1531420618",
+                body,
+                1531420618 + 3600,
+                DEFAULT_TOLERANCE
+            ),
+            Err(WebhookError::Stale)
+        );
+        assert_eq!(
+            verify_slack(
+                secret,
+                header,
+                "1531420619",
+                body,
+                1531420618,
+                DEFAULT_TOLERANCE
+            ),
+            Err(WebhookError::InvalidSignature)
+        );
+        assert_eq!(
+            verify_slack(
+                secret,
+                header,
+                "1531420618",
+                &body[1..],
+                1531420618,
+                DEFAULT_TOLERANCE
+            ),
+            Err(WebhookError::InvalidSignature)
+        );
+        assert_eq!(
+            verify_slack(
+                secret,
+                &header[3..],
+                "1531420618",
+                body,
+                1531420618,
+                DEFAULT_TOLERANCE
+            ),
+            Err(WebhookError::Malformed)
+        );
+        assert_eq!(
+            verify_slack(
+                secret,
+                header,
+                "+1531420618",
+                body,
+                1531420618,
+                DEFAULT_TOLERANCE
+            ),
+            Err(WebhookError::Malformed)
+        );
+    }
+}